@@ -1,3 +1,17 @@
 fn main() {
     slint_build::compile("src/main.slint").expect("failed to compile Slint UI");
+
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc for this host");
+    let mut config = prost_build::Config::new();
+    config.protoc_executable(protoc);
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_with_config(config, &["proto/compress_img.proto"], &["proto"])
+        .expect("failed to compile protobuf definitions");
 }
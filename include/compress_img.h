@@ -0,0 +1,37 @@
+/* compress_img C API，需要以 `--features ffi` 构建 cdylib。 */
+#ifndef COMPRESS_IMG_H
+#define COMPRESS_IMG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct CiBuffer {
+    uint8_t *data;
+    size_t len;
+} CiBuffer;
+
+/* 以下函数成功返回 0，失败返回 -1，错误信息见 compress_img_last_error()。
+ * options_json 为 NULL 时使用默认设置，格式与程序导出的配置文件相同。 */
+
+/* 压缩内存中的图像，开启转换时输出为转换的格式，否则与输入相同；out 需用 compress_img_free_buffer 释放。 */
+int32_t compress_img_compress_buffer(const uint8_t *input, size_t input_len,
+                                     const char *options_json, CiBuffer *out);
+
+/* 原地压缩文件，original_size / new_size 可以为 NULL。 */
+int32_t compress_img_compress_file(const char *path, const char *options_json,
+                                   uint64_t *original_size, uint64_t *new_size);
+
+void compress_img_free_buffer(CiBuffer buffer);
+
+/* 当前线程最近一次失败的错误信息（UTF-8），无错误时为 NULL。 */
+const char *compress_img_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* COMPRESS_IMG_H */
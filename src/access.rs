@@ -0,0 +1,150 @@
+//! 服务模式的访问控制。接口可以提交任意文件夹和完整的设置，能改写甚至删除图像，
+//! 所以只接受允许的根文件夹内的任务，并可要求每个请求携带令牌。
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::options::CompressionOptions;
+
+#[derive(Clone, Debug, Default)]
+pub struct AccessPolicy {
+    /// 规范化后的根文件夹；为空时拒绝所有任务
+    roots: Vec<PathBuf>,
+    token: Option<String>,
+}
+
+impl AccessPolicy {
+    /// roots 中的文件夹必须存在
+    pub fn new(roots: &[PathBuf], token: Option<String>) -> Result<Self> {
+        let roots = roots
+            .iter()
+            .map(|root| {
+                root.canonicalize()
+                    .with_context(|| format!("无法访问允许的文件夹: {}", root.display()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            roots,
+            token: token.filter(|token| !token.is_empty()),
+        })
+    }
+
+    pub fn requires_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// 没有设置令牌时总是通过；比较耗时与令牌内容无关
+    pub fn token_matches(&self, provided: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let provided = provided.unwrap_or_default().as_bytes();
+        provided.len() == token.len()
+            && provided
+                .iter()
+                .zip(token.as_bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    /// 任务会读写的位置（源文件夹、输出文件夹、备份位置）都要在允许的根文件夹内
+    pub fn check_job(&self, folder: &Path, options: &CompressionOptions) -> Result<()> {
+        self.check_path(folder)?;
+        if let Some(output) = &options.output.folder {
+            self.check_path(output)?;
+        }
+        if let Some(backup) = options.backup.location(folder) {
+            self.check_path(&backup)?;
+        }
+        // 副本与原文件同名加后缀，后缀中有路径分隔符就能写到别处
+        if options.output.keep_both_suffix.contains(['/', '\\']) {
+            bail!("副本后缀不能包含路径分隔符");
+        }
+        if options.scan.follow_symlinks {
+            bail!("服务接口提交的任务不能跟随符号链接");
+        }
+        Ok(())
+    }
+
+    fn check_path(&self, path: &Path) -> Result<()> {
+        let resolved = resolve(path)?;
+        if self.roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(())
+        } else {
+            Err(anyhow!("不在允许的文件夹内: {}", path.display()))
+        }
+    }
+}
+
+/// 规范化 path；还不存在的部分（如新的输出文件夹）接在最近的已存在上级之后，
+/// 其中不允许有 `..`
+fn resolve(path: &Path) -> Result<PathBuf> {
+    let absolute =
+        std::path::absolute(path).with_context(|| format!("无效的路径: {}", path.display()))?;
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            let mut resolved = canonical;
+            for part in missing.iter().rev() {
+                resolved.push(part);
+            }
+            return Ok(resolved);
+        }
+        let Some(parent) = existing.parent() else {
+            bail!("无效的路径: {}", path.display());
+        };
+        match existing.components().next_back() {
+            Some(Component::Normal(part)) => missing.push(part.to_os_string()),
+            _ => bail!("路径中不能包含 ..: {}", path.display()),
+        }
+        existing = parent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn jobs_must_stay_inside_the_allowed_folders() {
+        let root = std::env::temp_dir().join(format!("compress_img_access_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let allowed = root.join("photos");
+        let other = root.join("other");
+        fs::create_dir_all(allowed.join("album")).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let policy = AccessPolicy::new(std::slice::from_ref(&allowed), None).unwrap();
+        let options = CompressionOptions::default();
+
+        assert!(policy.check_job(&allowed.join("album"), &options).is_ok());
+        assert!(policy.check_job(&other, &options).is_err());
+        assert!(policy
+            .check_job(
+                &allowed.join("album").join("..").join("..").join("other"),
+                &options
+            )
+            .is_err());
+
+        let mut escaping = CompressionOptions::default();
+        escaping.output.folder = Some(allowed.join("new").join("..").join("..").join("x"));
+        assert!(policy.check_job(&allowed, &escaping).is_err());
+        escaping.output.folder = Some(allowed.join("out").join("2024"));
+        assert!(policy.check_job(&allowed, &escaping).is_ok());
+        escaping.backup.folder = Some(other.clone());
+        assert!(policy.check_job(&allowed, &escaping).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn token_is_required_only_when_configured() {
+        let open = AccessPolicy::default();
+        assert!(open.token_matches(None));
+        let locked = AccessPolicy::new(&[], Some("secret".to_string())).unwrap();
+        assert!(locked.requires_token());
+        assert!(locked.token_matches(Some("secret")));
+        assert!(!locked.token_matches(Some("secreT")));
+        assert!(!locked.token_matches(None));
+    }
+}
@@ -0,0 +1,300 @@
+//! 动图：GIF 和 APNG 逐帧解码为完整画面，重新编码为 GIF、APNG 或动画 WebP。
+//! 相同的相邻帧合并，之后每帧只写出相对上一帧变化的矩形区域，未变化的像素设为透明；
+//! 某帧需要把不透明像素变回透明时无法这样叠加，整个动画改为写出完整的帧。
+
+use anyhow::{anyhow, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{imageops, AnimationDecoder, DynamicImage, Frames, ImageFormat, RgbaImage};
+use std::io::Cursor;
+
+use crate::codec;
+use crate::options::CompressionOptions;
+
+struct Frame {
+    image: RgbaImage,
+    delay_ms: u32,
+}
+
+struct Animation {
+    frames: Vec<Frame>,
+    /// 播放次数，0 为无限循环
+    plays: u32,
+}
+
+/// 帧相对上一帧变化的区域
+struct Patch {
+    left: u32,
+    top: u32,
+    image: RgbaImage,
+}
+
+/// 重新编码的结果
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    pub frames: usize,
+}
+
+/// GIF（包括只有一帧的）和带 acTL 块的 PNG 按动图处理
+pub fn is_animation(input: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => true,
+        ImageFormat::Png => is_apng(input),
+        _ => false,
+    }
+}
+
+// acTL 必须出现在第一个 IDAT 之前，不必读完整个文件
+fn is_apng(input: &[u8]) -> bool {
+    let mut rest = input.get(8..).unwrap_or_default();
+    while rest.len() >= 8 {
+        match &rest[4..8] {
+            b"acTL" => return true,
+            b"IDAT" => return false,
+            _ => {}
+        }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        rest = rest.get(length.saturating_add(12)..).unwrap_or_default();
+    }
+    false
+}
+
+/// 把 format 格式的动图重新编码为 target；超过最大宽高时每帧等比缩小
+pub fn recompress(
+    input: &[u8],
+    format: ImageFormat,
+    target: ImageFormat,
+    options: &CompressionOptions,
+) -> Result<Encoded> {
+    let mut animation = decode(input, format)?;
+    for frame in &mut animation.frames {
+        let image = DynamicImage::ImageRgba8(std::mem::take(&mut frame.image));
+        frame.image = match codec::limit_dimensions(&image, &options.resize) {
+            Some(smaller) => smaller.to_rgba8(),
+            None => image.into_rgba8(),
+        };
+    }
+    merge_repeated(&mut animation.frames);
+
+    let bytes = match target {
+        ImageFormat::Gif => encode_gif(&animation, options)?,
+        ImageFormat::Png => encode_apng(&animation, options)?,
+        #[cfg(not(target_arch = "wasm32"))]
+        ImageFormat::WebP => encode_webp(&animation, options)?,
+        other => return Err(anyhow!("无法把动图编码为 {other:?} 格式")),
+    };
+    Ok(Encoded {
+        bytes,
+        frames: animation.frames.len(),
+    })
+}
+
+fn decode(input: &[u8], format: ImageFormat) -> Result<Animation> {
+    let (frames, plays): (Frames, u32) = match format {
+        ImageFormat::Gif => {
+            let decoder = gif::DecodeOptions::new().read_info(Cursor::new(input))?;
+            // NETSCAPE 扩展中的次数是首次播放之后再重复的次数，没有扩展时只播放一次
+            let plays = match decoder.repeat() {
+                gif::Repeat::Infinite => 0,
+                gif::Repeat::Finite(repeats) => repeats as u32 + 1,
+            };
+            (GifDecoder::new(Cursor::new(input))?.into_frames(), plays)
+        }
+        ImageFormat::Png => {
+            let decoder = png::Decoder::new(Cursor::new(input)).read_info()?;
+            let plays = decoder
+                .info()
+                .animation_control
+                .map_or(0, |control| control.num_plays);
+            (
+                PngDecoder::new(Cursor::new(input))?.apng()?.into_frames(),
+                plays,
+            )
+        }
+        other => return Err(anyhow!("{other:?} 不是动图格式")),
+    };
+
+    // 解码出的每帧都是叠加后的完整画面
+    let frames = frames
+        .map(|frame| {
+            let frame = frame?;
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            Ok(Frame {
+                delay_ms: numerator / denominator.max(1),
+                image: frame.into_buffer(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if frames.is_empty() {
+        return Err(anyhow!("动图中没有任何帧"));
+    }
+    Ok(Animation { frames, plays })
+}
+
+fn merge_repeated(frames: &mut Vec<Frame>) {
+    frames.dedup_by(|next, kept| {
+        let same = next.image == kept.image;
+        if same {
+            kept.delay_ms += next.delay_ms;
+        }
+        same
+    });
+}
+
+/// 每帧变化的像素都不透明时，只写变化区域也能在上一帧上叠加出正确的画面
+fn can_patch(frames: &[Frame]) -> bool {
+    frames.windows(2).all(|pair| {
+        pair[0]
+            .image
+            .pixels()
+            .zip(pair[1].image.pixels())
+            .all(|(before, after)| before == after || after[3] == u8::MAX)
+    })
+}
+
+fn patch(previous: &RgbaImage, current: &RgbaImage) -> Patch {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in current.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+    // 相同的帧已经合并，这里至少有一个像素变化；仍然没有时写出一个像素
+    if left > right {
+        (left, top, right, bottom) = (0, 0, 0, 0);
+    }
+    let mut image =
+        imageops::crop_imm(current, left, top, right - left + 1, bottom - top + 1).to_image();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if previous.get_pixel(left + x, top + y) == pixel {
+            *pixel = image::Rgba([0, 0, 0, 0]);
+        }
+    }
+    Patch { left, top, image }
+}
+
+/// 第一帧完整写出，之后按 can_patch 的结果写变化区域或完整的帧
+fn patches(frames: &[Frame]) -> (Vec<Patch>, bool) {
+    let patched = can_patch(frames);
+    let full = |frame: &Frame| Patch {
+        left: 0,
+        top: 0,
+        image: frame.image.clone(),
+    };
+    let mut result = vec![full(&frames[0])];
+    for pair in frames.windows(2) {
+        result.push(if patched {
+            patch(&pair[0].image, &pair[1].image)
+        } else {
+            full(&pair[1])
+        });
+    }
+    (result, patched)
+}
+
+// 每帧最多 256 色，超过时用 NeuQuant 重新量化；速度跟随 PNG 力度
+fn encode_gif(animation: &Animation, options: &CompressionOptions) -> Result<Vec<u8>> {
+    let canvas = &animation.frames[0].image;
+    let dimension =
+        |size: u32| u16::try_from(size).map_err(|_| anyhow!("GIF 的宽高不能超过 65535"));
+    let (width, height) = (dimension(canvas.width())?, dimension(canvas.height())?);
+    let speed = match options.png.effort {
+        0..=2 => 30,
+        3..=4 => 10,
+        _ => 1,
+    };
+
+    let mut output = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut output, width, height, &[])?;
+    match animation.plays {
+        0 => encoder.set_repeat(gif::Repeat::Infinite)?,
+        1 => {}
+        plays => {
+            encoder.set_repeat(gif::Repeat::Finite((plays - 1).min(u16::MAX as u32) as u16))?
+        }
+    }
+    let (patches, patched) = patches(&animation.frames);
+    for (patch, frame) in patches.into_iter().zip(&animation.frames) {
+        let (patch_width, patch_height) = patch.image.dimensions();
+        let mut pixels = patch.image.into_raw();
+        let mut encoded = gif::Frame::from_rgba_speed(
+            dimension(patch_width)?,
+            dimension(patch_height)?,
+            &mut pixels,
+            speed,
+        );
+        encoded.left = dimension(patch.left)?;
+        encoded.top = dimension(patch.top)?;
+        // GIF 的延迟以 10 毫秒为单位
+        encoded.delay = (frame.delay_ms.div_ceil(10)).min(u16::MAX as u32) as u16;
+        encoded.dispose = if patched {
+            gif::DisposalMethod::Keep
+        } else {
+            gif::DisposalMethod::Background
+        };
+        encoder.write_frame(&encoded)?;
+    }
+    drop(encoder);
+    Ok(output)
+}
+
+fn encode_apng(animation: &Animation, options: &CompressionOptions) -> Result<Vec<u8>> {
+    let canvas = &animation.frames[0].image;
+    let mut output = Vec::new();
+    let mut encoder = png::Encoder::new(&mut output, canvas.width(), canvas.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(animation.frames.len() as u32, animation.plays)?;
+    encoder.set_filter(png::Filter::Adaptive);
+    encoder.set_deflate_compression(png::DeflateCompression::Level(match options.png.effort {
+        0..=2 => 6,
+        _ => 9,
+    }));
+    let mut writer = encoder.write_header()?;
+    let (patches, patched) = patches(&animation.frames);
+    for (patch, frame) in patches.iter().zip(&animation.frames) {
+        // 先回到左上角再改尺寸，否则新尺寸加上旧位置可能超出画布
+        writer.reset_frame_position()?;
+        writer.set_frame_dimension(patch.image.width(), patch.image.height())?;
+        writer.set_frame_position(patch.left, patch.top)?;
+        writer.set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)?;
+        writer.set_dispose_op(png::DisposeOp::None)?;
+        writer.set_blend_op(if patched {
+            png::BlendOp::Over
+        } else {
+            png::BlendOp::Source
+        })?;
+        writer.write_image_data(patch.image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(output)
+}
+
+// libwebp 自己比较相邻帧，只需给出完整画面和每帧开始的时间
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_webp(animation: &Animation, options: &CompressionOptions) -> Result<Vec<u8>> {
+    let canvas = &animation.frames[0].image;
+    let (width, height) = canvas.dimensions();
+    let mut config = webp::WebPConfig::new().map_err(|_| anyhow!("无法初始化 WebP 编码器"))?;
+    config.lossless = options.webp.lossless as i32;
+    config.quality = options.webp.quality.max(1) as f32;
+    let mut encoder = webp::AnimEncoder::new(width, height, &config);
+    encoder.set_loop_count(animation.plays as i32);
+    let mut timestamp = 0;
+    for frame in &animation.frames {
+        encoder.add_frame(webp::AnimFrame::from_rgba(
+            frame.image.as_raw(),
+            width,
+            height,
+            timestamp,
+        ));
+        timestamp += frame.delay_ms as i32;
+    }
+    let encoded = encoder
+        .try_encode()
+        .map_err(|err| anyhow!("动画 WebP 编码失败: {err:?}"))?;
+    Ok(encoded.to_vec())
+}
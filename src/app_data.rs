@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const APP_DIR_NAME: &str = "compress_img";
+
+/// 程序自己的数据目录（Windows 上为 %APPDATA%\compress_img），不存在时自动创建
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = dirs::data_dir()
+        .ok_or_else(|| anyhow!("无法确定用户数据目录"))?
+        .join(APP_DIR_NAME);
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建数据目录: {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// 用于在数据文件中标识文件夹或文件的键，尽量使用规范化后的绝对路径
+pub fn path_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// 持有数据文件 path 旁锁文件的独占锁期间执行 f。同一数据文件的读写都经过这里，
+/// 同时运行的任务（包括其他进程）保存时不会读到写了一半的文件
+pub fn with_file_lock<R>(path: &Path, f: impl FnOnce() -> Result<R>) -> Result<R> {
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("无法创建锁文件: {}", lock_path.display()))?;
+    file.lock()
+        .with_context(|| format!("无法锁定: {}", lock_path.display()))?;
+    // 文件随 file 关闭释放锁
+    f()
+}
+
+/// 先写到旁边的临时文件并落盘再改名替换，中途退出不会留下写了一半的数据文件
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = path.with_extension("tmp");
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.with_context(|| format!("无法写入: {}", path.display()))
+}
+
+/// 把本次改动过的键（包括删除的）从 ours 合并进刚从磁盘读出的 current，
+/// 其余键保留 current 中其他任务写下的内容
+pub fn merge_changed<V: Clone>(
+    current: &mut BTreeMap<String, V>,
+    ours: &BTreeMap<String, V>,
+    changed: &BTreeSet<String>,
+) {
+    for key in changed {
+        match ours.get(key) {
+            Some(value) => {
+                current.insert(key.clone(), value.clone());
+            }
+            None => {
+                current.remove(key);
+            }
+        }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// UTC 日期，如 2024-05-01
+pub fn format_date(unix_secs: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// UTC 日期和时间，如 2024-05-01 13:45
+pub fn format_datetime(unix_secs: u64) -> String {
+    let minutes = unix_secs / 60 % 1440;
+    format!(
+        "{} {:02}:{:02}",
+        format_date(unix_secs),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+// Howard Hinnant 的 civil_from_days 算法
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
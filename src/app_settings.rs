@@ -0,0 +1,128 @@
+//! 与具体文件夹无关的程序设置：上次关闭时的文件夹和设置、命名预设
+use crate::app_data;
+use crate::options::{CompressionOptions, MetadataMode};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+const SETTINGS_FILE_NAME: &str = "app_settings.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// 启动时检查新版本，默认关闭
+    pub check_updates: bool,
+    /// 用户选择不再提醒的版本
+    pub skipped_version: Option<String>,
+    /// 上次关闭时选择的文件夹
+    pub last_folder: Option<PathBuf>,
+    // 保存原始 JSON，读取时走 CompressionOptions 的版本迁移
+    last_options: Option<Value>,
+    /// 命名预设，按保存的顺序排列；第一次启动时为内置的几个
+    pub presets: Vec<NamedPreset>,
+}
+
+/// 一组完整的设置，可以在下拉框中按名称选用
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedPreset {
+    pub name: String,
+    options: Value,
+}
+
+impl NamedPreset {
+    pub fn new(name: &str, options: &CompressionOptions) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            options: serde_json::to_value(options)?,
+        })
+    }
+
+    pub fn options(&self) -> Result<CompressionOptions> {
+        CompressionOptions::from_value(self.options.clone())
+            .with_context(|| format!("预设“{}”无效", self.name))
+    }
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            check_updates: false,
+            skipped_version: None,
+            last_folder: None,
+            last_options: None,
+            presets: builtin_presets(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn load() -> Result<Self> {
+        let path = settings_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("无法读取程序设置: {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("无法解析程序设置: {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = settings_path()?;
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(&path, text).with_context(|| format!("无法写入程序设置: {}", path.display()))
+    }
+
+    /// 上次关闭时的设置，没有保存过或已无法解析时为 None
+    pub fn last_options(&self) -> Option<CompressionOptions> {
+        let value = self.last_options.clone()?;
+        CompressionOptions::from_value(value)
+            .inspect_err(|err| log::warn!("上次的设置无法读取: {err:#}"))
+            .ok()
+    }
+
+    pub fn set_last_options(&mut self, options: &CompressionOptions) -> Result<()> {
+        self.last_options = Some(serde_json::to_value(options)?);
+        Ok(())
+    }
+
+    /// 同名的预设会被替换
+    pub fn save_preset(&mut self, name: &str, options: &CompressionOptions) -> Result<()> {
+        let preset = NamedPreset::new(name, options)?;
+        match self.presets.iter_mut().find(|preset| preset.name == name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+        Ok(())
+    }
+
+    pub fn remove_preset(&mut self, name: &str) {
+        self.presets.retain(|preset| preset.name != name);
+    }
+}
+
+fn builtin_presets() -> Vec<NamedPreset> {
+    let mut web = CompressionOptions::default();
+    web.jpeg.quality = 75;
+    web.webp.quality = 75;
+    web.png.lossy_level = 40;
+    web.resize.max_width = 1920;
+    web.metadata.mode = MetadataMode::StripAll;
+
+    // 只做无损的处理：JPEG 和 WebP 重新编码会有损失，不处理
+    let mut archive = CompressionOptions::default();
+    archive.jpeg.enabled = false;
+    archive.webp.enabled = false;
+    archive.png.effort = 6;
+    archive.png.lossy_level = 0;
+
+    [("网页 75%", web), ("存档无损", archive)]
+        .iter()
+        .filter_map(|(name, options)| NamedPreset::new(name, options).ok())
+        .collect()
+}
+
+fn settings_path() -> Result<PathBuf> {
+    Ok(app_data::data_dir()?.join(SETTINGS_FILE_NAME))
+}
@@ -0,0 +1,327 @@
+//! 原文件备份：开启后每次运行在数据目录的 backups/<运行编号>/ 下
+//! （或设置的备份位置下）按相对路径保存被覆盖前的原文件，并记录一份清单用于恢复。
+//! 用过的自定义备份位置记在数据目录中，恢复时一并列出。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::app_data::{self, path_key};
+
+const BACKUP_DIR_NAME: &str = "backups";
+const RUN_FILE_NAME: &str = "run.json";
+const MANIFEST_FILE_NAME: &str = "manifest.jsonl";
+const OUTPUTS_FILE_NAME: &str = "outputs.jsonl";
+const FILES_DIR_NAME: &str = "files";
+const ROOTS_FILE_NAME: &str = "backup_roots.json";
+
+/// 本进程中下一个备份会话的序号
+static NEXT_SESSION: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RunHeader {
+    folder: String,
+    created_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ManifestLine {
+    original: PathBuf,
+    /// 相对于本次运行 files/ 目录的路径
+    stored: PathBuf,
+    size: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct OutputLine {
+    original: PathBuf,
+    output: PathBuf,
+}
+
+/// 一次运行的全部备份
+#[derive(Clone, Debug)]
+pub struct BackupRun {
+    pub id: String,
+    pub folder: String,
+    pub created_at: u64,
+    pub dir: PathBuf,
+    pub entries: Vec<BackupEntry>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BackupEntry {
+    pub original: PathBuf,
+    pub stored: PathBuf,
+    pub size: u64,
+    /// 转换格式时在原文件旁新建的文件，恢复时删除
+    pub created: Option<PathBuf>,
+}
+
+impl BackupRun {
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+}
+
+/// 运行过程中逐个保存原文件，清单逐行追加，程序中途退出也不会丢失已备份的记录
+pub struct BackupSession {
+    folder: PathBuf,
+    dir: PathBuf,
+    files_dir: PathBuf,
+    manifest: File,
+    saved: usize,
+}
+
+impl BackupSession {
+    /// location 为 None 时保存在数据目录中
+    pub fn start(folder: &Path, started_at: u64, location: Option<&Path>) -> Result<Self> {
+        // 同一秒内同一进程可能开始多个任务（服务模式），加上序号避免共用目录
+        let sequence = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+        let id = format!("{started_at}-{}-{sequence}", std::process::id());
+        let root = match location {
+            Some(location) => {
+                remember_root(location)?;
+                location.to_path_buf()
+            }
+            None => backups_dir()?,
+        };
+        let dir = root.join(id);
+        let files_dir = dir.join(FILES_DIR_NAME);
+        fs::create_dir_all(&files_dir)
+            .with_context(|| format!("无法创建备份目录: {}", files_dir.display()))?;
+        let header = RunHeader {
+            folder: path_key(folder),
+            created_at: started_at,
+        };
+        fs::write(dir.join(RUN_FILE_NAME), serde_json::to_string(&header)?)
+            .with_context(|| format!("无法写入备份信息: {}", dir.display()))?;
+        let manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(MANIFEST_FILE_NAME))
+            .with_context(|| format!("无法创建备份清单: {}", dir.display()))?;
+        Ok(Self {
+            folder: folder.to_path_buf(),
+            dir,
+            files_dir,
+            manifest,
+            saved: 0,
+        })
+    }
+
+    /// 在覆盖 path 之前调用
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        let relative = path
+            .strip_prefix(&self.folder)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .or_else(|| path.file_name().map(Path::new))
+            .unwrap_or(path)
+            .to_path_buf();
+        let stored = self.files_dir.join(&relative);
+        if let Some(parent) = stored.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建备份目录: {}", parent.display()))?;
+        }
+        let size = fs::copy(path, &stored)
+            .with_context(|| format!("无法备份原文件: {}", path.display()))?;
+        let line = ManifestLine {
+            original: path.to_path_buf(),
+            stored: relative,
+            size,
+        };
+        writeln!(self.manifest, "{}", serde_json::to_string(&line)?).context("无法写入备份清单")?;
+        self.saved += 1;
+        Ok(())
+    }
+
+    /// 原文件被转换成 output 时调用，恢复时删掉这个新文件
+    pub fn record_output(&mut self, original: &Path, output: &Path) -> Result<()> {
+        let line = OutputLine {
+            original: original.to_path_buf(),
+            output: output.to_path_buf(),
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(OUTPUTS_FILE_NAME))
+            .context("无法写入备份清单")?;
+        writeln!(file, "{}", serde_json::to_string(&line)?).context("无法写入备份清单")?;
+        Ok(())
+    }
+
+    /// 运行结束时调用，一个文件都没备份时删掉空目录
+    pub fn finish(self) {
+        if self.saved == 0 {
+            drop(self.manifest);
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// 所有备份（含自定义位置中的），最新的在前
+pub fn list_runs() -> Result<Vec<BackupRun>> {
+    let dir = backups_dir()?;
+    let mut runs = Vec::new();
+    for entry in
+        fs::read_dir(&dir).with_context(|| format!("无法读取备份目录: {}", dir.display()))?
+    {
+        let run_dir = entry?.path();
+        if let Some(run) = load_run(&run_dir) {
+            runs.push(run);
+        }
+    }
+    // 自定义位置可能已被移走或在未连接的磁盘上，读不到时跳过
+    for root in remembered_roots() {
+        for entry in fs::read_dir(&root).into_iter().flatten().flatten() {
+            if let Some(run) = load_run(&entry.path()) {
+                runs.push(run);
+            }
+        }
+    }
+    runs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    Ok(runs)
+}
+
+/// 最近一次运行的备份
+pub fn latest_run() -> Result<Option<BackupRun>> {
+    Ok(list_runs()?.into_iter().next())
+}
+
+/// 把备份复制回原位置，覆盖当前文件；转换出的新文件一并删除
+pub fn restore_entry(entry: &BackupEntry) -> Result<()> {
+    if let Some(parent) = entry.original.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+    }
+    fs::copy(&entry.stored, &entry.original)
+        .with_context(|| format!("无法恢复文件: {}", entry.original.display()))?;
+    if let Some(created) = &entry.created
+        && *created != entry.original
+    {
+        match fs::remove_file(created) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err)
+                    .with_context(|| format!("无法删除转换出的文件: {}", created.display()));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 恢复整次运行，返回成功数和各失败文件的错误
+pub fn restore_run(run: &BackupRun) -> (usize, Vec<String>) {
+    let mut restored = 0;
+    let mut errors = Vec::new();
+    for entry in &run.entries {
+        match restore_entry(entry) {
+            Ok(()) => restored += 1,
+            Err(err) => errors.push(format!("{err:#}")),
+        }
+    }
+    (restored, errors)
+}
+
+pub fn delete_run(run: &BackupRun) -> Result<()> {
+    fs::remove_dir_all(&run.dir).with_context(|| format!("无法删除备份: {}", run.dir.display()))
+}
+
+/// 删除早于 max_age_days 天的备份，再从最旧的开始删，直到总大小不超过 max_total_bytes。
+/// 返回 (删除的运行数, 释放的字节数)
+pub fn cleanup(max_age_days: Option<u32>, max_total_bytes: Option<u64>) -> Result<(usize, u64)> {
+    let mut runs = list_runs()?;
+    let now = app_data::unix_now();
+    let mut removed = 0;
+    let mut freed = 0;
+
+    if let Some(days) = max_age_days {
+        let cutoff = now.saturating_sub(days as u64 * 86_400);
+        let (old, kept): (Vec<_>, Vec<_>) =
+            runs.into_iter().partition(|run| run.created_at < cutoff);
+        for run in old {
+            delete_run(&run)?;
+            removed += 1;
+            freed += run.total_bytes();
+        }
+        runs = kept;
+    }
+
+    if let Some(limit) = max_total_bytes {
+        let mut total: u64 = runs.iter().map(BackupRun::total_bytes).sum();
+        // runs 最新在前，从末尾删起
+        while total > limit
+            && let Some(run) = runs.pop()
+        {
+            delete_run(&run)?;
+            removed += 1;
+            freed += run.total_bytes();
+            total -= run.total_bytes();
+        }
+    }
+    Ok((removed, freed))
+}
+
+fn load_run(dir: &Path) -> Option<BackupRun> {
+    let header: RunHeader =
+        serde_json::from_str(&fs::read_to_string(dir.join(RUN_FILE_NAME)).ok()?).ok()?;
+    let files_dir = dir.join(FILES_DIR_NAME);
+    let mut outputs: HashMap<PathBuf, PathBuf> = fs::read_to_string(dir.join(OUTPUTS_FILE_NAME))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<OutputLine>(line).ok())
+        .map(|line| (line.original, line.output))
+        .collect();
+    let entries = fs::read_to_string(dir.join(MANIFEST_FILE_NAME))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ManifestLine>(line).ok())
+        .map(|line| BackupEntry {
+            created: outputs.remove(&line.original),
+            original: line.original,
+            stored: files_dir.join(line.stored),
+            size: line.size,
+        })
+        .collect();
+    Some(BackupRun {
+        id: dir.file_name()?.to_string_lossy().into_owned(),
+        folder: header.folder,
+        created_at: header.created_at,
+        dir: dir.to_path_buf(),
+        entries,
+    })
+}
+
+fn remembered_roots() -> Vec<PathBuf> {
+    app_data::data_dir()
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(ROOTS_FILE_NAME)).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn remember_root(location: &Path) -> Result<()> {
+    fs::create_dir_all(location)
+        .with_context(|| format!("无法创建备份目录: {}", location.display()))?;
+    let location = fs::canonicalize(location).unwrap_or_else(|_| location.to_path_buf());
+    let mut roots = remembered_roots();
+    if !roots.contains(&location) {
+        roots.push(location);
+        fs::write(
+            app_data::data_dir()?.join(ROOTS_FILE_NAME),
+            serde_json::to_string_pretty(&roots)?,
+        )
+        .context("无法记录备份位置")?;
+    }
+    Ok(())
+}
+
+fn backups_dir() -> Result<PathBuf> {
+    let dir = app_data::data_dir()?.join(BACKUP_DIR_NAME);
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建备份目录: {}", dir.display()))?;
+    Ok(dir)
+}
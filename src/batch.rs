@@ -0,0 +1,1119 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::backup::BackupSession;
+use crate::control::RunControl;
+use crate::error::{self, CompressError, Language};
+use crate::failures::{FailureStore, REVIEW_DIR_NAME};
+use crate::history::{self, RunRecord};
+use crate::lock::{FolderLock, LockPolicy};
+use crate::options::{CompressionOptions, FailureAction, OutputFormat, TargetMode};
+use crate::prefetch::prefetch;
+use crate::processed::ProcessedIndex;
+use crate::scan::ScanProgress;
+pub use crate::scan::SkipReason;
+use crate::{app_data, cloud, convert, output::OutputPlanner};
+use crate::{
+    bytes_to_kb, bytes_to_mb, compress_source_converted, compress_source_to, savings_percent, scan,
+    CompressionStats, SourceBytes,
+};
+
+pub enum BatchEvent {
+    /// 文件夹被其他任务占用，正在排队（仅 LockPolicy::Wait）
+    WaitingForLock,
+    /// 遍历目录过程中的进度
+    Scanning(ScanProgress),
+    /// 扫描和过滤完成，即将开始压缩
+    Scanned {
+        total: usize,
+        /// 待处理文件的总字节数
+        total_bytes: u64,
+        /// 按历史速度估算的耗时（秒），没有历史记录时为 None
+        estimated_secs: Option<u64>,
+        /// 增量模式下因处理完之后未修改而跳过的文件数
+        unchanged: usize,
+        /// 不匹配包含列表而跳过的文件数
+        not_included: usize,
+        /// 刚被修改、推迟到下次处理的文件数
+        too_recent: usize,
+        /// 不在“最大文件”范围内而跳过的文件数
+        not_targeted: usize,
+        /// 在忽略列表中而跳过的文件数
+        ignored: usize,
+        /// 转换模式下目标文件已存在而跳过的文件数
+        already_converted: usize,
+        /// 只在云端的文件数，未开启下载时这些文件被跳过
+        cloud_only: usize,
+        errors: Vec<anyhow::Error>,
+    },
+    /// 扫描到但本次不处理的文件
+    Skipped { path: PathBuf, reason: SkipReason },
+    FileFinished {
+        processed: usize,
+        total: usize,
+        path: PathBuf,
+        outcome: FileOutcome,
+    },
+}
+
+pub enum FileOutcome {
+    Compressed(CompressionStats),
+    Failed(FileFailure),
+}
+
+pub struct FileFailure {
+    pub error: anyhow::Error,
+    /// 达到连续失败次数阈值时的次数和随后的处理；未达到或只报告时为 None
+    pub persistent: Option<(u32, PersistentFailure)>,
+}
+
+/// 反复失败的文件按设置做的处理
+pub enum PersistentFailure {
+    Ignored,
+    MovedToReview(PathBuf),
+    /// 移到复审文件夹失败，附带下层的 io 错误
+    MoveFailed(String),
+}
+
+impl From<anyhow::Error> for FileFailure {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            error,
+            persistent: None,
+        }
+    }
+}
+
+impl FileFailure {
+    pub fn render(&self, language: Language) -> String {
+        let message = error::render_error(&self.error, language);
+        let Some((count, handling)) = &self.persistent else {
+            return message;
+        };
+        let zh = language == Language::Chinese;
+        let handling = match handling {
+            PersistentFailure::Ignored if zh => "已加入忽略列表".to_string(),
+            PersistentFailure::Ignored => "added to the ignore list".to_string(),
+            PersistentFailure::MovedToReview(target) if zh => {
+                format!("已移到 {}", target.display())
+            }
+            PersistentFailure::MovedToReview(target) => {
+                format!("moved to {}", target.display())
+            }
+            PersistentFailure::MoveFailed(err) if zh => format!("移到复审文件夹失败: {err}"),
+            PersistentFailure::MoveFailed(err) => {
+                format!("could not move to the review folder: {err}")
+            }
+        };
+        if zh {
+            format!("{message}（已连续失败 {count} 次，{handling}）")
+        } else {
+            format!("{message} (failed {count} times in a row, {handling})")
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    /// succeeded 中判断不值得改写、保持原样的文件数
+    pub kept: usize,
+    pub failed: usize,
+    pub total_saved: i64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// 各文件读写重试次数之和
+    pub retries: u32,
+    pub cancelled: bool,
+    /// 失败比例过高而中止时的情况
+    pub aborted: Option<AbortReason>,
+}
+
+/// 失败比例超过上限而中止运行
+#[derive(Clone, Debug)]
+pub struct AbortReason {
+    pub failed: usize,
+    pub processed: usize,
+    /// 允许的失败比例（百分比）
+    pub limit: usize,
+}
+
+impl AbortReason {
+    pub fn render(&self, language: Language) -> String {
+        let AbortReason {
+            failed,
+            processed,
+            limit,
+        } = self;
+        match language {
+            Language::Chinese => format!(
+                "{failed}/{processed} 个文件失败，超过 {limit}%，请检查文件夹、权限和磁盘空间"
+            ),
+            Language::English => format!(
+                "{failed}/{processed} files failed, more than {limit}%; \
+                 check the folder, permissions and free disk space"
+            ),
+        }
+    }
+}
+
+/// 至少处理这么多个文件后才检查失败比例，避免开头一两个失败就中止
+const ABORT_MIN_PROCESSED: usize = 10;
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 扫描完成、开始覆盖原文件之前交给调用方确认的信息
+pub struct PreRunInfo {
+    pub total: usize,
+    pub total_bytes: u64,
+    pub estimated_secs: Option<u64>,
+}
+
+/// 锁定 folder 后扫描并逐个压缩，每一步通过 on_event 通知调用方。
+/// 有文件要处理时先调用 confirm，返回 false 则不做任何修改、按取消返回。
+/// control 取消后在正在处理的文件完成时停止，已处理的文件保持不变；暂停期间不开始新的文件。
+pub fn run_batch(
+    folder: &Path,
+    options: &CompressionOptions,
+    lock_policy: LockPolicy,
+    control: &RunControl,
+    confirm: impl FnOnce(&PreRunInfo) -> bool,
+    on_event: impl FnMut(BatchEvent),
+) -> Result<BatchSummary> {
+    run_batch_with(
+        folder,
+        options,
+        lock_policy,
+        control,
+        &FileOverrides::default(),
+        confirm,
+        on_event,
+    )
+}
+
+/// 开始前对单个文件所做的调整，路径与扫描结果一致
+#[derive(Clone, Debug, Default)]
+pub struct FileOverrides {
+    /// 本次跳过的文件
+    pub excluded: HashSet<PathBuf>,
+    /// 单独指定输出格式的文件，None 表示保持原格式、不转换
+    pub formats: HashMap<PathBuf, Option<OutputFormat>>,
+    /// 只处理这些文件（拖入的单个文件），None 时处理扫描到的全部
+    pub only: Option<HashSet<PathBuf>>,
+}
+
+/// 一次运行要处理的一个文件夹，files 为 Some 时只处理其中的这些文件
+#[derive(Clone, Debug)]
+pub struct BatchTarget {
+    pub folder: PathBuf,
+    pub files: Option<HashSet<PathBuf>>,
+}
+
+/// 把文件夹和单个文件混合的列表整理成按文件夹处理的目标：单个文件按所在文件夹归组，
+/// 已在列表中某个文件夹之下的文件不再单独处理
+pub fn group_targets(paths: &[PathBuf]) -> Vec<BatchTarget> {
+    let folders: Vec<&PathBuf> = paths.iter().filter(|path| path.is_dir()).collect();
+    let mut targets: Vec<BatchTarget> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            if !targets.iter().any(|target| target.folder == *path) {
+                targets.push(BatchTarget {
+                    folder: path.clone(),
+                    files: None,
+                });
+            }
+            continue;
+        }
+        if folders.iter().any(|folder| path.starts_with(folder)) {
+            continue;
+        }
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        match targets
+            .iter_mut()
+            .find(|target| target.folder == parent && target.files.is_some())
+        {
+            Some(target) => {
+                target.files.get_or_insert_default().insert(path.clone());
+            }
+            None => targets.push(BatchTarget {
+                folder: parent.to_path_buf(),
+                files: Some(HashSet::from([path.clone()])),
+            }),
+        }
+    }
+    targets
+}
+
+impl FileOverrides {
+    /// path 实际使用的设置
+    pub fn options_for<'a>(
+        &self,
+        path: &Path,
+        options: &'a CompressionOptions,
+    ) -> Cow<'a, CompressionOptions> {
+        let Some(&format) = self.formats.get(path) else {
+            return Cow::Borrowed(options);
+        };
+        let mut adjusted = options.clone();
+        match format {
+            Some(format) => {
+                adjusted.convert.enabled = true;
+                adjusted.convert.format = format;
+            }
+            None => adjusted.convert.enabled = false,
+        }
+        Cow::Owned(adjusted)
+    }
+}
+
+/// 与 run_batch 相同，但按 overrides 跳过部分文件或改用其他输出格式
+pub fn run_batch_with(
+    folder: &Path,
+    options: &CompressionOptions,
+    lock_policy: LockPolicy,
+    control: &RunControl,
+    overrides: &FileOverrides,
+    confirm: impl FnOnce(&PreRunInfo) -> bool,
+    mut on_event: impl FnMut(BatchEvent),
+) -> Result<BatchSummary> {
+    if !folder.exists() {
+        return Err(CompressError::FolderNotFound(folder.to_path_buf()).into());
+    }
+    if !folder.is_dir() {
+        return Err(CompressError::NotAFolder(folder.to_path_buf()).into());
+    }
+
+    let Some(_lock) = FolderLock::acquire_with(folder, lock_policy, control.cancel_flag(), || {
+        on_event(BatchEvent::WaitingForLock)
+    })?
+    else {
+        return Ok(BatchSummary {
+            cancelled: true,
+            ..BatchSummary::default()
+        });
+    };
+
+    let mut record = RunRecord::new(folder, app_data::unix_now());
+    let WorkList {
+        files,
+        unchanged,
+        not_included,
+        deferred,
+        not_targeted,
+        ignored,
+        already_converted,
+        cloud_only,
+        errors,
+        mut failure_store,
+        mut processed_index,
+    } = collect_work(folder, options, overrides, record.started_at, &mut on_event);
+    record.deferred_paths = deferred;
+    let mut planner = OutputPlanner::new(
+        folder,
+        &options.output,
+        files.iter().map(|(_, path)| path.as_path()),
+    )?;
+
+    let total = files.len();
+    let total_bytes: u64 = files.iter().map(|(size, _)| size).sum();
+    let estimated_secs = history::estimated_throughput()
+        .map(|throughput| (total_bytes as f64 / throughput).ceil() as u64);
+    on_event(BatchEvent::Scanned {
+        total,
+        total_bytes,
+        estimated_secs,
+        unchanged,
+        not_included,
+        too_recent: record.deferred_paths.len(),
+        not_targeted,
+        ignored,
+        already_converted,
+        cloud_only,
+        errors,
+    });
+    if total > 0
+        && !confirm(&PreRunInfo {
+            total,
+            total_bytes,
+            estimated_secs,
+        })
+    {
+        return Ok(BatchSummary {
+            total,
+            cancelled: true,
+            ..BatchSummary::default()
+        });
+    }
+
+    // 写到输出文件夹或副本时原文件不会被改动，无需备份
+    let mut backup = if options.backup.enabled && planner.is_none() {
+        Some(BackupSession::start(
+            folder,
+            record.started_at,
+            options.backup.location(folder).as_deref(),
+        )?)
+    } else {
+        None
+    };
+
+    let mut summary = BatchSummary {
+        total,
+        ..BatchSummary::default()
+    };
+    let workers = options.parallel.worker_count().min(total).max(1);
+    let sources = prefetch(files.into_iter().map(|(_, path)| path).collect(), options);
+    // 中止后排队中的文件不再开始
+    let halted = AtomicBool::new(false);
+    // 转换格式新建的文件，运行结束后记入备份，撤销时删除
+    let backing_up = backup.is_some();
+    let mut created = Vec::new();
+    // 结果按完成的顺序在当前线程汇总，on_event 不会被并发调用
+    let mut finish = |path: PathBuf, result: Result<CompressionStats>| {
+        let outcome = match result {
+            Ok(stats) => {
+                failure_store.record_success(&path);
+                processed_index.record_source(&path);
+                // 记下压缩结果所在的文件；写到别处时原文件未经压缩，下次照常处理
+                processed_index.record(stats.output.as_deref().unwrap_or(&path));
+                if backing_up && let Some(output) = &stats.output {
+                    created.push((path.clone(), output.clone()));
+                }
+                summary.succeeded += 1;
+                if stats.kept_reason.is_some() {
+                    summary.kept += 1;
+                }
+                summary.total_saved += stats.original_size.saturating_sub(stats.new_size) as i64;
+                summary.bytes_before += stats.original_size;
+                summary.bytes_after += stats.new_size;
+                summary.retries += stats.retries;
+                FileOutcome::Compressed(stats)
+            }
+            Err(err) => {
+                log::warn!("压缩失败 {}: {err:#}", path.display());
+                summary.failed += 1;
+                let count = failure_store.record_failure(&path, &err.to_string());
+                let persistent = (count >= options.failures.threshold.max(1))
+                    .then(|| {
+                        handle_persistent_failure(
+                            folder,
+                            &path,
+                            options.failures.action,
+                            &mut failure_store,
+                        )
+                    })
+                    .flatten()
+                    .map(|handling| (count, handling));
+                // 被忽略或移走的文件下次不必重试
+                if path.exists() && !failure_store.is_ignored(&path) {
+                    record.failed_paths.push(path.clone());
+                }
+                FileOutcome::Failed(FileFailure {
+                    error: err,
+                    persistent,
+                })
+            }
+        };
+        on_event(BatchEvent::FileFinished {
+            processed: summary.processed(),
+            total,
+            path,
+            outcome,
+        });
+
+        let limit = options.failures.abort_percent as usize;
+        let processed = summary.processed();
+        if summary.aborted.is_none()
+            && limit > 0
+            && processed >= ABORT_MIN_PROCESSED
+            && summary.failed * 100 > limit * processed
+        {
+            let reason = AbortReason {
+                failed: summary.failed,
+                processed,
+                limit,
+            };
+            log::warn!(
+                "中止运行 {}: {}",
+                folder.display(),
+                reason.render(Language::Chinese)
+            );
+            summary.aborted = Some(reason);
+            halted.store(true, Ordering::Relaxed);
+        }
+    };
+    let stopped = || control.is_cancelled() || halted.load(Ordering::Relaxed);
+
+    // 本次运行中已分配给原地转换结果的文件名
+    let mut reserved = HashSet::new();
+    // 队列只比工作线程多容纳一轮，限制同时在内存中的源文件
+    let (job_sender, jobs) = mpsc::sync_channel::<Job>(workers);
+    let jobs = Mutex::new(jobs);
+    let (result_sender, results) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let jobs = &jobs;
+            let result_sender = result_sender.clone();
+            scope.spawn(move || {
+                loop {
+                    // 只在取任务时持有锁，压缩期间其他线程可以继续取
+                    let next = jobs.lock().ok().and_then(|jobs| jobs.recv().ok());
+                    let Some(job) = next else {
+                        return;
+                    };
+                    control.wait_while_paused();
+                    if stopped() {
+                        continue;
+                    }
+                    let result = match &job.converted {
+                        Some(converted) => compress_source_converted(
+                            &job.path,
+                            job.source,
+                            converted,
+                            &job.options,
+                        ),
+                        None => compress_source_to(
+                            &job.path,
+                            job.source,
+                            job.destination.as_deref(),
+                            &job.options,
+                        ),
+                    };
+                    if result_sender.send((job.path, result)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+
+        for (path, source) in sources {
+            while let Ok((path, result)) = results.try_recv() {
+                finish(path, result);
+            }
+            // 暂停期间不再派发，但仍然汇报正在处理的文件的结果
+            while control.is_paused() && !stopped() {
+                match results.recv_timeout(PAUSE_POLL_INTERVAL) {
+                    Ok((path, result)) => finish(path, result),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            if stopped() {
+                break;
+            }
+            // 输出位置和备份按原来的顺序在当前线程准备；备份失败时不覆盖原文件
+            let job = source.and_then(|source| {
+                let job_options = overrides.options_for(&path, options);
+                // 原地转换的新文件名也在这里统一分配，工作线程不再自行选名
+                let converted = if planner.is_some() {
+                    None
+                } else {
+                    convert::reserve_output(&path, &source.bytes, &job_options, &mut reserved)?
+                };
+                let destination = match (planner.as_mut(), backup.as_mut()) {
+                    (Some(planner), _) => {
+                        // 按内容判断，动图保持原格式时也能取到正确的扩展名
+                        let extension = convert::planned_format(&source.bytes, &job_options)
+                            .map(|(_, target)| target.extensions_str()[0]);
+                        Some(planner.destination(&path, extension)?)
+                    }
+                    (None, Some(backup)) => {
+                        backup.save(&path)?;
+                        None
+                    }
+                    (None, None) => None,
+                };
+                Ok(Job {
+                    options: job_options,
+                    path: path.clone(),
+                    source,
+                    destination,
+                    converted,
+                })
+            });
+            match job {
+                Ok(job) => {
+                    if job_sender.send(job).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => finish(path, Err(err)),
+            }
+        }
+        drop(job_sender);
+        // 已经开始的文件照常完成并汇报
+        for (path, result) in results {
+            finish(path, result);
+        }
+    });
+    summary.cancelled = control.is_cancelled() && summary.processed() < total;
+
+    if let Some(mut backup) = backup {
+        for (original, output) in &created {
+            if let Err(err) = backup.record_output(original, output) {
+                log::warn!("{err:#}");
+            }
+        }
+        backup.finish();
+    }
+    if let Err(err) = failure_store.save() {
+        log::warn!("{err:#}");
+    }
+    if let Err(err) = processed_index.save() {
+        log::warn!("{err:#}");
+    }
+
+    record.finished_at = app_data::unix_now();
+    record.total = summary.total;
+    record.succeeded = summary.succeeded;
+    record.failed = summary.failed;
+    record.bytes_before = summary.bytes_before;
+    record.bytes_after = summary.bytes_after;
+    record.cancelled = summary.cancelled || summary.aborted.is_some();
+    // 历史只用于统计和增量模式，写入失败不影响本次结果
+    if let Err(err) = history::append(&record) {
+        log::warn!("{err:#}");
+    }
+    Ok(summary)
+}
+
+/// 扫描和过滤后本次要处理的文件
+pub struct WorkList {
+    /// (大小, 路径)，大文件在前
+    pub files: Vec<(u64, PathBuf)>,
+    pub unchanged: usize,
+    pub not_included: usize,
+    /// 刚被修改、推迟到下次处理的文件
+    pub deferred: Vec<PathBuf>,
+    pub not_targeted: usize,
+    pub ignored: usize,
+    pub already_converted: usize,
+    pub cloud_only: usize,
+    pub errors: Vec<anyhow::Error>,
+    failure_store: FailureStore,
+    processed_index: ProcessedIndex,
+}
+
+/// 扫描 folder，再按忽略列表、已处理记录、最短修改间隔和“最大文件”等设置过滤，
+/// 与实际运行时得到同样的文件列表；跳过的文件和扫描进度通过 on_event 通知。
+/// 不加锁，也不修改任何文件，预览时直接使用
+pub fn collect_work(
+    folder: &Path,
+    options: &CompressionOptions,
+    overrides: &FileOverrides,
+    started_at: u64,
+    mut on_event: impl FnMut(BatchEvent),
+) -> WorkList {
+    let scan = scan::scan_folder_with_progress(folder, options, |progress| {
+        on_event(BatchEvent::Scanning(progress))
+    });
+    for (path, reason) in scan.skipped {
+        on_event(BatchEvent::Skipped { path, reason });
+    }
+    let mut files = scan.files;
+    if let Some(only) = &overrides.only {
+        files.retain(|path| only.contains(path));
+    }
+    // 与已处理索引一样，记录损坏时从空记录开始，不中止整批任务
+    let failure_store = FailureStore::load().unwrap_or_else(|err| {
+        log::warn!("{err:#}");
+        FailureStore::default()
+    });
+    let (ignored, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|path| failure_store.is_ignored(path));
+    for path in &ignored {
+        on_event(BatchEvent::Skipped {
+            path: path.clone(),
+            reason: SkipReason::Ignored,
+        });
+    }
+    let (deselected, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|path| overrides.excluded.contains(path));
+    for path in deselected {
+        on_event(BatchEvent::Skipped {
+            path,
+            reason: SkipReason::Deselected,
+        });
+    }
+    let (converted, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|path| convert::already_converted(path, &overrides.options_for(path, options)));
+    for path in &converted {
+        on_event(BatchEvent::Skipped {
+            path: path.clone(),
+            reason: SkipReason::AlreadyConverted,
+        });
+    }
+    // 只读元数据不会触发下载，开启下载时照常处理，读取内容时由系统自动下载
+    let (cloud_only, mut files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|path| cloud::is_placeholder(path));
+    let cloud_only_count = cloud_only.len();
+    if options.scan.download_cloud_files {
+        files.extend(cloud_only);
+    } else {
+        for path in cloud_only {
+            on_event(BatchEvent::Skipped {
+                path,
+                reason: SkipReason::CloudOnly,
+            });
+        }
+    }
+    // 索引损坏时当作没有记录，不影响本次运行
+    let processed_index = ProcessedIndex::load().unwrap_or_else(|err| {
+        log::warn!("{err:#}");
+        ProcessedIndex::default()
+    });
+    let mut unchanged = 0;
+    let mut deferred = Vec::new();
+    if options.scan.incremental {
+        let (changed, skipped) = split_unchanged(files, &processed_index);
+        files = changed;
+        unchanged = skipped.len();
+        for path in skipped {
+            on_event(BatchEvent::Skipped {
+                path,
+                reason: SkipReason::Unchanged,
+            });
+        }
+    }
+    if options.scan.skip_processed {
+        let (processed, rest): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|path| processed_index.is_unchanged(path));
+        files = rest;
+        for path in processed {
+            on_event(BatchEvent::Skipped {
+                path,
+                reason: SkipReason::AlreadyProcessed,
+            });
+        }
+    }
+    if options.scan.min_age_minutes > 0 {
+        let cutoff = started_at.saturating_sub(options.scan.min_age_minutes as u64 * 60);
+        let (recent, settled) = files
+            .into_iter()
+            .partition(|path| modified_after(path, cutoff));
+        files = settled;
+        for path in &recent {
+            on_event(BatchEvent::Skipped {
+                path: path.clone(),
+                reason: SkipReason::TooRecent,
+            });
+        }
+        deferred = recent;
+    }
+    let mut files = largest_first(files);
+    let not_targeted = select_targets(&mut files, options.scan.target);
+    for (_, path) in &not_targeted {
+        on_event(BatchEvent::Skipped {
+            path: path.clone(),
+            reason: SkipReason::NotTargeted,
+        });
+    }
+    WorkList {
+        files,
+        unchanged,
+        not_included: scan.not_included,
+        deferred,
+        not_targeted: not_targeted.len(),
+        ignored: ignored.len(),
+        already_converted: converted.len(),
+        cloud_only: cloud_only_count,
+        errors: scan.errors,
+        failure_store,
+        processed_index,
+    }
+}
+
+/// 交给工作线程压缩的一个文件
+struct Job<'a> {
+    path: PathBuf,
+    source: SourceBytes,
+    destination: Option<PathBuf>,
+    /// 原地转换时预留的新文件名
+    converted: Option<PathBuf>,
+    options: Cow<'a, CompressionOptions>,
+}
+
+// 返回所做的处理，Report 时为 None
+fn handle_persistent_failure(
+    folder: &Path,
+    path: &Path,
+    action: FailureAction,
+    store: &mut FailureStore,
+) -> Option<PersistentFailure> {
+    match action {
+        FailureAction::Report => None,
+        FailureAction::Ignore => {
+            store.ignore(path, "反复压缩失败");
+            Some(PersistentFailure::Ignored)
+        }
+        FailureAction::MoveToReview => {
+            let relative = path.strip_prefix(folder).unwrap_or(path);
+            let target = folder.join(REVIEW_DIR_NAME).join(relative);
+            let moved = target
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::rename(path, &target));
+            Some(match moved {
+                Ok(()) => {
+                    store.record_success(path);
+                    PersistentFailure::MovedToReview(target)
+                }
+                Err(err) => PersistentFailure::MoveFailed(err.to_string()),
+            })
+        }
+    }
+}
+
+/// 按体积从大到小排队：大文件先开始，节省量早早涨上来，
+/// 并行处理时也不会剩一个巨型文件在最后单独跑
+fn largest_first(files: Vec<PathBuf>) -> Vec<(u64, PathBuf)> {
+    let mut sized: Vec<(u64, PathBuf)> = files
+        .into_iter()
+        .map(|path| (path.metadata().map(|m| m.len()).unwrap_or(0), path))
+        .collect();
+    sized.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+    sized
+}
+
+// sized 需已按体积降序排列，超出范围的部分被移出并返回
+fn select_targets(sized: &mut Vec<(u64, PathBuf)>, target: TargetMode) -> Vec<(u64, PathBuf)> {
+    let keep = match target {
+        TargetMode::All => sized.len(),
+        TargetMode::LargestCount { count } => count.min(sized.len()),
+        TargetMode::LargestShare { percent } => {
+            let total: u64 = sized.iter().map(|(size, _)| size).sum();
+            let goal = total as f64 * percent.min(100) as f64 / 100.0;
+            let mut covered = 0;
+            sized
+                .iter()
+                .take_while(|(size, _)| {
+                    let needed = (covered as f64) < goal;
+                    covered += size;
+                    needed
+                })
+                .count()
+        }
+    };
+    sized.split_off(keep)
+}
+
+/// 增量模式：按已处理索引中每个文件处理完时的大小和修改时间分出没改动过的文件，
+/// 返回 (要处理的, 没改动的)。上次没有处理到的文件（失败、推迟、未选中、新增）照常处理
+fn split_unchanged(files: Vec<PathBuf>, index: &ProcessedIndex) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    files
+        .into_iter()
+        .partition(|path| !index.is_source_unchanged(path))
+}
+
+fn modified_after(path: &Path, timestamp: u64) -> bool {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| app_data::unix_secs(modified) >= timestamp)
+        .unwrap_or(true)
+}
+
+impl FileOutcome {
+    pub fn log_line(&self, path: &Path, language: Language) -> String {
+        let zh = language == Language::Chinese;
+        match self {
+            FileOutcome::Compressed(stats) if stats.kept_reason.is_some() => format!(
+                "➖ {} | {}",
+                path.display(),
+                stats.kept_text(language).unwrap_or_default()
+            ),
+            FileOutcome::Compressed(stats) => format!(
+                "✔ {}{} | {:.2} KB → {:.2} KB ({} {:.2}%){}",
+                path.display(),
+                stats
+                    .output
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .map(|name| format!(" → {}", name.to_string_lossy()))
+                    .unwrap_or_default(),
+                bytes_to_kb(stats.original_size),
+                bytes_to_kb(stats.new_size),
+                if zh { "节省" } else { "saved" },
+                savings_percent(stats.original_size, stats.new_size),
+                match stats.retries {
+                    0 => String::new(),
+                    retries if zh => format!(" | 重试 {retries} 次"),
+                    retries => format!(" | {retries} retries"),
+                }
+            ),
+            FileOutcome::Failed(failure) => format!(
+                "✖ {} | {}: {}",
+                path.display(),
+                if zh { "失败" } else { "failed" },
+                failure.render(language)
+            ),
+        }
+    }
+
+    /// 调试模式下显示的细节：各阶段耗时和编码参数
+    pub fn details(&self, language: Language) -> Option<String> {
+        let FileOutcome::Compressed(stats) = self else {
+            return None;
+        };
+        let ms = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+        let timings = &stats.timings;
+        let labels = match language {
+            Language::Chinese => ["读取", "解码", "编码", "写入"],
+            Language::English => ["read", "decode", "encode", "write"],
+        };
+        Some(format!(
+            "{} {:.1} ms | {} {:.1} ms | {} {:.1} ms | {} {:.1} ms | {}",
+            labels[0],
+            ms(timings.read),
+            labels[1],
+            ms(timings.decode),
+            labels[2],
+            ms(timings.encode),
+            labels[3],
+            ms(timings.write),
+            stats.encoder
+        ))
+    }
+}
+
+/// 按原因归类收集的跳过文件，附在运行日志末尾，回答“这个文件为什么没被处理”
+#[derive(Debug, Default)]
+pub struct SkippedFiles {
+    by_reason: BTreeMap<SkipReason, Vec<PathBuf>>,
+}
+
+impl SkippedFiles {
+    pub fn record(&mut self, path: PathBuf, reason: SkipReason) {
+        self.by_reason.entry(reason).or_default().push(path);
+    }
+
+    pub fn count(&self, reason: SkipReason) -> usize {
+        self.by_reason.get(&reason).map_or(0, Vec::len)
+    }
+
+    pub fn total(&self) -> usize {
+        self.by_reason.values().map(Vec::len).sum()
+    }
+
+    pub fn to_text(&self, language: Language) -> String {
+        if self.by_reason.is_empty() {
+            return String::new();
+        }
+        let zh = language == Language::Chinese;
+        let mut text = if zh {
+            format!("跳过的文件（共 {} 项）:\n", self.total())
+        } else {
+            format!("Skipped files ({} in total):\n", self.total())
+        };
+        for (reason, paths) in &self.by_reason {
+            let count = paths.len();
+            text.push_str(&if zh {
+                format!("[{}] {count} 项\n", reason.render(language))
+            } else {
+                format!("[{}] {count}\n", reason.render(language))
+            });
+            for path in paths {
+                text.push_str(&format!("    ↷ {}\n", path.display()));
+            }
+        }
+        text
+    }
+}
+
+/// 开始压缩前的概要，如“共 8214 个文件，12.40 GB，预计约 40 分钟”
+pub fn pre_run_summary(
+    total: usize,
+    total_bytes: u64,
+    estimated_secs: Option<u64>,
+    language: Language,
+) -> String {
+    let gb = bytes_to_mb(total_bytes) / 1024.0;
+    let minutes = estimated_secs.map(|secs| (secs >= 60).then(|| secs.div_ceil(60)));
+    match language {
+        Language::Chinese => {
+            let mut text = format!("共 {total} 个文件，{gb:.2} GB");
+            match minutes {
+                Some(Some(minutes)) => text.push_str(&format!("，预计约 {minutes} 分钟")),
+                Some(None) => text.push_str("，预计不到 1 分钟"),
+                None => {}
+            }
+            text
+        }
+        Language::English => {
+            let mut text = format!("{total} files, {gb:.2} GB");
+            match minutes {
+                Some(Some(minutes)) => text.push_str(&format!(", about {minutes} minutes")),
+                Some(None) => text.push_str(", under a minute"),
+                None => {}
+            }
+            text
+        }
+    }
+}
+
+impl BatchSummary {
+    pub fn processed(&self) -> usize {
+        self.succeeded + self.failed
+    }
+
+    /// 累加依次处理的另一个文件夹的结果
+    pub fn merge(&mut self, other: BatchSummary) {
+        self.total += other.total;
+        self.succeeded += other.succeeded;
+        self.kept += other.kept;
+        self.failed += other.failed;
+        self.total_saved += other.total_saved;
+        self.bytes_before += other.bytes_before;
+        self.bytes_after += other.bytes_after;
+        self.retries += other.retries;
+        self.cancelled |= other.cancelled;
+        self.aborted = self.aborted.take().or(other.aborted);
+    }
+
+    pub fn status_text(&self, language: Language) -> String {
+        match language {
+            Language::Chinese => self.status_text_zh(),
+            Language::English => self.status_text_en(),
+        }
+    }
+
+    fn status_text_zh(&self) -> String {
+        let processed = self.processed();
+        let prefix = if let Some(reason) = &self.aborted {
+            format!(
+                "已中止: 完成 {processed}/{} 个图像（{}）",
+                self.total,
+                reason.render(Language::Chinese)
+            )
+        } else if self.cancelled {
+            format!("已取消: 完成 {processed}/{} 个图像", self.total)
+        } else {
+            format!("完成: 共处理 {processed} 个图像")
+        };
+        let prefix = if self.kept > 0 {
+            format!("{prefix}，其中 {} 个保持原样", self.kept)
+        } else {
+            prefix
+        };
+        let prefix = if self.retries > 0 {
+            format!("{prefix}（读写重试 {} 次）", self.retries)
+        } else {
+            prefix
+        };
+        if self.total_saved >= 0 {
+            format!(
+                "{prefix}，累计节省 {:.2} MB",
+                bytes_to_mb(self.total_saved as u64)
+            )
+        } else {
+            format!(
+                "{prefix}，文件总体增大 {:.2} MB",
+                bytes_to_mb((-self.total_saved) as u64)
+            )
+        }
+    }
+
+    fn status_text_en(&self) -> String {
+        let processed = self.processed();
+        let prefix = if let Some(reason) = &self.aborted {
+            format!(
+                "Aborted: {processed}/{} images done ({})",
+                self.total,
+                reason.render(Language::English)
+            )
+        } else if self.cancelled {
+            format!("Cancelled: {processed}/{} images done", self.total)
+        } else {
+            format!("Done: {processed} images processed")
+        };
+        let prefix = if self.kept > 0 {
+            format!("{prefix}, {} left unchanged", self.kept)
+        } else {
+            prefix
+        };
+        let prefix = if self.retries > 0 {
+            format!("{prefix} ({} read/write retries)", self.retries)
+        } else {
+            prefix
+        };
+        if self.total_saved >= 0 {
+            format!(
+                "{prefix}, {:.2} MB saved in total",
+                bytes_to_mb(self.total_saved as u64)
+            )
+        } else {
+            format!(
+                "{prefix}, files grew by {:.2} MB in total",
+                bytes_to_mb((-self.total_saved) as u64)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn incremental_skips_only_files_recorded_unchanged() {
+        let root =
+            std::env::temp_dir().join(format!("compress_img_incremental_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let old = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let write = |name: &str, contents: &str| {
+            let path = root.join(name);
+            fs::write(&path, contents).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+            path
+        };
+        let unchanged = write("unchanged.jpg", "a");
+        let edited = write("edited.jpg", "a");
+        let touched = write("touched.jpg", "a");
+        let mut index = ProcessedIndex::default();
+        for path in [&unchanged, &edited, &touched] {
+            index.record_source(path);
+        }
+        // 内容变了但保留了修改时间，或只改了修改时间，都算改动
+        write("edited.jpg", "ab");
+        fs::File::options()
+            .write(true)
+            .open(&touched)
+            .unwrap()
+            .set_modified(old + Duration::from_secs(60))
+            .unwrap();
+        // 像 cp -p 一样带着旧修改时间复制进来的新文件
+        let copied = write("copied.jpg", "a");
+
+        let files = vec![
+            unchanged.clone(),
+            edited.clone(),
+            touched.clone(),
+            copied.clone(),
+        ];
+        let (changed, skipped) = split_unchanged(files, &index);
+        assert_eq!(changed, vec![edited, touched, copied]);
+        assert_eq!(skipped, vec![unchanged]);
+        let _ = fs::remove_dir_all(root);
+    }
+}
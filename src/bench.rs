@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use image::{DynamicImage, ImageFormat};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::options::CompressionOptions;
+use crate::{bytes_to_mb, codec, savings_percent, scan};
+
+// 每个组合都要把样本完整编码一遍，AVIF 尤其慢，样本不宜多
+const SAMPLE_LIMIT: usize = 8;
+
+pub struct BenchCase {
+    pub label: String,
+    pub format: ImageFormat,
+    pub options: CompressionOptions,
+}
+
+pub struct BenchResult {
+    pub label: String,
+    pub failures: usize,
+    pub original_bytes: u64,
+    pub encoded_bytes: u64,
+    pub elapsed: Duration,
+}
+
+pub struct BenchReport {
+    pub samples: usize,
+    pub decode_time: Duration,
+    pub results: Vec<BenchResult>,
+}
+
+pub fn cases() -> Vec<BenchCase> {
+    let mut cases = Vec::new();
+    for quality in [60, 75, 85, 95] {
+        let mut options = CompressionOptions::default();
+        options.jpeg.quality = quality;
+        cases.push(BenchCase {
+            label: format!("JPEG 质量 {quality}"),
+            format: ImageFormat::Jpeg,
+            options,
+        });
+    }
+    for (effort, lossy_level) in [(2, 0), (4, 0), (6, 0), (6, 40)] {
+        let mut options = CompressionOptions::default();
+        options.png.effort = effort;
+        options.png.lossy_level = lossy_level;
+        cases.push(BenchCase {
+            label: format!("PNG 力度 {effort} 有损 {lossy_level}"),
+            format: ImageFormat::Png,
+            options,
+        });
+    }
+    for quality in [60, 75, 85, 95] {
+        let mut options = CompressionOptions::default();
+        options.webp.quality = quality;
+        cases.push(BenchCase {
+            label: format!("WebP 质量 {quality}"),
+            format: ImageFormat::WebP,
+            options,
+        });
+    }
+    for quality in [50, 65, 80] {
+        let mut options = CompressionOptions::default();
+        options.avif.quality = quality;
+        cases.push(BenchCase {
+            label: format!("AVIF 质量 {quality}"),
+            format: ImageFormat::Avif,
+            options,
+        });
+    }
+    cases
+}
+
+/// 从文件夹中抽样，把每个样本用所有编码器/参数组合在内存中编码一遍。
+/// on_progress 收到 (已完成步数, 总步数)。
+pub fn run_benchmark(
+    folder: &Path,
+    options: &CompressionOptions,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<BenchReport> {
+    let files = scan::scan_folder(folder, options).files;
+    if files.is_empty() {
+        return Err(anyhow!("文件夹中没有可用于测试的图像"));
+    }
+
+    let decode_start = Instant::now();
+    let samples: Vec<(u64, DynamicImage)> = scan::sample_evenly(&files, SAMPLE_LIMIT)
+        .iter()
+        .filter_map(|path| {
+            let size = fs::metadata(path).ok()?.len();
+            let (_, image) = codec::open_image(path).ok()?;
+            Some((size, image))
+        })
+        .collect();
+    if samples.is_empty() {
+        return Err(anyhow!("抽样的图像均无法解码"));
+    }
+    let decode_time = decode_start.elapsed();
+
+    let cases = cases();
+    let total_steps = cases.len() * samples.len();
+    let mut step = 0;
+    let mut results = Vec::with_capacity(cases.len());
+    for case in cases {
+        let mut result = BenchResult {
+            label: case.label,
+            failures: 0,
+            original_bytes: 0,
+            encoded_bytes: 0,
+            elapsed: Duration::ZERO,
+        };
+        for (size, image) in &samples {
+            let start = Instant::now();
+            let encoded = codec::encode_image(image, case.format, &case.options);
+            result.elapsed += start.elapsed();
+            match encoded {
+                Ok(buffer) => {
+                    result.original_bytes += size;
+                    result.encoded_bytes += buffer.len() as u64;
+                }
+                Err(_) => result.failures += 1,
+            }
+            step += 1;
+            on_progress(step, total_steps);
+        }
+        results.push(result);
+    }
+
+    Ok(BenchReport {
+        samples: samples.len(),
+        decode_time,
+        results,
+    })
+}
+
+impl BenchReport {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "基准测试: {} 个样本，平均解码 {:.1} ms/张\n",
+            self.samples,
+            self.decode_time.as_secs_f64() * 1000.0 / self.samples as f64
+        );
+        for result in &self.results {
+            let encoded = self.samples - result.failures;
+            if encoded == 0 {
+                text.push_str(&format!("{} | 全部失败\n", result.label));
+                continue;
+            }
+            text.push_str(&format!(
+                "{} | {:.1} ms/张 | {:.2} MB → {:.2} MB (节省 {:.2}%)",
+                result.label,
+                result.elapsed.as_secs_f64() * 1000.0 / encoded as f64,
+                bytes_to_mb(result.original_bytes),
+                bytes_to_mb(result.encoded_bytes),
+                savings_percent(result.original_bytes, result.encoded_bytes)
+            ));
+            if result.failures > 0 {
+                text.push_str(&format!(" | 失败 {} 张", result.failures));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
@@ -0,0 +1,99 @@
+//! 无界面的服务进程，可同时开启多种接口并共用任务列表:
+//! compress_img_server --allow /srv/photos --http 8080 --grpc 50051
+//! 只接受 --allow 指定的文件夹（可多次指定）内的任务；只写端口时只监听本机。
+//! --token <令牌>（或环境变量 COMPRESS_IMG_TOKEN）要求每个请求携带令牌，监听其他地址时必须设置。
+//! 可选 --webhook <URL> / --notify-email <收件人> 在每个任务结束时发送通知
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use compress_img::access::AccessPolicy;
+use compress_img::error::Language;
+use compress_img::jobs::JobManager;
+use compress_img::notify::{EmailTarget, Notifier};
+use tokio::task::JoinSet;
+
+#[cfg(not(any(feature = "http", feature = "grpc")))]
+compile_error!("compress_img_server 需要启用 http 或 grpc feature");
+
+const USAGE: &str = "用法: compress_img_server --allow <文件夹> [--http <端口或地址:端口>] \
+                     [--grpc <端口或地址:端口>] [--token <令牌>] \
+                     [--webhook <URL>] [--notify-email <收件人>]";
+const TOKEN_ENV: &str = "COMPRESS_IMG_TOKEN";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    compress_img::crash::install(compress_img::logging::init().ok());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut notifier = Notifier::default();
+    let mut listeners = Vec::new();
+    let mut roots = Vec::new();
+    let mut token = std::env::var(TOKEN_ENV).ok();
+
+    for pair in args.chunks(2) {
+        let [flag, value] = pair else {
+            return Err(anyhow!(USAGE));
+        };
+        match flag.as_str() {
+            "--allow" => roots.push(PathBuf::from(value)),
+            "--token" => token = Some(value.clone()),
+            "--webhook" => notifier.webhook_url = Some(value.clone()),
+            "--notify-email" => notifier.email = Some(EmailTarget::new(value.clone())),
+            _ => listeners.push((flag.as_str(), listen_addr(value)?)),
+        }
+    }
+    if roots.is_empty() {
+        return Err(anyhow!("需要用 --allow 指定允许处理的文件夹\n{USAGE}"));
+    }
+    let access = AccessPolicy::new(&roots, token)?;
+    if !access.requires_token()
+        && let Some((_, addr)) = listeners.iter().find(|(_, addr)| !addr.ip().is_loopback())
+    {
+        return Err(anyhow!(
+            "监听 {addr} 时其他机器也能访问，需要用 --token 或 {TOKEN_ENV} 设置令牌"
+        ));
+    }
+
+    let jobs = Arc::new(
+        JobManager::with_notifier(notifier)
+            .with_access(access)
+            .with_language(Language::detect()),
+    );
+    let mut servers = JoinSet::new();
+    for (flag, addr) in listeners {
+        match flag {
+            #[cfg(feature = "http")]
+            "--http" => {
+                eprintln!("HTTP 服务监听于 {addr}");
+                servers.spawn(compress_img::http::serve(addr, jobs.clone()));
+            }
+            #[cfg(feature = "grpc")]
+            "--grpc" => {
+                eprintln!("gRPC 服务监听于 {addr}");
+                servers.spawn(compress_img::grpc::serve(addr, jobs.clone()));
+            }
+            other => return Err(anyhow!("不支持的参数 {other}（是否缺少对应的 feature？）\n{USAGE}")),
+        }
+    }
+    if servers.is_empty() {
+        return Err(anyhow!(USAGE));
+    }
+
+    // 任意一个接口退出（通常是端口被占用）即结束进程
+    match servers.join_next().await {
+        Some(result) => result?,
+        None => Ok(()),
+    }
+}
+
+/// 只写端口时监听本机回环地址
+fn listen_addr(value: &str) -> Result<SocketAddr> {
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+    }
+    value
+        .parse()
+        .with_context(|| format!("无效的监听地址: {value}"))
+}
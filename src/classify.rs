@@ -0,0 +1,104 @@
+use image::DynamicImage;
+use std::collections::HashSet;
+
+// 每张图最多抽取的像素数量，足够区分内容类型且保持很快
+const MAX_SAMPLES: u64 = 65_536;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageClass {
+    Photo,
+    Screenshot,
+    /// 白底上的线条、图表、漫画线稿等
+    LineArt,
+    Scan,
+}
+
+impl ImageClass {
+    pub fn label(self) -> &'static str {
+        match self {
+            ImageClass::Photo => "照片",
+            ImageClass::Screenshot => "截图",
+            ImageClass::LineArt => "线稿",
+            ImageClass::Scan => "扫描件",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImageFeatures {
+    /// 与右侧像素完全相同的比例，截图的大片纯色区域会很高
+    pub flat_ratio: f32,
+    /// 近似灰度像素的比例
+    pub gray_ratio: f32,
+    /// 高亮（接近纸张白）像素的比例
+    pub bright_ratio: f32,
+    /// 量化到 15 位色后不同颜色数与样本数之比
+    pub color_ratio: f32,
+}
+
+pub fn classify(image: &DynamicImage) -> ImageClass {
+    classify_features(&measure(image))
+}
+
+pub fn classify_features(features: &ImageFeatures) -> ImageClass {
+    // 线稿与扫描件都以白底为主，但线稿几乎没有纸张噪点，大片完全相同
+    if features.bright_ratio > 0.6 && features.flat_ratio > 0.7 && features.color_ratio < 0.01 {
+        ImageClass::LineArt
+    } else if features.gray_ratio > 0.9 && features.bright_ratio > 0.5 {
+        ImageClass::Scan
+    } else if features.flat_ratio > 0.5 || features.color_ratio < 0.02 {
+        ImageClass::Screenshot
+    } else {
+        ImageClass::Photo
+    }
+}
+
+pub fn measure(image: &DynamicImage) -> ImageFeatures {
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    if width < 2 || height == 0 {
+        return ImageFeatures::default();
+    }
+
+    let pixel_count = (width as u64 - 1) * height as u64;
+    let stride = (pixel_count / MAX_SAMPLES).max(1);
+
+    let mut samples = 0u32;
+    let mut flat = 0u32;
+    let mut gray = 0u32;
+    let mut bright = 0u32;
+    let mut colors = HashSet::new();
+
+    let mut index = 0u64;
+    while index < pixel_count {
+        let x = (index % (width as u64 - 1)) as u32;
+        let y = (index / (width as u64 - 1)) as u32;
+        let [r, g, b] = rgb.get_pixel(x, y).0;
+        let right = rgb.get_pixel(x + 1, y).0;
+
+        samples += 1;
+        if right == [r, g, b] {
+            flat += 1;
+        }
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        if max - min < 16 {
+            gray += 1;
+        }
+        let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+        if luma > 200 {
+            bright += 1;
+        }
+        colors.insert(((r >> 3) as u16) << 10 | ((g >> 3) as u16) << 5 | (b >> 3) as u16);
+
+        index += stride;
+    }
+
+    let samples_f = samples as f32;
+    ImageFeatures {
+        flat_ratio: flat as f32 / samples_f,
+        gray_ratio: gray as f32 / samples_f,
+        bright_ratio: bright as f32 / samples_f,
+        color_ratio: colors.len() as f32 / samples_f,
+    }
+}
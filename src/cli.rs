@@ -0,0 +1,400 @@
+//! 命令行模式，不显示界面，供脚本和定时任务调用。进度输出到标准输出，
+//! 任一文件处理失败时返回错误，进程以非零状态退出。输出的文字按调用方传入的语言。
+
+use anyhow::{anyhow, Result};
+use image::ImageFormat;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::batch::{self, BatchEvent, BatchSummary, FileOverrides, SkippedFiles};
+use crate::control::RunControl;
+use crate::error::{render_error, Language};
+use crate::lock::LockPolicy;
+use crate::manifest::JobManifest;
+use crate::options::{CompressionOptions, OutputFormat};
+use crate::report::RunReport;
+use crate::{app_data, bench, bytes_to_mb, compress_buffer, estimate, savings_percent};
+
+const USAGE_ZH: &str = "用法:
+  compress_img --input <文件夹> [--output <文件夹>] [--quality 1-100] [--format jpeg|png|webp|avif]
+               [--jobs N] [--recursive] [--force] [--dry-run] [--report <报告.csv|报告.json>]
+               [--config <配置文件>]
+  compress_img --job <任务清单>
+  compress_img --benchmark <文件夹>
+  compress_img --stdin [--format jpeg|png|webp|avif] [--quality 1-100] < 输入 > 输出";
+
+const USAGE_EN: &str = "Usage:
+  compress_img --input <folder> [--output <folder>] [--quality 1-100] [--format jpeg|png|webp|avif]
+               [--jobs N] [--recursive] [--force] [--dry-run] [--report <report.csv|report.json>]
+               [--config <config file>]
+  compress_img --job <job manifest>
+  compress_img --benchmark <folder>
+  compress_img --stdin [--format jpeg|png|webp|avif] [--quality 1-100] < input > output";
+
+pub fn usage(language: Language) -> &'static str {
+    match language {
+        Language::Chinese => USAGE_ZH,
+        Language::English => USAGE_EN,
+    }
+}
+
+/// 按参数选择命令行功能
+pub fn run(args: &[String], language: Language) -> Result<()> {
+    match args {
+        [flag] if flag == "--help" || flag == "-h" => {
+            println!("{}", usage(language));
+            Ok(())
+        }
+        [flag, folder] if flag == "--benchmark" => run_benchmark(Path::new(folder)),
+        [flag, job] if flag == "--job" => run_job(Path::new(job), language),
+        [flag, rest @ ..] if flag == "--stdin" => run_stdin(rest, language),
+        _ => run_input(args, language),
+    }
+}
+
+/// 压缩 --input 指定的文件夹；只有加上 --recursive 才进入子文件夹。
+/// 指定 --config 时以其中的配置为基础（包括是否进入子文件夹），其他参数覆盖对应的设置；
+/// 加上 --force 时以前压缩过、之后未改动的文件也再次压缩；
+/// 加上 --dry-run 时只报告压缩后的大小，不写入任何文件；
+/// 指定 --report 时运行结束后把每个文件的结果写入报告
+fn run_input(args: &[String], language: Language) -> Result<()> {
+    let zh = language == Language::Chinese;
+    let usage = usage(language);
+    let mut input = None;
+    let mut output = None;
+    let mut config = None;
+    let mut quality = None;
+    let mut format = None;
+    let mut jobs = None;
+    let mut recursive = false;
+    let mut force = false;
+    let mut dry_run = false;
+    let mut report_path = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next().ok_or_else(|| match language {
+                Language::Chinese => anyhow!("{flag} 缺少参数值\n{usage}"),
+                Language::English => anyhow!("{flag} needs a value\n{usage}"),
+            })
+        };
+        match flag.as_str() {
+            "--recursive" | "-r" => recursive = true,
+            "--force" => force = true,
+            "--dry-run" | "-n" => dry_run = true,
+            "--input" | "-i" => input = Some(PathBuf::from(value()?)),
+            "--output" | "-o" => output = Some(PathBuf::from(value()?)),
+            "--config" | "-c" => config = Some(PathBuf::from(value()?)),
+            "--report" => report_path = Some(PathBuf::from(value()?)),
+            "--quality" | "-q" => quality = Some(parse_quality(value()?, language)?),
+            "--format" | "-f" => format = Some(parse_format(value()?, language)?),
+            "--jobs" | "-j" => {
+                let value = value()?;
+                jobs = Some(value.parse::<u32>().map_err(|_| {
+                    if zh {
+                        anyhow!("同时压缩的文件数应为整数: {value}\n{usage}")
+                    } else {
+                        anyhow!("--jobs must be a whole number: {value}\n{usage}")
+                    }
+                })?);
+            }
+            _ if zh => return Err(anyhow!("未知的参数: {flag}\n{usage}")),
+            _ => return Err(anyhow!("unknown argument: {flag}\n{usage}")),
+        }
+    }
+    let input = input.ok_or_else(|| {
+        if zh {
+            anyhow!("缺少 --input\n{usage}")
+        } else {
+            anyhow!("--input is required\n{usage}")
+        }
+    })?;
+
+    let mut options = match &config {
+        Some(path) => CompressionOptions::load(path)?,
+        None => CompressionOptions::default(),
+    };
+    // 没有 --recursive 时保留配置文件中的设置
+    if recursive || config.is_none() {
+        options.scan.recursive = recursive;
+    }
+    if force {
+        options.scan.skip_processed = false;
+    }
+    if let Some(output) = output {
+        options.output.folder = Some(output);
+    }
+    if let Some(quality) = quality {
+        set_quality(&mut options, quality);
+    }
+    if let Some(format) = format {
+        options.convert.enabled = true;
+        options.convert.format = format;
+    }
+    if let Some(jobs) = jobs {
+        options.parallel.workers = jobs;
+    }
+
+    let mut report = RunReport::default();
+    if dry_run {
+        run_preview(&input, &options, &mut report, language);
+        return save_report(&report, report_path.as_deref(), language);
+    }
+    let summary = run_folder(
+        &input,
+        &options,
+        &RunControl::default(),
+        &mut report,
+        language,
+    )?;
+    save_report(&report, report_path.as_deref(), language)?;
+    if summary.failed > 0 || summary.aborted.is_some() {
+        let failed = summary.failed;
+        return Err(if zh {
+            anyhow!("{failed} 个文件处理失败: {}", input.display())
+        } else {
+            anyhow!("{failed} files failed: {}", input.display())
+        });
+    }
+    Ok(())
+}
+
+/// 逐个文件在内存中压缩，输出与实际运行相同格式的结果和汇总。
+/// 文件列表与实际运行时一样经过忽略列表、已处理记录等过滤
+fn run_preview(
+    folder: &Path,
+    options: &CompressionOptions,
+    report: &mut RunReport,
+    language: Language,
+) {
+    let mut skipped = SkippedFiles::default();
+    let work = batch::collect_work(
+        folder,
+        options,
+        &FileOverrides::default(),
+        app_data::unix_now(),
+        |event| {
+            if let BatchEvent::Skipped { path, reason } = event {
+                report.record_skipped(&path, reason, language);
+                skipped.record(path, reason);
+            }
+        },
+    );
+    for err in &work.errors {
+        print_scan_error(err, language);
+    }
+    let total = work.files.len();
+    let (mut before, mut after, mut failed) = (0, 0, 0);
+    for (index, (_, path)) in work.files.iter().enumerate() {
+        let (estimate, outcome) = estimate::preview_file(path, options);
+        println!(
+            "[{}/{total}] {}",
+            index + 1,
+            outcome.log_line(path, language)
+        );
+        report.record(path, &outcome, language);
+        match estimate.predicted {
+            Ok(size) => {
+                before += estimate.original_size;
+                after += size;
+            }
+            Err(_) => failed += 1,
+        }
+    }
+    print!("{}", skipped.to_text(language));
+    let (before_mb, after_mb) = (bytes_to_mb(before), bytes_to_mb(after));
+    let saved = savings_percent(before, after);
+    match language {
+        Language::Chinese => println!(
+            "预览完成，未写入任何文件: {total} 个文件，{before_mb:.2} MB → {after_mb:.2} MB（节省 {saved:.1}%），{failed} 个无法处理"
+        ),
+        Language::English => println!(
+            "Preview finished, nothing was written: {total} files, {before_mb:.2} MB → {after_mb:.2} MB ({saved:.1}% saved), {failed} could not be processed"
+        ),
+    }
+}
+
+fn print_scan_error(err: &anyhow::Error, language: Language) {
+    let err = render_error(err, language);
+    match language {
+        Language::Chinese => eprintln!("遍历时出错: {err}"),
+        Language::English => eprintln!("Error while scanning: {err}"),
+    }
+}
+
+fn save_report(report: &RunReport, path: Option<&Path>, language: Language) -> Result<()> {
+    if let Some(path) = path {
+        report.save(path)?;
+        match language {
+            Language::Chinese => println!("已保存报告: {}", path.display()),
+            Language::English => println!("Report saved: {}", path.display()),
+        }
+    }
+    Ok(())
+}
+
+fn run_benchmark(folder: &Path) -> Result<()> {
+    let options = CompressionOptions::default();
+    let report = bench::run_benchmark(folder, &options, |done, total| {
+        eprint!("\r{done}/{total}");
+    })?;
+    eprintln!();
+    print!("{}", report.to_text());
+    Ok(())
+}
+
+/// 从标准输入读一张图，压缩结果写到标准输出，不读写任何文件。
+/// 未指定 --format 时保持原格式，--quality 设置 JPEG、WebP、AVIF 的质量
+fn run_stdin(args: &[String], language: Language) -> Result<()> {
+    let mut options = CompressionOptions::default();
+    // 输入是调用方明确交给我们的，所有能解码的格式都处理
+    options.jpeg.enabled = true;
+    options.png.enabled = true;
+    options.webp.enabled = true;
+    options.avif.enabled = true;
+    options.animation.gif_enabled = true;
+    for pair in args.chunks(2) {
+        let [flag, value] = pair else {
+            return Err(anyhow!(usage(language)));
+        };
+        match flag.as_str() {
+            "--format" | "-f" => {
+                options.convert.enabled = true;
+                options.convert.format = parse_format(value, language)?;
+            }
+            "--quality" | "-q" => set_quality(&mut options, parse_quality(value, language)?),
+            _ => return Err(anyhow!(usage(language))),
+        }
+    }
+
+    let mut input = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut input)?;
+    let output = compress_buffer(&input, &options)?;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&output)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// 按任务清单依次处理其中的文件夹，不询问是否覆盖
+fn run_job(path: &Path, language: Language) -> Result<()> {
+    let zh = language == Language::Chinese;
+    let job = JobManifest::load(path)?;
+    let control = RunControl::default();
+    let mut failed = false;
+    for folder in &job.folders {
+        if zh {
+            println!("处理文件夹: {}", folder.display());
+        } else {
+            println!("Processing folder: {}", folder.display());
+        }
+        let report = &mut RunReport::default();
+        match run_folder(folder, &job.options, &control, report, language) {
+            Ok(summary) => failed |= summary.failed > 0 || summary.aborted.is_some(),
+            Err(err) => {
+                let err = render_error(&err, language);
+                if zh {
+                    eprintln!("处理失败: {err}");
+                } else {
+                    eprintln!("Failed: {err}");
+                }
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(if zh {
+            anyhow!("任务中有文件处理失败: {}", path.display())
+        } else {
+            anyhow!("some files in the job failed: {}", path.display())
+        });
+    }
+    Ok(())
+}
+
+/// 压缩一个文件夹，逐个文件输出结果并记入 report；文件夹正被其他任务处理时排队等待
+fn run_folder(
+    folder: &Path,
+    options: &CompressionOptions,
+    control: &RunControl,
+    report: &mut RunReport,
+    language: Language,
+) -> Result<BatchSummary> {
+    let mut skipped = SkippedFiles::default();
+    let summary = batch::run_batch(
+        folder,
+        options,
+        LockPolicy::Wait,
+        control,
+        |_| true,
+        |event| match event {
+            BatchEvent::WaitingForLock => match language {
+                Language::Chinese => println!("文件夹正被其他任务处理，等待中..."),
+                Language::English => println!("The folder is busy with another job, waiting..."),
+            },
+            BatchEvent::Scanned {
+                total,
+                total_bytes,
+                estimated_secs,
+                errors,
+                ..
+            } => {
+                for err in &errors {
+                    print_scan_error(err, language);
+                }
+                println!(
+                    "{}",
+                    batch::pre_run_summary(total, total_bytes, estimated_secs, language)
+                );
+            }
+            BatchEvent::Skipped { path, reason } => {
+                report.record_skipped(&path, reason, language);
+                skipped.record(path, reason);
+            }
+            BatchEvent::FileFinished {
+                processed,
+                total,
+                path,
+                outcome,
+            } => {
+                report.record(&path, &outcome, language);
+                println!(
+                    "[{processed}/{total}] {}",
+                    outcome.log_line(&path, language)
+                );
+            }
+            BatchEvent::Scanning(_) => {}
+        },
+    )?;
+    print!("{}", skipped.to_text(language));
+    println!("{}", summary.status_text(language));
+    Ok(summary)
+}
+
+fn parse_quality(value: &str, language: Language) -> Result<u8> {
+    let usage = usage(language);
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|quality| (1..=100).contains(quality))
+        .ok_or_else(|| match language {
+            Language::Chinese => anyhow!("质量应为 1-100: {value}\n{usage}"),
+            Language::English => anyhow!("quality must be 1-100: {value}\n{usage}"),
+        })
+}
+
+fn parse_format(value: &str, language: Language) -> Result<OutputFormat> {
+    let usage = usage(language);
+    OutputFormat::from_name(value).ok_or_else(|| match language {
+        Language::Chinese => anyhow!("未知的输出格式: {value}\n{usage}"),
+        Language::English => anyhow!("unknown output format: {value}\n{usage}"),
+    })
+}
+
+/// 同一个质量用于所有有质量参数的格式
+fn set_quality(options: &mut CompressionOptions, quality: u8) {
+    for format in [ImageFormat::Jpeg, ImageFormat::WebP, ImageFormat::Avif] {
+        options.set_quality(format, quality);
+    }
+}
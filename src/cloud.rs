@@ -0,0 +1,39 @@
+//! 识别 OneDrive、iCloud、Dropbox 等同步盘中只在云端、尚未下载到本地的占位文件。
+//! 读取这类文件会触发下载，文件夹很大时可能意外拉取大量数据。
+
+use std::fs::Metadata;
+use std::path::Path;
+
+/// 元数据读取失败时按本地文件处理，交给后续读取报告错误
+pub fn is_placeholder(path: &Path) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|metadata| is_placeholder_metadata(&metadata))
+}
+
+// Windows 云文件 API（OneDrive、Dropbox 等）用文件属性标记未下载的文件
+#[cfg(windows)]
+fn is_placeholder_metadata(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS, FILE_ATTRIBUTE_RECALL_ON_OPEN,
+    };
+
+    const CLOUD_ONLY: u32 = FILE_ATTRIBUTE_OFFLINE
+        | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS
+        | FILE_ATTRIBUTE_RECALL_ON_OPEN;
+    metadata.file_attributes() & CLOUD_ONLY != 0
+}
+
+// macOS 上 iCloud 和文件提供程序把未下载的文件标记为 dataless
+#[cfg(target_os = "macos")]
+fn is_placeholder_metadata(metadata: &Metadata) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    const SF_DATALESS: u32 = 0x4000_0000;
+    metadata.st_flags() & SF_DATALESS != 0
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn is_placeholder_metadata(_metadata: &Metadata) -> bool {
+    false
+}
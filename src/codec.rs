@@ -0,0 +1,602 @@
+use anyhow::{anyhow, Result};
+use color_quant::NeuQuant;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{
+    imageops, DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, ImageReader, Limits,
+};
+use std::borrow::Cow;
+use std::io::{BufRead, Cursor, Seek};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use crate::error::CompressError;
+use crate::metadata::ImageMetadata;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::options::{ChromaSubsampling, JpegEncoderKind, JpegOptions};
+use crate::options::{CompressionOptions, PngOptions, ResizeFilter, ResizeOptions};
+use crate::png_opt;
+
+const AVIF_ENCODER_SPEED: u8 = 6;
+const MIN_PALETTE_COLORS: usize = 8;
+
+// IJG 标准亮度量化表，质量 50 时的取值
+const STANDARD_LUMA_TABLE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+// mozjpeg 默认（网格量化开启时）使用的亮度表，即其 jcparam.c 中编号为 3 的 ImageMagick 表，
+// 同样按 IJG 的方式随质量缩放
+const MOZJPEG_LUMA_TABLE: [u16; 64] = [
+    16, 16, 16, 18, 25, 37, 56, 85, 16, 17, 20, 27, 34, 40, 53, 75, 16, 20, 24, 31, 43, 62, 91,
+    135, 18, 27, 31, 40, 53, 74, 106, 156, 25, 34, 43, 53, 69, 94, 131, 189, 37, 40, 62, 74, 94,
+    124, 169, 238, 56, 53, 91, 106, 131, 169, 226, 311, 85, 75, 135, 156, 189, 238, 311, 418,
+];
+
+/// 服务接口提交的任务解码时的限制，防止很小的文件解压出巨大的图像（解压炸弹）
+const UNTRUSTED_MAX_SIDE: u32 = 32_768;
+const UNTRUSTED_MAX_ALLOC: u64 = 1024 * 1024 * 1024;
+
+/// 超过这个大小的源文件用内存映射读取，不整个复制到堆上
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const MMAP_THRESHOLD: u64 = 32 * 1024 * 1024;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_image(path: &Path) -> Result<(ImageFormat, DynamicImage), CompressError> {
+    let unreadable = |source| CompressError::Read {
+        path: path.to_path_buf(),
+        source,
+        retries: 0,
+    };
+    let file = std::fs::File::open(path).map_err(unreadable)?;
+    let decoded = if file.metadata().is_ok_and(|m| m.len() >= MMAP_THRESHOLD) {
+        // SAFETY: 映射只在解码期间存在，本进程不会同时改写这个文件
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(unreadable)?;
+        decode_buffer(&map)
+    } else {
+        decode(ImageReader::new(std::io::BufReader::new(file)), false)
+    };
+    decoded.map_err(|err| err.at(path))
+}
+
+pub fn decode_buffer(bytes: &[u8]) -> Result<(ImageFormat, DynamicImage), CompressError> {
+    decode(ImageReader::new(Cursor::new(bytes)), false)
+}
+
+/// 按 options 解码：服务接口提交的任务限制尺寸和内存，本地任务不限制，超大的照片也能处理
+pub fn decode_for(
+    bytes: &[u8],
+    options: &CompressionOptions,
+) -> Result<(ImageFormat, DynamicImage), CompressError> {
+    decode(
+        ImageReader::new(Cursor::new(bytes)),
+        options.untrusted_input,
+    )
+}
+
+/// 按亮度量化表反推 JPEG 的大致质量（IJG 标尺 1-100），不是 JPEG 或没有量化表时返回 None。
+/// 依次假设文件用的是 IJG 标准表或 mozjpeg 的默认表，取缩放后与实际最接近的质量
+pub fn estimate_jpeg_quality(bytes: &[u8]) -> Option<u8> {
+    let mut actual = luma_quant_table(bytes)?;
+    // 文件中的表按之字形顺序存放，排序后比较即可，不必还原位置
+    actual.sort_unstable();
+    // 只有不限定基线时取值才会超过 255
+    let limit = if actual.iter().any(|&value| value > 255) {
+        32_767
+    } else {
+        255
+    };
+    [&STANDARD_LUMA_TABLE, &MOZJPEG_LUMA_TABLE]
+        .into_iter()
+        .flat_map(|base| (1..=100u8).map(move |quality| (base, quality)))
+        .min_by_key(|&(base, quality)| {
+            let scaled = scale_quant_table(base, quality, limit);
+            scaled
+                .iter()
+                .zip(&actual)
+                .map(|(&a, &b)| a.abs_diff(b) as u32)
+                .sum::<u32>()
+        })
+        .map(|(_, quality)| quality)
+}
+
+// 与 libjpeg 的 jpeg_add_quant_table 相同: 质量 q < 50 时 scale = 5000 / q，否则 scale = 200 - 2q
+fn scale_quant_table(base: &[u16; 64], quality: u8, limit: u16) -> Vec<u16> {
+    let quality = quality as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let mut scaled: Vec<u16> = base
+        .iter()
+        .map(|&value| ((value as u32 * scale + 50) / 100).clamp(1, limit as u32) as u16)
+        .collect();
+    scaled.sort_unstable();
+    scaled
+}
+
+// 找到编号为 0 的量化表（通常是亮度表）
+fn luma_quant_table(bytes: &[u8]) -> Option<Vec<u16>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        match marker {
+            // 填充字节
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // 没有长度字段的标记
+            0x01 | 0xD0..=0xD7 => {
+                pos += 2;
+                continue;
+            }
+            // 到达图像数据或结尾仍未找到
+            0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xDB {
+            let mut offset = 0;
+            while offset < segment.len() {
+                let precision = segment[offset] >> 4;
+                let id = segment[offset] & 0x0F;
+                offset += 1;
+                let size = if precision == 0 { 64 } else { 128 };
+                let data = segment.get(offset..offset + size)?;
+                offset += size;
+                if id == 0 {
+                    return Some(if precision == 0 {
+                        data.iter().map(|&value| value as u16).collect()
+                    } else {
+                        data.chunks_exact(2)
+                            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                            .collect()
+                    });
+                }
+            }
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+fn decode<R: BufRead + Seek>(
+    mut reader: ImageReader<R>,
+    untrusted: bool,
+) -> Result<(ImageFormat, DynamicImage), CompressError> {
+    if untrusted {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(UNTRUSTED_MAX_SIDE);
+        limits.max_image_height = Some(UNTRUSTED_MAX_SIDE);
+        limits.max_alloc = Some(UNTRUSTED_MAX_ALLOC);
+        reader.limits(limits);
+    } else {
+        reader.no_limits();
+    }
+    let unknown = CompressError::UnknownFormat { path: None };
+    reader = reader.with_guessed_format().map_err(|_| unknown)?;
+
+    let format = reader
+        .format()
+        .ok_or(CompressError::UnknownFormat { path: None })?;
+
+    let image = reader.decode().map_err(|err| CompressError::Decode {
+        path: None,
+        detail: err.to_string().trim_end().to_string(),
+    })?;
+    Ok((format, image))
+}
+
+/// 超过设置的最大宽高时等比缩小，不需要缩小时返回 None
+pub fn limit_dimensions(image: &DynamicImage, options: &ResizeOptions) -> Option<DynamicImage> {
+    let (width, height) = options.fit(image.width(), image.height())?;
+    let filter = match options.filter {
+        ResizeFilter::Lanczos3 => imageops::FilterType::Lanczos3,
+        ResizeFilter::Triangle => imageops::FilterType::Triangle,
+    };
+    #[cfg(all(feature = "gpu", not(target_arch = "wasm32")))]
+    if options.gpu
+        && let Some(resized) = resize_on_gpu(image, width, height, filter)
+    {
+        return Some(resized);
+    }
+    Some(image.resize_exact(width, height, filter))
+}
+
+/// 只处理足够大的 8 位图像，结果换回原来的颜色类型；失败时返回 None 改用 CPU
+#[cfg(all(feature = "gpu", not(target_arch = "wasm32")))]
+fn resize_on_gpu(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: imageops::FilterType,
+) -> Option<DynamicImage> {
+    let eight_bit = matches!(
+        image,
+        DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+    );
+    if !eight_bit || (image.width() as u64 * image.height() as u64) < crate::gpu::MIN_PIXELS {
+        return None;
+    }
+    let resized = match crate::gpu::resize(&image.to_rgba8(), width, height, filter) {
+        Ok(resized) => DynamicImage::ImageRgba8(resized),
+        Err(err) => {
+            log::debug!("GPU 缩放失败，改用 CPU: {err:#}");
+            return None;
+        }
+    };
+    Some(match image {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(resized.to_luma8()),
+        DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(resized.to_luma_alpha8()),
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(resized.to_rgb8()),
+        _ => resized,
+    })
+}
+
+/// 按 format 对应的设置把图像编码到内存
+pub fn encode_image(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>> {
+    encode_with_metadata(image, format, options, &ImageMetadata::default())
+}
+
+/// 同 [`encode_image`]，并把 metadata 写入 JPEG 和 PNG，其他格式忽略
+pub fn encode_with_metadata(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &CompressionOptions,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>> {
+    let denoised;
+    let image = if options.denoise.strength > 0 && options.is_lossy(format) {
+        denoised = denoise(image, options.denoise.strength);
+        &denoised
+    } else {
+        image
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => {
+            let opaque;
+            let image = if image.color().has_alpha() {
+                // 每个带透明通道的文件都会经过这里，只在调试日志中记录
+                log::debug!("JPEG 不支持透明通道，编码时已丢弃");
+                opaque = DynamicImage::ImageRgb8(image.to_rgb8());
+                &opaque
+            } else {
+                image
+            };
+            match options.jpeg.encoder {
+                #[cfg(not(target_arch = "wasm32"))]
+                JpegEncoderKind::Mozjpeg => {
+                    *cursor.get_mut() = encode_mozjpeg(image, &options.jpeg, metadata)?;
+                }
+                _ => {
+                    let mut encoder =
+                        JpegEncoder::new_with_quality(&mut cursor, options.jpeg.quality.max(1));
+                    if let Some(icc) = &metadata.icc {
+                        encoder.set_icc_profile(icc.clone())?;
+                    }
+                    if let Some(exif) = &metadata.exif {
+                        encoder.set_exif_metadata(exif.clone())?;
+                    }
+                    encoder.encode_image(image)?;
+                }
+            }
+        }
+        ImageFormat::Png => {
+            encode_png(&mut cursor, image, &options.png, metadata)?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        ImageFormat::WebP => {
+            // webp 只接受 8 位 RGB 和 RGBA，灰度和 16 位图像先转换
+            let (width, height) = (image.width(), image.height());
+            let rgba;
+            let rgb;
+            let encoder = if image.color().has_alpha() {
+                rgba = image.to_rgba8();
+                webp::Encoder::from_rgba(&rgba, width, height)
+            } else {
+                rgb = image.to_rgb8();
+                webp::Encoder::from_rgb(&rgb, width, height)
+            };
+            let encoded = if options.webp.lossless {
+                encoder.encode_lossless()
+            } else {
+                encoder.encode(options.webp.quality.max(1) as f32)
+            };
+            cursor.get_mut().extend_from_slice(&encoded);
+        }
+        // libwebp 无法编译到 wasm32，浏览器中退回到 image 自带的无损编码器
+        #[cfg(target_arch = "wasm32")]
+        ImageFormat::WebP => {
+            image
+                .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut cursor))?;
+        }
+        ImageFormat::Avif => {
+            let rgba = image.to_rgba8();
+            let (width, height) = rgba.dimensions();
+            let encoder = AvifEncoder::new_with_speed_quality(
+                &mut cursor,
+                AVIF_ENCODER_SPEED,
+                options.avif.quality.max(1),
+            );
+            encoder.write_image(rgba.as_raw(), width, height, ExtendedColorType::Rgba8)?;
+        }
+        other => {
+            return Err(CompressError::UnsupportedEncode(other).into());
+        }
+    }
+    Ok(cursor.into_inner())
+}
+
+// mozjpeg 出错时直接 panic，需要在这里捕获
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_mozjpeg(
+    image: &DynamicImage,
+    options: &JpegOptions,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>> {
+    let (color_space, pixels) = match image {
+        DynamicImage::ImageLuma8(gray) => (
+            mozjpeg::ColorSpace::JCS_GRAYSCALE,
+            Cow::Borrowed(gray.as_raw()),
+        ),
+        _ => (
+            mozjpeg::ColorSpace::JCS_RGB,
+            Cow::Owned(image.to_rgb8().into_raw()),
+        ),
+    };
+    let encode = || -> std::io::Result<Vec<u8>> {
+        let mut compress = mozjpeg::Compress::new(color_space);
+        if !options.trellis {
+            // 回到 libjpeg-turbo 的默认设置，关闭网格量化，仍然优化哈夫曼表
+            compress.set_fastest_defaults();
+            compress.set_optimize_coding(true);
+        }
+        compress.set_size(image.width() as usize, image.height() as usize);
+        compress.set_quality(options.quality.max(1) as f32);
+        if options.progressive {
+            compress.set_progressive_mode();
+        } else {
+            compress.set_optimize_scans(false);
+        }
+        if color_space == mozjpeg::ColorSpace::JCS_RGB {
+            let size = match options.subsampling {
+                ChromaSubsampling::S420 => (2, 2),
+                ChromaSubsampling::S444 => (1, 1),
+            };
+            compress.set_chroma_sampling_pixel_sizes(size, size);
+        }
+        let mut started = compress.start_compress(Vec::new())?;
+        if let Some(icc) = metadata.icc.as_deref().filter(|icc| !icc.is_empty()) {
+            started.write_icc_profile(icc);
+        }
+        if let Some(exif) = &metadata.exif {
+            started.write_marker(
+                mozjpeg::Marker::APP(1),
+                &[b"Exif\0\0", exif.as_slice()].concat(),
+            );
+        }
+        started.write_scanlines(&pixels)?;
+        started.finish()
+    };
+    std::panic::catch_unwind(encode)
+        .map_err(|_| anyhow!("mozjpeg 编码失败"))?
+        .map_err(Into::into)
+}
+
+fn encode_png(
+    cursor: &mut Cursor<Vec<u8>>,
+    image: &DynamicImage,
+    options: &PngOptions,
+    metadata: &ImageMetadata,
+) -> Result<()> {
+    if options.lossy_level == 0 {
+        let optimized = png_opt::optimize(image, options, metadata)?;
+        cursor.get_mut().extend_from_slice(&optimized);
+        return Ok(());
+    }
+
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    // 有损模式: 用 NeuQuant 量化成调色板后写出索引色 PNG
+    let colors = palette_size(options.lossy_level);
+    let sample_factor = match options.effort {
+        0..=2 => 30,
+        3..=4 => 10,
+        _ => 1,
+    };
+    let quantizer = NeuQuant::new(sample_factor, colors, rgba.as_raw());
+    let indices: Vec<u8> = rgba
+        .pixels()
+        .map(|pixel| quantizer.index_of(&pixel.0) as u8)
+        .collect();
+    let color_map = quantizer.color_map_rgba();
+    let palette: Vec<u8> = color_map
+        .chunks_exact(4)
+        .flat_map(|entry| [entry[0], entry[1], entry[2]])
+        .collect();
+    let alpha: Vec<u8> = color_map.chunks_exact(4).map(|entry| entry[3]).collect();
+
+    let mut info = png::Info::with_size(width, height);
+    info.icc_profile = metadata.icc.as_deref().map(Cow::Borrowed);
+    info.exif_metadata = metadata.exif.as_deref().map(Cow::Borrowed);
+    let mut encoder = png::Encoder::with_info(cursor, info)?;
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    if alpha.iter().any(|&a| a != u8::MAX) {
+        encoder.set_trns(alpha);
+    }
+    encoder.set_compression(match options.effort {
+        0..=2 => png::Compression::Fast,
+        3..=4 => png::Compression::Balanced,
+        _ => png::Compression::High,
+    });
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&indices)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 与模糊结果相差不大的像素视为噪点，向模糊值靠拢；相差大的视为边缘，保留原值
+fn denoise(image: &DynamicImage, strength: u8) -> DynamicImage {
+    let strength = strength.min(100) as f32 / 100.0;
+    let sigma = 0.6 + 1.4 * strength;
+    let threshold = 6.0 + 34.0 * strength;
+
+    let mut pixels = image.to_rgba8();
+    let blurred = imageops::fast_blur(&pixels, sigma);
+    for (pixel, smooth) in pixels.pixels_mut().zip(blurred.pixels()) {
+        let difference = (0..3)
+            .map(|channel| (smooth[channel] as f32 - pixel[channel] as f32).abs())
+            .fold(0.0, f32::max);
+        let weight = (1.0 - difference / threshold).clamp(0.0, 1.0);
+        for channel in 0..3 {
+            let original = pixel[channel] as f32;
+            pixel[channel] =
+                (original + (smooth[channel] as f32 - original) * weight).round() as u8;
+        }
+    }
+
+    if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(pixels)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(pixels).to_rgb8())
+    }
+}
+
+fn palette_size(lossy_level: u8) -> usize {
+    let level = lossy_level.clamp(1, 100) as usize;
+    256 - (level - 1) * (256 - MIN_PALETTE_COLORS) / 99
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+            Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8])
+        }))
+    }
+
+    #[test]
+    fn estimates_ijg_jpeg_quality() {
+        for quality in [20, 50, 75, 90] {
+            let mut bytes = Vec::new();
+            gradient()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))
+                .unwrap();
+            let estimated = estimate_jpeg_quality(&bytes).unwrap();
+            assert!(
+                estimated.abs_diff(quality) <= 2,
+                "质量 {quality} 估计为 {estimated}"
+            );
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn estimates_mozjpeg_quality() {
+        for trellis in [true, false] {
+            for quality in [30, 60, 85, 95] {
+                let options = JpegOptions {
+                    quality,
+                    trellis,
+                    ..JpegOptions::default()
+                };
+                let bytes =
+                    encode_mozjpeg(&gradient(), &options, &ImageMetadata::default()).unwrap();
+                let estimated = estimate_jpeg_quality(&bytes).unwrap();
+                assert!(
+                    estimated.abs_diff(quality) <= 2,
+                    "mozjpeg（网格量化 {trellis}）质量 {quality} 估计为 {estimated}"
+                );
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn encodes_grayscale_and_16_bit_images_as_webp() {
+        let gray = DynamicImage::ImageLuma8(image::GrayImage::from_fn(32, 24, |x, y| {
+            image::Luma([(x * 8 + y) as u8])
+        }));
+        // 完全不透明时 libwebp 会省掉 alpha，这里让透明度有变化
+        let gray_alpha =
+            DynamicImage::ImageLumaA8(image::GrayAlphaImage::from_fn(32, 24, |x, y| {
+                image::LumaA([(x * 8) as u8, (y * 10) as u8])
+            }));
+        let deep = DynamicImage::ImageRgb16(gradient().to_rgb16());
+        let options = CompressionOptions::default();
+        for image in [gray, gray_alpha, deep] {
+            let encoded = encode_image(&image, ImageFormat::WebP, &options).unwrap();
+            let (format, decoded) = decode_buffer(&encoded).unwrap();
+            assert_eq!(format, ImageFormat::WebP);
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (image.width(), image.height())
+            );
+            assert_eq!(decoded.color().has_alpha(), image.color().has_alpha());
+        }
+    }
+
+    #[test]
+    fn untrusted_input_rejects_huge_dimensions() {
+        let mut png = Vec::new();
+        gradient()
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        // 把 IHDR 中的宽高改成 100000 x 100000，并重新计算校验值
+        png[16..20].copy_from_slice(&100_000u32.to_be_bytes());
+        png[20..24].copy_from_slice(&100_000u32.to_be_bytes());
+        let crc = crc32fast::hash(&png[12..29]);
+        png[29..33].copy_from_slice(&crc.to_be_bytes());
+
+        let options = CompressionOptions {
+            untrusted_input: true,
+            ..CompressionOptions::default()
+        };
+        assert!(matches!(
+            decode_for(&png, &options),
+            Err(CompressError::Decode { .. })
+        ));
+        let mut small = Vec::new();
+        gradient()
+            .write_to(&mut Cursor::new(&mut small), ImageFormat::Png)
+            .unwrap();
+        assert!(decode_for(&small, &options).is_ok());
+    }
+
+    #[test]
+    fn quality_of_non_jpeg_is_unknown() {
+        let mut png = Vec::new();
+        gradient()
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        assert_eq!(estimate_jpeg_quality(&png), None);
+        // 量化表之前就被截断
+        assert_eq!(estimate_jpeg_quality(&[0xFF, 0xD8, 0xFF, 0xDB, 0x00]), None);
+    }
+}
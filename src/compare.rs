@@ -0,0 +1,249 @@
+//! 对比源文件夹和输出文件夹：按相对路径把每个源文件对应到输出文件，
+//! 报告每个文件和每个文件夹的体积变化、缺失的文件和变大的文件，
+//! 方便在删除原文件之前核对输出到其他文件夹的结果。不修改任何文件。
+
+use anyhow::{anyhow, Result};
+use ignore::WalkBuilder;
+use image::ImageFormat;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::options::CompressionOptions;
+use crate::{bytes_to_kb, bytes_to_mb, savings_percent, scan};
+
+pub struct FileComparison {
+    /// 相对于源文件夹的路径
+    pub relative: PathBuf,
+    pub source_size: u64,
+    /// 对应的输出文件（相对于输出文件夹）及其大小，没有找到时为 None
+    pub output: Option<(PathBuf, u64)>,
+}
+
+impl FileComparison {
+    pub fn grew(&self) -> bool {
+        self.output
+            .as_ref()
+            .is_some_and(|(_, size)| *size > self.source_size)
+    }
+}
+
+/// 一个文件夹及其所有子文件夹的合计，字节数只统计找到了输出的文件
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FolderTotals {
+    pub files: usize,
+    pub missing: usize,
+    pub source_bytes: u64,
+    pub output_bytes: u64,
+}
+
+pub struct TreeComparison {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub files: Vec<FileComparison>,
+    /// 以相对路径为键，根目录为空路径
+    pub folders: BTreeMap<PathBuf, FolderTotals>,
+    /// 输出文件夹中没有对应源文件的图像
+    pub extra: Vec<PathBuf>,
+}
+
+/// 需要设置了输出文件夹；重命名模板生成的文件名无法按路径对应，不支持
+pub fn compare_trees(source: &Path, options: &CompressionOptions) -> Result<TreeComparison> {
+    let output = options
+        .output
+        .folder
+        .clone()
+        .ok_or_else(|| anyhow!("未设置输出文件夹，没有可对比的输出"))?;
+    if !options.output.rename_template.trim().is_empty() {
+        return Err(anyhow!(
+            "使用重命名模板时输出文件名与源文件不对应，无法按路径对比"
+        ));
+    }
+    if !output.is_dir() {
+        return Err(anyhow!("输出文件夹不存在: {}", output.display()));
+    }
+
+    // 输出文件按去掉扩展名的相对路径分组，转换格式后扩展名会变
+    let mut outputs: HashMap<PathBuf, Vec<(PathBuf, u64)>> = HashMap::new();
+    for entry in WalkBuilder::new(&output)
+        .standard_filters(false)
+        .build()
+        .flatten()
+    {
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file()) || ImageFormat::from_path(path).is_err()
+        {
+            continue;
+        }
+        let relative = path.strip_prefix(&output).unwrap_or(path).to_path_buf();
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        outputs
+            .entry(relative.with_extension(""))
+            .or_default()
+            .push((relative, size));
+    }
+
+    let convert_extension = options
+        .convert
+        .enabled
+        .then(|| options.convert.format.extension());
+    let mut files = Vec::new();
+    for path in scan::scan_folder(source, options).files {
+        let relative = path.strip_prefix(source).unwrap_or(&path).to_path_buf();
+        let source_size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        let output = outputs
+            .get_mut(&relative.with_extension(""))
+            .and_then(|candidates| {
+                let extension = relative.extension();
+                let preferred = candidates
+                    .iter()
+                    .position(|(candidate, _)| candidate.extension() == extension)
+                    .or_else(|| {
+                        candidates.iter().position(|(candidate, _)| {
+                            convert_extension.is_some_and(|ext| {
+                                candidate
+                                    .extension()
+                                    .is_some_and(|c| c.eq_ignore_ascii_case(ext))
+                            })
+                        })
+                    })
+                    .unwrap_or(0);
+                (!candidates.is_empty()).then(|| candidates.swap_remove(preferred))
+            });
+        files.push(FileComparison {
+            relative,
+            source_size,
+            output,
+        });
+    }
+    if files.is_empty() {
+        return Err(anyhow!("源文件夹中没有可对比的图像"));
+    }
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+
+    let mut folders: BTreeMap<PathBuf, FolderTotals> = BTreeMap::new();
+    for file in &files {
+        for folder in file.relative.ancestors().skip(1) {
+            let totals = folders.entry(folder.to_path_buf()).or_default();
+            totals.files += 1;
+            match &file.output {
+                Some((_, size)) => {
+                    totals.source_bytes += file.source_size;
+                    totals.output_bytes += size;
+                }
+                None => totals.missing += 1,
+            }
+        }
+    }
+
+    let mut extra: Vec<PathBuf> = outputs
+        .into_values()
+        .flatten()
+        .map(|(relative, _)| relative)
+        .collect();
+    extra.sort();
+
+    Ok(TreeComparison {
+        source: source.to_path_buf(),
+        output,
+        files,
+        folders,
+        extra,
+    })
+}
+
+impl TreeComparison {
+    pub fn missing(&self) -> impl Iterator<Item = &FileComparison> {
+        self.files.iter().filter(|file| file.output.is_none())
+    }
+
+    pub fn grown(&self) -> impl Iterator<Item = &FileComparison> {
+        self.files.iter().filter(|file| file.grew())
+    }
+
+    pub fn totals(&self) -> FolderTotals {
+        self.folders.get(Path::new("")).copied().unwrap_or_default()
+    }
+
+    pub fn to_text(&self) -> String {
+        let totals = self.totals();
+        let missing: Vec<_> = self.missing().collect();
+        let grown: Vec<_> = self.grown().collect();
+        let mut text = format!(
+            "对比 {} → {}\n共 {} 个源文件，{} 个缺少输出，{} 个变大，输出中多出 {} 个文件\n已输出部分: {:.2} MB → {:.2} MB（节省 {:.1}%）\n",
+            self.source.display(),
+            self.output.display(),
+            totals.files,
+            missing.len(),
+            grown.len(),
+            self.extra.len(),
+            bytes_to_mb(totals.source_bytes),
+            bytes_to_mb(totals.output_bytes),
+            savings_percent(totals.source_bytes, totals.output_bytes)
+        );
+
+        text.push_str("\n按文件夹（含子文件夹）:\n");
+        for (folder, totals) in &self.folders {
+            let name = if folder.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                folder.display().to_string()
+            };
+            text.push_str(&format!(
+                "  {name} | {} 个文件 | {:.2} MB → {:.2} MB（{:+.1}%）",
+                totals.files,
+                bytes_to_mb(totals.source_bytes),
+                bytes_to_mb(totals.output_bytes),
+                -savings_percent(totals.source_bytes, totals.output_bytes)
+            ));
+            if totals.missing > 0 {
+                text.push_str(&format!(" | 缺少 {} 个", totals.missing));
+            }
+            text.push('\n');
+        }
+
+        if !missing.is_empty() {
+            text.push_str(&format!("\n缺少输出（{} 个）:\n", missing.len()));
+            for file in &missing {
+                text.push_str(&format!("  ✖ {}\n", file.relative.display()));
+            }
+        }
+        if !grown.is_empty() {
+            text.push_str(&format!("\n输出比源文件大（{} 个）:\n", grown.len()));
+            for file in &grown {
+                text.push_str(&format!("  ▲ {}\n", file_line(file)));
+            }
+        }
+        if !self.extra.is_empty() {
+            text.push_str(&format!("\n输出中多出的文件（{} 个）:\n", self.extra.len()));
+            for path in &self.extra {
+                text.push_str(&format!("  ? {}\n", path.display()));
+            }
+        }
+
+        text.push_str("\n逐个文件:\n");
+        for file in &self.files {
+            if file.output.is_some() {
+                text.push_str(&format!("  {}\n", file_line(file)));
+            }
+        }
+        text
+    }
+}
+
+fn file_line(file: &FileComparison) -> String {
+    let Some((output, size)) = &file.output else {
+        return file.relative.display().to_string();
+    };
+    let renamed = if *output != file.relative {
+        format!(" → {}", output.display())
+    } else {
+        String::new()
+    };
+    format!(
+        "{}{renamed} | {:.1} KB → {:.1} KB（{:+.1}%）",
+        file.relative.display(),
+        bytes_to_kb(file.source_size),
+        bytes_to_kb(*size),
+        -savings_percent(file.source_size, *size)
+    )
+}
@@ -0,0 +1,48 @@
+//! 运行中的批量任务的取消和暂停。界面线程和处理线程共享同一个 RunControl，
+//! 批量处理在两个文件之间检查它，正在处理的文件总会完整写完。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+#[derive(Default)]
+pub struct RunControl {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl RunControl {
+    /// 取消同时解除暂停，让等待中的线程尽快退出
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.set_paused(false);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        if let Ok(mut state) = self.paused.lock() {
+            *state = paused;
+        }
+        self.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.lock().is_ok_and(|paused| *paused)
+    }
+
+    /// 暂停期间阻塞，直到恢复或取消
+    pub fn wait_while_paused(&self) {
+        let Ok(paused) = self.paused.lock() else {
+            return;
+        };
+        drop(self.resumed.wait_while(paused, |paused| *paused));
+    }
+
+    /// 只关心取消的地方（如排队等待文件夹锁）使用
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+}
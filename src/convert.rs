@@ -0,0 +1,210 @@
+//! “全部转换为一种格式”模式下转换结果的命名
+
+use anyhow::Result;
+use image::ImageFormat;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::animation;
+use crate::error::CompressError;
+use crate::options::{CollisionRule, CompressionOptions, ConvertOptions};
+
+/// 按扩展名判断 path 在转换模式下会写到哪里，不需要转换时返回 None
+pub fn converted_path(path: &Path, options: &CompressionOptions) -> Option<PathBuf> {
+    let convert = &options.convert;
+    if !convert.enabled {
+        return None;
+    }
+    let input = ImageFormat::from_path(path).ok()?;
+    (input != convert.format.image_format())
+        .then(|| path.with_extension(convert.format.extension()))
+}
+
+/// 原地转换时，跳过规则下转换结果已经存在，说明之前转换过
+pub fn already_converted(path: &Path, options: &CompressionOptions) -> bool {
+    options.output.folder.is_none()
+        && options.convert.collision == CollisionRule::Skip
+        && converted_path(path, options).is_some_and(|target| target.exists())
+}
+
+/// 按冲突规则确定最终写入的文件。reserved 为本次运行中已分配给其他文件的名字，
+/// 视同已存在，覆盖规则下也不覆盖它们
+pub fn resolve_output(
+    path: &Path,
+    convert: &ConvertOptions,
+    reserved: &HashSet<PathBuf>,
+) -> Result<PathBuf> {
+    let target = path.with_extension(convert.format.extension());
+    let taken = |candidate: &Path| reserved.contains(candidate) || candidate.exists();
+    if !taken(&target) {
+        return Ok(target);
+    }
+    match convert.collision {
+        CollisionRule::Overwrite if !reserved.contains(&target) => Ok(target),
+        CollisionRule::Overwrite | CollisionRule::Skip => {
+            Err(CompressError::TargetExists(target).into())
+        }
+        CollisionRule::Rename => {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (1..)
+                .map(|index| {
+                    path.with_file_name(format!("{stem}-{index}.{}", convert.format.extension()))
+                })
+                .find(|candidate| !taken(candidate))
+                .ok_or_else(|| CompressError::NoFreeName.into())
+        }
+    }
+}
+
+/// 按源文件内容预计的（输入格式, 写出格式），与 compress_image 的判断相同：
+/// 动图只能保持原格式或转为 WebP。无法识别格式时返回 None
+pub fn planned_format(
+    input: &[u8],
+    options: &CompressionOptions,
+) -> Option<(ImageFormat, ImageFormat)> {
+    let format = image::guess_format(input).ok()?;
+    let target = if animation::is_animation(input, format) {
+        options.for_animation().output_format(format)
+    } else {
+        options.output_format(format)
+    };
+    Some((format, target))
+}
+
+/// 批量处理时在派发线程上为原地转换的 path 预留新文件名，并行的工作线程之间
+/// 不会再为同名的 a.jpg 和 a.png 选中同一个 a.webp。input 为已读入的源文件内容，
+/// 动图按 for_animation 的格式；不需要转换时返回 None
+pub fn reserve_output(
+    path: &Path,
+    input: &[u8],
+    options: &CompressionOptions,
+    reserved: &mut HashSet<PathBuf>,
+) -> Result<Option<PathBuf>> {
+    let Some((format, target)) = planned_format(input, options) else {
+        return Ok(None);
+    };
+    if target == format {
+        return Ok(None);
+    }
+    let animation_options;
+    let options = if animation::is_animation(input, format) {
+        animation_options = options.for_animation();
+        &animation_options
+    } else {
+        options
+    };
+    let target = resolve_output(path, &options.convert, reserved)?;
+    reserved.insert(target.clone());
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OutputFormat;
+    use std::fs;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "compress_img_convert_{name}_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn webp(collision: CollisionRule) -> ConvertOptions {
+        ConvertOptions {
+            enabled: true,
+            format: OutputFormat::Webp,
+            collision,
+            ..ConvertOptions::default()
+        }
+    }
+
+    #[test]
+    fn free_target_is_used_under_every_rule() {
+        let root = temp_folder("free");
+        let source = root.join("photo.png");
+        for rule in [
+            CollisionRule::Skip,
+            CollisionRule::Overwrite,
+            CollisionRule::Rename,
+        ] {
+            assert_eq!(
+                resolve_output(&source, &webp(rule), &HashSet::new()).unwrap(),
+                root.join("photo.webp")
+            );
+        }
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn existing_target_follows_collision_rule() {
+        let root = temp_folder("taken");
+        let source = root.join("photo.png");
+        fs::write(root.join("photo.webp"), b"").unwrap();
+        fs::write(root.join("photo-1.webp"), b"").unwrap();
+
+        let none = HashSet::new();
+        assert!(resolve_output(&source, &webp(CollisionRule::Skip), &none).is_err());
+        assert_eq!(
+            resolve_output(&source, &webp(CollisionRule::Overwrite), &none).unwrap(),
+            root.join("photo.webp")
+        );
+        assert_eq!(
+            resolve_output(&source, &webp(CollisionRule::Rename), &none).unwrap(),
+            root.join("photo-2.webp")
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn same_named_sources_get_different_reserved_targets() {
+        let root = temp_folder("reserved");
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(2, 2)
+            .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(2, 2)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        let mut reserved = HashSet::new();
+
+        let mut options = CompressionOptions {
+            convert: webp(CollisionRule::Rename),
+            ..CompressionOptions::default()
+        };
+        let first = reserve_output(&root.join("photo.png"), &png, &options, &mut reserved);
+        let second = reserve_output(&root.join("photo.jpg"), &jpeg, &options, &mut reserved);
+        assert_eq!(first.unwrap(), Some(root.join("photo.webp")));
+        assert_eq!(second.unwrap(), Some(root.join("photo-1.webp")));
+
+        // 覆盖规则只覆盖已有的文件，本次运行中的另一个结果不能被覆盖
+        options.convert.collision = CollisionRule::Overwrite;
+        let mut reserved = HashSet::new();
+        assert!(reserve_output(&root.join("photo.png"), &png, &options, &mut reserved).is_ok());
+        assert!(reserve_output(&root.join("photo.jpg"), &jpeg, &options, &mut reserved).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn converted_path_skips_files_already_in_the_target_format() {
+        let mut options = CompressionOptions {
+            convert: webp(CollisionRule::Skip),
+            ..CompressionOptions::default()
+        };
+        assert_eq!(
+            converted_path(Path::new("a/photo.jpg"), &options),
+            Some(PathBuf::from("a/photo.webp"))
+        );
+        assert_eq!(converted_path(Path::new("a/photo.webp"), &options), None);
+        options.convert.enabled = false;
+        assert_eq!(converted_path(Path::new("a/photo.jpg"), &options), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! 崩溃报告：panic 时在数据目录的 crashes/<时间>/ 下写入报告、
+//! 诊断日志末尾和当时的设置（路径已脱敏），下次启动时提示用户查看。
+
+use anyhow::{Context, Result};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::app_data;
+use crate::options::CompressionOptions;
+
+const CRASH_DIR_NAME: &str = "crashes";
+const REPORT_FILE_NAME: &str = "report.txt";
+const LOG_FILE_NAME: &str = "log_tail.txt";
+const SETTINGS_FILE_NAME: &str = "settings.json";
+// 用户已看过提示的报告会留下该标记
+const SEEN_MARKER: &str = ".seen";
+const LOG_TAIL_LINES: usize = 200;
+
+// 最近一次开始处理时的文件夹和设置，崩溃时一并写入报告
+static CONTEXT: Mutex<Option<String>> = Mutex::new(None);
+
+/// 在 logging::init 之后调用，使诊断日志先记下崩溃信息
+pub fn install(log_path: Option<PathBuf>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        log::logger().flush();
+        let message = format!("{info}");
+        let _ = write_bundle(&message, log_path.as_deref());
+    }));
+}
+
+/// 记录当前处理的文件夹和设置
+pub fn set_context(folder: &Path, options: &CompressionOptions) {
+    let snapshot = serde_json::json!({
+        "folder": redact(&folder.display().to_string()),
+        "options": options,
+    });
+    if let Ok(text) = serde_json::to_string_pretty(&snapshot)
+        && let Ok(mut context) = CONTEXT.lock()
+    {
+        *context = Some(text);
+    }
+}
+
+/// 尚未提示过用户的崩溃报告目录，最新的在前
+pub fn pending_reports() -> Result<Vec<PathBuf>> {
+    let dir = crashes_dir()?;
+    let mut reports = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("无法读取目录: {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.join(REPORT_FILE_NAME).exists() && !path.join(SEEN_MARKER).exists() {
+            reports.push(path);
+        }
+    }
+    reports.sort();
+    reports.reverse();
+    Ok(reports)
+}
+
+pub fn mark_seen(report: &Path) -> Result<()> {
+    fs::write(report.join(SEEN_MARKER), "")
+        .with_context(|| format!("无法更新崩溃报告: {}", report.display()))
+}
+
+fn write_bundle(message: &str, log_path: Option<&Path>) -> Result<PathBuf> {
+    let now = app_data::unix_now();
+    let dir = crashes_dir()?.join(format!("{now}-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let current = thread::current();
+    let mut report = fs::File::create(dir.join(REPORT_FILE_NAME))?;
+    writeln!(report, "版本: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        report,
+        "系统: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(report, "时间: {} (UTC)", app_data::format_datetime(now))?;
+    writeln!(report, "线程: {}", current.name().unwrap_or("<未命名>"))?;
+    writeln!(report, "错误: {}", redact(message))?;
+    writeln!(report)?;
+    writeln!(report, "调用栈:")?;
+    writeln!(
+        report,
+        "{}",
+        redact(&Backtrace::force_capture().to_string())
+    )?;
+
+    // panic 可能发生在持锁期间，拿不到锁时不等待
+    if let Ok(context) = CONTEXT.try_lock()
+        && let Some(settings) = context.as_ref()
+    {
+        fs::write(dir.join(SETTINGS_FILE_NAME), settings)?;
+    }
+    if let Some(log_path) = log_path
+        && let Ok(text) = fs::read_to_string(log_path)
+    {
+        let lines: Vec<&str> = text.lines().collect();
+        let tail = lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n");
+        fs::write(dir.join(LOG_FILE_NAME), redact(&tail))?;
+    }
+    Ok(dir)
+}
+
+// 用户主目录替换为 ~，避免报告中带出用户名
+fn redact(text: &str) -> String {
+    match dirs::home_dir() {
+        Some(home) if !home.as_os_str().is_empty() => {
+            text.replace(&home.display().to_string(), "~")
+        }
+        _ => text.to_string(),
+    }
+}
+
+fn crashes_dir() -> Result<PathBuf> {
+    let dir = app_data::data_dir()?.join(CRASH_DIR_NAME);
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建目录: {}", dir.display()))?;
+    Ok(dir)
+}
@@ -0,0 +1,229 @@
+//! 近似重复检测：对每张图计算 dHash 和 pHash，
+//! 两种哈希都足够接近的图像视为同一组（缩放副本、重新保存等）。
+
+use anyhow::{anyhow, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::options::CompressionOptions;
+use crate::{bytes_to_mb, codec, scan};
+
+// 64 位哈希允许的最大汉明距离，两种哈希都不超过才算重复
+const MAX_DISTANCE: u32 = 8;
+const PHASH_SIZE: usize = 32;
+const PHASH_LOW: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageHash {
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+impl ImageHash {
+    pub fn of(image: &DynamicImage) -> Self {
+        Self {
+            dhash: dhash(image),
+            phash: phash(image),
+        }
+    }
+
+    pub fn is_near(&self, other: &ImageHash) -> bool {
+        (self.dhash ^ other.dhash).count_ones() <= MAX_DISTANCE
+            && (self.phash ^ other.phash).count_ones() <= MAX_DISTANCE
+    }
+}
+
+pub struct DuplicateFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 同一组内按分辨率从高到低、体积从小到大排序，第一张建议保留
+pub struct DuplicateGroup {
+    pub files: Vec<DuplicateFile>,
+}
+
+impl DuplicateGroup {
+    /// 删除除第一张以外的文件可节省的字节数
+    pub fn redundant_bytes(&self) -> u64 {
+        self.files.iter().skip(1).map(|file| file.size).sum()
+    }
+}
+
+pub struct DuplicateReport {
+    pub scanned: usize,
+    pub failures: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// on_progress 收到 (已计算哈希的文件数, 文件总数)
+pub fn find_duplicates(
+    folder: &Path,
+    options: &CompressionOptions,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<DuplicateReport> {
+    let files = scan::scan_folder(folder, options).files;
+    if files.is_empty() {
+        return Err(anyhow!("文件夹中没有可分析的图像"));
+    }
+
+    let total = files.len();
+    let mut hashed = Vec::with_capacity(total);
+    let mut failures = 0;
+    for (index, path) in files.into_iter().enumerate() {
+        match hash_file(&path) {
+            Some((file, hash)) => hashed.push((file, hash)),
+            None => failures += 1,
+        }
+        on_progress(index + 1, total);
+    }
+
+    // 并查集：两两比较，把相近的图像合并到同一组
+    let mut parent: Vec<usize> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if hashed[i].1.is_near(&hashed[j].1) {
+                let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<DuplicateFile>> = (0..hashed.len()).map(|_| Vec::new()).collect();
+    for (index, (file, _)) in hashed.into_iter().enumerate() {
+        let root = find_root(&mut parent, index);
+        members[root].push(file);
+    }
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_iter()
+        .filter(|files| files.len() > 1)
+        .map(|mut files| {
+            files.sort_by(|a, b| {
+                let pixels = |f: &DuplicateFile| f.width as u64 * f.height as u64;
+                pixels(b).cmp(&pixels(a)).then(a.size.cmp(&b.size))
+            });
+            DuplicateGroup { files }
+        })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.redundant_bytes()));
+
+    Ok(DuplicateReport {
+        scanned: total,
+        failures,
+        groups,
+    })
+}
+
+impl DuplicateReport {
+    pub fn redundant_bytes(&self) -> u64 {
+        self.groups
+            .iter()
+            .map(DuplicateGroup::redundant_bytes)
+            .sum()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "重复检测: 分析 {} 张图像，发现 {} 组近似重复，删除多余副本可节省 {:.2} MB\n",
+            self.scanned,
+            self.groups.len(),
+            bytes_to_mb(self.redundant_bytes())
+        );
+        if self.failures > 0 {
+            text.push_str(&format!("{} 张图像无法解码，已跳过\n", self.failures));
+        }
+        for (index, group) in self.groups.iter().enumerate() {
+            text.push_str(&format!("\n第 {} 组:\n", index + 1));
+            for (position, file) in group.files.iter().enumerate() {
+                let mark = if position == 0 { "保留" } else { "重复" };
+                text.push_str(&format!(
+                    "  [{mark}] {} ({}x{}, {:.2} MB)\n",
+                    file.path.display(),
+                    file.width,
+                    file.height,
+                    bytes_to_mb(file.size)
+                ));
+            }
+        }
+        text
+    }
+}
+
+fn hash_file(path: &Path) -> Option<(DuplicateFile, ImageHash)> {
+    let size = fs::metadata(path).ok()?.len();
+    let (_, image) = codec::open_image(path).ok()?;
+    let (width, height) = image.dimensions();
+    let file = DuplicateFile {
+        path: path.to_path_buf(),
+        size,
+        width,
+        height,
+    };
+    Some((file, ImageHash::of(&image)))
+}
+
+fn find_root(parent: &mut [usize], mut index: usize) -> usize {
+    while parent[index] != index {
+        parent[index] = parent[parent[index]];
+        index = parent[index];
+    }
+    index
+}
+
+// 缩到 9x8 灰度，逐行比较相邻像素的明暗
+fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | bit as u64;
+        }
+    }
+    hash
+}
+
+// 缩到 32x32 灰度做 DCT，取左上角 8x8 低频系数与其中位数比较
+fn phash(image: &DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(PHASH_SIZE as u32, PHASH_SIZE as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    let n = PHASH_SIZE as f64;
+    let cosines: Vec<f64> = (0..PHASH_LOW * PHASH_SIZE)
+        .map(|i| {
+            let (u, x) = (i / PHASH_SIZE, i % PHASH_SIZE);
+            ((2 * x + 1) as f64 * u as f64 * PI / (2.0 * n)).cos()
+        })
+        .collect();
+    let mut coefficients = Vec::with_capacity(PHASH_LOW * PHASH_LOW);
+    for v in 0..PHASH_LOW {
+        for u in 0..PHASH_LOW {
+            let mut sum = 0.0;
+            for y in 0..PHASH_SIZE {
+                for x in 0..PHASH_SIZE {
+                    sum += pixels[y * PHASH_SIZE + x]
+                        * cosines[u * PHASH_SIZE + x]
+                        * cosines[v * PHASH_SIZE + y];
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    // 直流分量只反映整体亮度，不参与中位数
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    coefficients
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
@@ -0,0 +1,357 @@
+//! 压缩核心产生的错误。核心只构造带结构的错误，文字由界面层按语言渲染：
+//! 图形界面目前只有中文，命令行和服务进程跟随系统语言，用 [`render_error`] 渲染整条错误链。
+//! Display 使用默认语言（中文），供日志和图形界面使用。
+
+use image::ImageFormat;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    Chinese,
+    English,
+}
+
+impl Language {
+    /// 按 BCP 47 语言标签选择，如 `zh-CN`、`en_US.UTF-8`；非中文一律用英文
+    pub fn from_locale(tag: &str) -> Self {
+        if tag.to_ascii_lowercase().starts_with("zh") {
+            Language::Chinese
+        } else {
+            Language::English
+        }
+    }
+
+    /// 系统语言，取不到时用中文
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn detect() -> Self {
+        sys_locale::get_locale().map_or(Language::Chinese, |tag| Self::from_locale(&tag))
+    }
+}
+
+/// 按 language 渲染 anyhow 的整条错误链，格式与 `{:#}` 相同；
+/// 核心的结构化错误按语言渲染，其他错误（多为下层库的）原样输出
+pub fn render_error(err: &anyhow::Error, language: Language) -> String {
+    err.chain()
+        .map(|cause| {
+            if let Some(err) = cause.downcast_ref::<CompressError>() {
+                err.render(language)
+            } else if let Some(err) = cause.downcast_ref::<ConfigError>() {
+                err.render(language)
+            } else {
+                cause.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+#[derive(Debug)]
+pub enum CompressError {
+    FolderNotFound(PathBuf),
+    NotAFolder(PathBuf),
+    /// retries 为遇到暂时性错误后重试过的次数
+    Read {
+        path: PathBuf,
+        source: io::Error,
+        retries: u32,
+    },
+    UnknownFormat {
+        path: Option<PathBuf>,
+    },
+    /// detail 来自解码库，不做翻译
+    Decode {
+        path: Option<PathBuf>,
+        detail: String,
+    },
+    FormatDisabled(ImageFormat),
+    UnsupportedEncode(ImageFormat),
+    Encode {
+        path: PathBuf,
+        detail: String,
+    },
+    CreateDir {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+        retries: u32,
+    },
+    RemoveOriginal {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// 转换格式时目标文件已存在
+    TargetExists(PathBuf),
+    NoFreeName,
+}
+
+impl CompressError {
+    /// 补上解码时还不知道的文件路径
+    pub fn at(self, path: &Path) -> Self {
+        match self {
+            CompressError::UnknownFormat { path: None } => CompressError::UnknownFormat {
+                path: Some(path.to_path_buf()),
+            },
+            CompressError::Decode { path: None, detail } => CompressError::Decode {
+                path: Some(path.to_path_buf()),
+                detail,
+            },
+            other => other,
+        }
+    }
+
+    pub fn render(&self, language: Language) -> String {
+        let zh = language == Language::Chinese;
+        let retried = |retries: u32| match (retries, zh) {
+            (0, _) => String::new(),
+            (n, true) => format!("（已重试 {n} 次）"),
+            (n, false) => format!(" (retried {n} times)"),
+        };
+        let located = |path: &Option<PathBuf>| match path {
+            Some(path) => format!(": {}", path.display()),
+            None => String::new(),
+        };
+        match self {
+            CompressError::FolderNotFound(path) if zh => format!("路径不存在: {}", path.display()),
+            CompressError::FolderNotFound(path) => {
+                format!("Path does not exist: {}", path.display())
+            }
+            CompressError::NotAFolder(path) if zh => {
+                format!("选择的路径不是文件夹: {}", path.display())
+            }
+            CompressError::NotAFolder(path) => format!("Not a folder: {}", path.display()),
+            CompressError::Read {
+                path,
+                source,
+                retries,
+            } if zh => format!(
+                "无法读取文件: {}: {source}{}",
+                path.display(),
+                retried(*retries)
+            ),
+            CompressError::Read {
+                path,
+                source,
+                retries,
+            } => format!(
+                "Cannot read file: {}: {source}{}",
+                path.display(),
+                retried(*retries)
+            ),
+            CompressError::UnknownFormat { path } if zh => {
+                format!("无法确定图像格式{}", located(path))
+            }
+            CompressError::UnknownFormat { path } => {
+                format!("Unrecognized image format{}", located(path))
+            }
+            CompressError::Decode { path, detail } if zh => {
+                format!("无法解码图像{}: {detail}", located(path))
+            }
+            CompressError::Decode { path, detail } => {
+                format!("Cannot decode image{}: {detail}", located(path))
+            }
+            CompressError::FormatDisabled(format) if zh => {
+                format!("未启用 {format:?} 格式的压缩")
+            }
+            CompressError::FormatDisabled(format) => {
+                format!("Compression of {format:?} is disabled")
+            }
+            CompressError::UnsupportedEncode(format) if zh => {
+                format!("暂不支持重新编码 {format:?} 格式")
+            }
+            CompressError::UnsupportedEncode(format) => {
+                format!("Re-encoding {format:?} is not supported")
+            }
+            CompressError::Encode { path, detail } if zh => {
+                format!("无法重新编码图像: {}: {detail}", path.display())
+            }
+            CompressError::Encode { path, detail } => {
+                format!("Cannot re-encode image: {}: {detail}", path.display())
+            }
+            CompressError::CreateDir { path, source } if zh => {
+                format!("无法创建输出目录: {}: {source}", path.display())
+            }
+            CompressError::CreateDir { path, source } => {
+                format!("Cannot create output folder: {}: {source}", path.display())
+            }
+            CompressError::Write {
+                path,
+                source,
+                retries,
+            } if zh => format!(
+                "无法写入压缩结果: {}: {source}{}",
+                path.display(),
+                retried(*retries)
+            ),
+            CompressError::Write {
+                path,
+                source,
+                retries,
+            } => format!(
+                "Cannot write result: {}: {source}{}",
+                path.display(),
+                retried(*retries)
+            ),
+            CompressError::RemoveOriginal { path, source } if zh => {
+                format!("无法删除原文件: {}: {source}", path.display())
+            }
+            CompressError::RemoveOriginal { path, source } => {
+                format!("Cannot remove original: {}: {source}", path.display())
+            }
+            CompressError::TargetExists(path) if zh => {
+                format!("目标文件已存在: {}", path.display())
+            }
+            CompressError::TargetExists(path) => {
+                format!("Target file already exists: {}", path.display())
+            }
+            CompressError::NoFreeName if zh => "无法确定转换后的文件名".to_string(),
+            CompressError::NoFreeName => {
+                "Cannot find a free name for the converted file".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Language::default()))
+    }
+}
+
+// 文字中已经包含下层的 io 错误，不再通过 source 暴露，免得 {:#} 输出两遍
+impl std::error::Error for CompressError {}
+
+/// 读写配置和解析扫描规则时的错误
+#[derive(Debug)]
+pub enum ConfigError {
+    /// detail 来自 serde_json，不做翻译；path 为 None 时配置不是从文件读入的
+    InvalidJson {
+        path: Option<PathBuf>,
+        detail: String,
+    },
+    InvalidFields {
+        path: Option<PathBuf>,
+        detail: String,
+    },
+    TooNew {
+        path: Option<PathBuf>,
+        version: u64,
+        supported: u32,
+    },
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// 包含或排除规则写错；detail 来自 globset
+    InvalidPattern {
+        exclude: bool,
+        pattern: String,
+        detail: String,
+    },
+}
+
+impl ConfigError {
+    /// 补上解析时还不知道的配置文件路径
+    pub fn at(self, file: &Path) -> Self {
+        let file = Some(file.to_path_buf());
+        match self {
+            ConfigError::InvalidJson { detail, .. } => {
+                ConfigError::InvalidJson { path: file, detail }
+            }
+            ConfigError::InvalidFields { detail, .. } => {
+                ConfigError::InvalidFields { path: file, detail }
+            }
+            ConfigError::TooNew {
+                version, supported, ..
+            } => ConfigError::TooNew {
+                path: file,
+                version,
+                supported,
+            },
+            other => other,
+        }
+    }
+
+    pub fn render(&self, language: Language) -> String {
+        let zh = language == Language::Chinese;
+        let located = |path: &Option<PathBuf>| match path {
+            Some(path) => format!(": {}", path.display()),
+            None => String::new(),
+        };
+        match self {
+            ConfigError::InvalidJson { path, detail } if zh => {
+                format!("配置不是有效的 JSON{}: {detail}", located(path))
+            }
+            ConfigError::InvalidJson { path, detail } => {
+                format!("Settings are not valid JSON{}: {detail}", located(path))
+            }
+            ConfigError::InvalidFields { path, detail } if zh => {
+                format!("配置字段无效{}: {detail}", located(path))
+            }
+            ConfigError::InvalidFields { path, detail } => {
+                format!("Invalid settings{}: {detail}", located(path))
+            }
+            ConfigError::TooNew {
+                path,
+                version,
+                supported,
+            } if zh => format!(
+                "配置版本 {version} 高于当前程序支持的版本 {supported}{}",
+                located(path)
+            ),
+            ConfigError::TooNew {
+                path,
+                version,
+                supported,
+            } => format!(
+                "Settings version {version} is newer than the supported version {supported}{}",
+                located(path)
+            ),
+            ConfigError::Read { path, source } if zh => {
+                format!("无法读取配置文件: {}: {source}", path.display())
+            }
+            ConfigError::Read { path, source } => {
+                format!("Cannot read settings file: {}: {source}", path.display())
+            }
+            ConfigError::Write { path, source } if zh => {
+                format!("无法写入配置文件: {}: {source}", path.display())
+            }
+            ConfigError::Write { path, source } => {
+                format!("Cannot write settings file: {}: {source}", path.display())
+            }
+            ConfigError::InvalidPattern {
+                exclude,
+                pattern,
+                detail,
+            } if zh => format!(
+                "{}规则无效: {pattern}: {detail}",
+                if *exclude { "排除" } else { "包含" }
+            ),
+            ConfigError::InvalidPattern {
+                exclude,
+                pattern,
+                detail,
+            } => format!(
+                "Invalid {} pattern: {pattern}: {detail}",
+                if *exclude { "exclude" } else { "include" }
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Language::default()))
+    }
+}
+
+impl std::error::Error for ConfigError {}
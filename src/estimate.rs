@@ -0,0 +1,154 @@
+//! 开始压缩前逐个预估输出大小：从图像各处截取原尺寸的小块拼成一张样图试编码，
+//! 再按像素数换算回整张图。缩小图像会让每个像素的细节变多，估出的体积偏大。
+//! 结果只是近似值，用来看出收益集中在哪些文件、哪些文件压了也没用。
+//! 预览则完整编码每个文件，大小是准确的，但慢得多。
+
+use anyhow::Result;
+use image::{imageops, DynamicImage, GenericImageView, ImageFormat};
+use std::path::{Path, PathBuf};
+
+use crate::batch::FileOutcome;
+use crate::metadata::ImageMetadata;
+use crate::options::CompressionOptions;
+use crate::profile::ContentProfile;
+use crate::{classify, codec, savings_percent, target_size};
+
+/// 样图由 SAMPLE_GRID × SAMPLE_GRID 个边长 SAMPLE_TILE 的小块拼成
+const SAMPLE_GRID: u32 = 4;
+const SAMPLE_TILE: u32 = 128;
+
+#[derive(Clone, Debug)]
+pub struct SizeEstimate {
+    pub path: PathBuf,
+    pub original_size: u64,
+    /// 预计的输出大小，源文件无法解码时为错误信息
+    pub predicted: Result<u64, String>,
+}
+
+impl SizeEstimate {
+    /// 预计节省的字节数，预计变大或无法预估时为 0
+    pub fn predicted_saving(&self) -> u64 {
+        self.predicted
+            .as_ref()
+            .map_or(0, |&size| self.original_size.saturating_sub(size))
+    }
+}
+
+pub fn estimate_file(path: &Path, options: &CompressionOptions) -> SizeEstimate {
+    let original_size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    SizeEstimate {
+        path: path.to_path_buf(),
+        original_size,
+        predicted: predict_size(path, original_size, options).map_err(|err| format!("{err:#}")),
+    }
+}
+
+/// 在内存中完整编码得到准确的大小，同时返回与实际运行时格式相同的结果，不写任何文件
+pub fn preview_file(path: &Path, options: &CompressionOptions) -> (SizeEstimate, FileOutcome) {
+    let original_size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    let (predicted, outcome) = match crate::preview_image(path, options) {
+        Ok(stats) => (Ok(stats.new_size), FileOutcome::Compressed(stats)),
+        Err(err) => (Err(format!("{err:#}")), FileOutcome::Failed(err.into())),
+    };
+    let estimate = SizeEstimate {
+        path: path.to_path_buf(),
+        original_size,
+        predicted,
+    };
+    (estimate, outcome)
+}
+
+fn predict_size(path: &Path, original_size: u64, options: &CompressionOptions) -> Result<u64> {
+    let (format, image) = codec::open_image(path)?;
+
+    // 与实际压缩时一样按内容类型调整参数
+    let tuned;
+    let options = if options.auto_tune.enabled {
+        let mut adjusted = options.clone();
+        ContentProfile::from(classify::classify(&image))
+            .bundle()
+            .apply_to(&mut adjusted);
+        tuned = adjusted;
+        &tuned
+    } else {
+        options
+    };
+    let for_source = options.for_source(format);
+    let options = for_source.as_ref();
+    let target = options.output_format(format);
+    // 与实际压缩时一样先缩小，缩小过的文件一定重新编码
+    let resized = codec::limit_dimensions(&image, &options.resize);
+    let unchanged = target == format && resized.is_none();
+    let image = resized.unwrap_or(image);
+
+    if let Some(max_bytes) = options.target_size.max_bytes() {
+        if unchanged && original_size <= max_bytes {
+            return Ok(original_size);
+        }
+        let sized = target_size::encode_to_size(
+            &image,
+            target,
+            options,
+            &ImageMetadata::default(),
+            max_bytes,
+        )?;
+        return Ok(sized.bytes.len() as u64);
+    }
+
+    // 会被原样保留的低质量 JPEG
+    if format == ImageFormat::Jpeg
+        && unchanged
+        && options.jpeg.keep_low_quality_sources
+        && let Ok(bytes) = std::fs::read(path)
+        && codec::estimate_jpeg_quality(&bytes).is_some_and(|q| q <= options.jpeg.quality)
+    {
+        return Ok(original_size);
+    }
+
+    let predicted = match probe_size(&image, target, options)? {
+        Some(size) => size,
+        None => codec::encode_image(&image, target, options)?.len() as u64,
+    };
+    // 预计收益低于探测阈值或没有变小的文件实际处理时会保持原样
+    let threshold = options.probe.min_saving_percent;
+    let no_gain = predicted >= original_size && !options.output.force_rewrite;
+    if unchanged
+        && (no_gain
+            || threshold > 0 && savings_percent(original_size, predicted) < threshold as f64)
+    {
+        return Ok(original_size);
+    }
+    Ok(predicted)
+}
+
+/// 用样图预估 image 编码为 target 后的大小。图像不比样图大时返回 None，
+/// 这时直接完整编码的代价和预估差不多
+pub(crate) fn probe_size(
+    image: &DynamicImage,
+    target: ImageFormat,
+    options: &CompressionOptions,
+) -> Result<Option<u64>> {
+    let (width, height) = image.dimensions();
+    let side = SAMPLE_GRID * SAMPLE_TILE;
+    if width <= side || height <= side {
+        return Ok(None);
+    }
+    let mut sample = DynamicImage::new(side, side, image.color());
+    for row in 0..SAMPLE_GRID {
+        for column in 0..SAMPLE_GRID {
+            // 小块均匀分布在整张图上
+            let x = (width - SAMPLE_TILE) * column / (SAMPLE_GRID - 1);
+            let y = (height - SAMPLE_TILE) * row / (SAMPLE_GRID - 1);
+            let tile = image.crop_imm(x, y, SAMPLE_TILE, SAMPLE_TILE);
+            imageops::replace(
+                &mut sample,
+                &tile,
+                (column * SAMPLE_TILE) as i64,
+                (row * SAMPLE_TILE) as i64,
+            );
+        }
+    }
+    let sample_size = codec::encode_image(&sample, target, options)?.len() as f64;
+    let ratio = (width as u64 * height as u64) as f64 / (side as u64 * side as u64) as f64;
+    Ok(Some((sample_size * ratio).round() as u64))
+}
@@ -0,0 +1,178 @@
+//! 跨运行记录每个文件连续失败的次数，以及“不再处理”的忽略列表。
+//! 忽略列表既有反复失败后自动加入的文件，也有用户手动加入的文件和文件夹。
+//! 保存时只合并本次改动过的条目，同时运行的任务不会抹掉彼此的记录。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app_data::{self, path_key};
+
+const STORE_FILE_NAME: &str = "failures.json";
+/// 源文件夹下存放反复失败文件的子文件夹，扫描时总是跳过
+pub const REVIEW_DIR_NAME: &str = "_compress_img_review";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureStore {
+    /// 键为规范化后的文件路径
+    failures: BTreeMap<String, FailureRecord>,
+    ignored: BTreeMap<String, IgnoredFile>,
+    /// 载入后改动过的键，保存时据此合并
+    #[serde(skip)]
+    changed_failures: BTreeSet<String>,
+    #[serde(skip)]
+    changed_ignored: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureRecord {
+    pub count: u32,
+    pub last_error: String,
+    pub last_failed: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IgnoredFile {
+    pub reason: String,
+    pub added_at: u64,
+}
+
+impl FailureStore {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&store_path()?)
+    }
+
+    /// 重新读出磁盘上的记录，合并本次改动后写回
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&store_path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        app_data::with_file_lock(path, || Self::read(path))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        app_data::with_file_lock(path, || {
+            // 文件损坏时以本次内容为准
+            let mut current = Self::read(path).unwrap_or_else(|err| {
+                log::warn!("{err:#}");
+                Self::default()
+            });
+            app_data::merge_changed(
+                &mut current.failures,
+                &self.failures,
+                &self.changed_failures,
+            );
+            app_data::merge_changed(&mut current.ignored, &self.ignored, &self.changed_ignored);
+            app_data::write_atomic(path, serde_json::to_string_pretty(&current)?.as_bytes())
+                .context("无法写入失败记录")
+        })
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("无法读取失败记录: {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("无法解析失败记录: {}", path.display()))
+    }
+
+    /// 记一次失败，返回该文件连续失败的次数
+    pub fn record_failure(&mut self, path: &Path, error: &str) -> u32 {
+        let key = path_key(path);
+        self.changed_failures.insert(key.clone());
+        let record = self.failures.entry(key).or_default();
+        record.count += 1;
+        record.last_error = error.to_string();
+        record.last_failed = app_data::unix_now();
+        record.count
+    }
+
+    /// 成功处理后清零
+    pub fn record_success(&mut self, path: &Path) {
+        let key = path_key(path);
+        if self.failures.remove(&key).is_some() {
+            self.changed_failures.insert(key);
+        }
+    }
+
+    /// path 本身或它所在的任一上级文件夹在忽略列表中
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        // 列表为空时省去逐个文件规范化路径的开销
+        if self.ignored.is_empty() {
+            return false;
+        }
+        let key = path_key(path);
+        Path::new(&key)
+            .ancestors()
+            .any(|ancestor| self.ignored.contains_key(&ancestor.display().to_string()))
+    }
+
+    /// 按路径排序的忽略列表
+    pub fn ignored(&self) -> impl Iterator<Item = (&str, &IgnoredFile)> {
+        self.ignored.iter().map(|(key, file)| (key.as_str(), file))
+    }
+
+    /// 从忽略列表中移除，key 为 ignored() 返回的路径
+    pub fn unignore(&mut self, key: &str) -> bool {
+        self.changed_ignored.insert(key.to_string());
+        self.ignored.remove(key).is_some()
+    }
+
+    pub fn ignore(&mut self, path: &Path, reason: &str) {
+        let key = path_key(path);
+        self.ignored.insert(
+            key.clone(),
+            IgnoredFile {
+                reason: reason.to_string(),
+                added_at: app_data::unix_now(),
+            },
+        );
+        self.changed_ignored.insert(key.clone());
+        self.failures.remove(&key);
+        self.changed_failures.insert(key);
+    }
+}
+
+fn store_path() -> Result<PathBuf> {
+    Ok(app_data::data_dir()?.join(STORE_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_sessions_keep_each_others_records() {
+        let root =
+            std::env::temp_dir().join(format!("compress_img_failures_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let store = root.join(STORE_FILE_NAME);
+        let (a, b, c) = (root.join("a.jpg"), root.join("b.jpg"), root.join("c.jpg"));
+        let mut earlier = FailureStore::default();
+        earlier.ignore(&c, "手动设为不再处理");
+        earlier.save_to(&store).unwrap();
+
+        let mut first = FailureStore::load_from(&store).unwrap();
+        let mut second = FailureStore::load_from(&store).unwrap();
+        first.record_failure(&a, "无法解码");
+        first.unignore(&path_key(&c));
+        second.ignore(&b, "手动设为不再处理");
+        second.record_success(&a);
+        first.save_to(&store).unwrap();
+        second.save_to(&store).unwrap();
+
+        let merged = FailureStore::load_from(&store).unwrap();
+        assert_eq!(merged.failures[&path_key(&a)].count, 1);
+        assert!(merged.is_ignored(&b));
+        assert!(!merged.is_ignored(&c));
+        let _ = fs::remove_dir_all(root);
+    }
+}
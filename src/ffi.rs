@@ -0,0 +1,131 @@
+//! C ABI，声明见 include/compress_img.h。
+//! 所有函数失败时返回非零值，错误信息通过 compress_img_last_error 获取。
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use anyhow::{anyhow, Result};
+
+use crate::options::CompressionOptions;
+
+const STATUS_OK: i32 = 0;
+const STATUS_ERROR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+#[repr(C)]
+pub struct CiBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// 压缩内存中的图像，成功时结果写入 out，需用 compress_img_free_buffer 释放。
+///
+/// # Safety
+/// input 必须指向 input_len 个可读字节；options_json 为 NULL 或以 NUL 结尾的 UTF-8 字符串；
+/// out 必须是可写的有效指针。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn compress_img_compress_buffer(
+    input: *const u8,
+    input_len: usize,
+    options_json: *const c_char,
+    out: *mut CiBuffer,
+) -> i32 {
+    guard(|| {
+        if input.is_null() || out.is_null() {
+            return Err(anyhow!("input 和 out 不能为空指针"));
+        }
+        let options = unsafe { parse_options(options_json) }?;
+        let input = unsafe { std::slice::from_raw_parts(input, input_len) };
+        let buffer = crate::compress_buffer(input, &options)?.into_boxed_slice();
+        let len = buffer.len();
+        let data = Box::into_raw(buffer) as *mut u8;
+        unsafe { out.write(CiBuffer { data, len }) };
+        Ok(())
+    })
+}
+
+/// 原地压缩一个文件，original_size / new_size 可以为 NULL。
+///
+/// # Safety
+/// path 必须是以 NUL 结尾的 UTF-8 字符串；options_json 为 NULL 或以 NUL 结尾的 UTF-8 字符串；
+/// original_size、new_size 为 NULL 或可写的有效指针。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn compress_img_compress_file(
+    path: *const c_char,
+    options_json: *const c_char,
+    original_size: *mut u64,
+    new_size: *mut u64,
+) -> i32 {
+    guard(|| {
+        let path = unsafe { c_str(path) }?;
+        let options = unsafe { parse_options(options_json) }?;
+        let stats = crate::compress_image(Path::new(path), &options)?;
+        if !original_size.is_null() {
+            unsafe { original_size.write(stats.original_size) };
+        }
+        if !new_size.is_null() {
+            unsafe { new_size.write(stats.new_size) };
+        }
+        Ok(())
+    })
+}
+
+/// 释放 compress_img_compress_buffer 返回的缓冲区，可以重复传入已清空的缓冲区。
+///
+/// # Safety
+/// buffer 必须来自 compress_img_compress_buffer，且尚未释放。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn compress_img_free_buffer(buffer: CiBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+    let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+    drop(unsafe { Box::from_raw(slice) });
+}
+
+/// 当前线程上一次失败的错误信息，没有错误时返回 NULL。
+/// 返回的指针在本线程下一次调用本库函数之前有效。
+#[unsafe(no_mangle)]
+pub extern "C" fn compress_img_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+fn guard(body: impl FnOnce() -> Result<()>) -> i32 {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|_| Err(anyhow!("压缩库内部发生 panic")));
+    let (status, message) = match result {
+        Ok(()) => (STATUS_OK, None),
+        Err(err) => {
+            let text = format!("{err:#}").replace('\0', " ");
+            (STATUS_ERROR, CString::new(text).ok())
+        }
+    };
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = message);
+    status
+}
+
+unsafe fn c_str<'a>(value: *const c_char) -> Result<&'a str> {
+    if value.is_null() {
+        return Err(anyhow!("字符串参数不能为空指针"));
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|_| anyhow!("字符串参数不是有效的 UTF-8"))
+}
+
+unsafe fn parse_options(options_json: *const c_char) -> Result<CompressionOptions> {
+    if options_json.is_null() {
+        return Ok(CompressionOptions::default());
+    }
+    CompressionOptions::from_json(unsafe { c_str(options_json) }?)
+}
@@ -0,0 +1,251 @@
+//! 重写文件时保留文件系统层面的元数据：修改时间和权限，Windows 的创建时间和隐藏、
+//! 存档等属性（照片管理软件常按时间排序），macOS 的扩展属性（Finder 标签、
+//! 隔离标记、聚焦注释等）。读取或恢复失败只记日志，不影响压缩结果。
+//!
+//! 先写到同一文件夹中的临时文件并刷到磁盘，再改名覆盖目标，
+//! 中途退出或断电时目标要么是原来的内容，要么是完整的新内容。
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// 由系统维护、普通进程无法写入的扩展属性
+#[cfg(target_os = "macos")]
+const SYSTEM_XATTRS: &[&str] = &["com.apple.provenance", "com.apple.rootless"];
+
+#[derive(Clone, Debug, Default)]
+pub struct PreservedAttrs {
+    modified: Option<SystemTime>,
+    // Windows 上只有只读一项，不保留，否则下次运行无法覆盖输出文件
+    #[cfg(unix)]
+    permissions: Option<fs::Permissions>,
+    /// 所有者和组，以其他用户身份运行时才可能与新文件不同
+    #[cfg(unix)]
+    owner: Option<(u32, u32)>,
+    #[cfg(windows)]
+    created: Option<SystemTime>,
+    #[cfg(windows)]
+    attributes: Option<u32>,
+    #[cfg(target_os = "macos")]
+    xattrs: Vec<(OsString, Vec<u8>)>,
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    pub use windows_sys::Win32::Storage::FileSystem::{
+        SetFileAttributesW, FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_NORMAL,
+        FILE_ATTRIBUTE_NOT_CONTENT_INDEXED, FILE_ATTRIBUTE_SYSTEM,
+    };
+
+    /// 需要保留的属性；只读不在其中，否则下次运行无法覆盖输出文件
+    pub const PRESERVED: u32 = FILE_ATTRIBUTE_HIDDEN
+        | FILE_ATTRIBUTE_SYSTEM
+        | FILE_ATTRIBUTE_ARCHIVE
+        | FILE_ATTRIBUTE_NOT_CONTENT_INDEXED;
+
+    /// 带这些属性的文件无法被截断重建
+    pub const BLOCKS_OVERWRITE: u32 = FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM;
+
+    pub fn attributes(path: &Path) -> io::Result<u32> {
+        use std::os::windows::fs::MetadataExt;
+        Ok(std::fs::metadata(path)?.file_attributes())
+    }
+
+    pub fn set_attributes(path: &Path, attributes: u32) -> io::Result<()> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        let attributes = if attributes == 0 {
+            FILE_ATTRIBUTE_NORMAL
+        } else {
+            attributes
+        };
+        // SAFETY: wide 以 0 结尾，调用期间一直有效
+        if unsafe { SetFileAttributesW(wide.as_ptr(), attributes) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// 在改写之前读取 path 的元数据；keep_modified 为 false 时写入后的修改时间为当前时间
+pub fn capture(path: &Path, keep_modified: bool) -> PreservedAttrs {
+    let Ok(metadata) = fs::metadata(path) else {
+        return PreservedAttrs::default();
+    };
+    let mut preserved = PreservedAttrs {
+        modified: metadata.modified().ok().filter(|_| keep_modified),
+        ..PreservedAttrs::default()
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        preserved.permissions = Some(metadata.permissions());
+        preserved.owner = Some((metadata.uid(), metadata.gid()));
+    }
+    #[cfg(windows)]
+    {
+        preserved.created = metadata.created().ok();
+        preserved.attributes = windows::attributes(path)
+            .ok()
+            .map(|attributes| attributes & windows::PRESERVED);
+    }
+    #[cfg(target_os = "macos")]
+    {
+        preserved.xattrs = match xattr::list(path) {
+            Ok(names) => names
+                .filter(|name| !SYSTEM_XATTRS.iter().any(|system| name == *system))
+                .filter_map(|name| {
+                    let value = xattr::get(path, &name).ok()??;
+                    Some((name, value))
+                })
+                .collect(),
+            Err(err) => {
+                log::warn!("无法读取扩展属性 {}: {err}", path.display());
+                Vec::new()
+            }
+        };
+    }
+    preserved
+}
+
+/// 写入 path（可以是原文件、转换后的新文件或输出文件夹中的文件），再套用 preserved。
+/// path 是符号链接时写入它指向的文件，链接本身不变
+pub fn write_preserving(path: &Path, bytes: &[u8], preserved: &PreservedAttrs) -> io::Result<()> {
+    let path = if path.is_symlink() {
+        fs::canonicalize(path)?
+    } else {
+        path.to_path_buf()
+    };
+    prepare_overwrite(&path);
+    let temp = temp_path(&path);
+    let result = write_temp(&temp, bytes, preserved).and_then(|()| fs::rename(&temp, &path));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    sync_parent(&path);
+    restore(&path, preserved);
+    Ok(())
+}
+
+/// 新建 path 并写入，再套用 preserved；path 已存在时返回 AlreadyExists，不覆盖。
+/// 用于事先分配好名字的新文件，两个源文件得到同一个名字时后写的一个失败。
+/// 直接写到 path，失败时删掉写了一半的文件
+pub fn write_new(path: &Path, bytes: &[u8], preserved: &PreservedAttrs) -> io::Result<()> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    if let Err(err) = write_file(file, path, bytes, preserved) {
+        let _ = fs::remove_file(path);
+        return Err(err);
+    }
+    sync_parent(path);
+    restore(path, preserved);
+    Ok(())
+}
+
+/// 同一文件夹中以点开头的隐藏文件，扩展名不是图像，不会被扫描到；
+/// 上次中途退出留下的同名文件直接覆盖
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".compress_img.tmp");
+    path.with_file_name(name)
+}
+
+fn write_temp(temp: &Path, bytes: &[u8], preserved: &PreservedAttrs) -> io::Result<()> {
+    write_file(fs::File::create(temp)?, temp, bytes, preserved)
+}
+
+/// 写入内容和要保留的时间、权限，并等数据落盘。改名不改变这些属性
+fn write_file(
+    mut file: fs::File,
+    path: &Path,
+    bytes: &[u8],
+    preserved: &PreservedAttrs,
+) -> io::Result<()> {
+    file.write_all(bytes)?;
+
+    let mut times = fs::FileTimes::new();
+    if let Some(modified) = preserved.modified {
+        times = times.set_modified(modified);
+    }
+    #[cfg(windows)]
+    if let Some(created) = preserved.created {
+        use std::os::windows::fs::FileTimesExt;
+        times = times.set_created(created);
+    }
+    if let Err(err) = file.set_times(times) {
+        log::warn!("无法恢复文件时间 {}: {err}", path.display());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Some((uid, gid)) = preserved.owner
+            && file
+                .metadata()
+                .is_ok_and(|metadata| (metadata.uid(), metadata.gid()) != (uid, gid))
+            && let Err(err) = std::os::unix::fs::fchown(&file, Some(uid), Some(gid))
+        {
+            log::warn!("无法恢复文件所有者 {}: {err}", path.display());
+        }
+        // 在改所有者之后设置，chown 会清掉 setuid 等位
+        if let Some(permissions) = &preserved.permissions
+            && let Err(err) = file.set_permissions(permissions.clone())
+        {
+            log::warn!("无法恢复文件权限 {}: {err}", path.display());
+        }
+    }
+    file.sync_all()
+}
+
+/// 改名记录在文件夹中，文件夹也刷到磁盘才算完成；Windows 上无法这样打开文件夹
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Err(err) = fs::File::open(parent).and_then(|dir| dir.sync_all())
+    {
+        log::debug!("无法刷新文件夹 {}: {err}", parent.display());
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+fn prepare_overwrite(path: &Path) {
+    #[cfg(windows)]
+    if let Ok(attributes) = windows::attributes(path)
+        && attributes & windows::BLOCKS_OVERWRITE != 0
+        && let Err(err) = windows::set_attributes(path, attributes & !windows::BLOCKS_OVERWRITE)
+    {
+        log::warn!("无法临时去掉文件属性 {}: {err}", path.display());
+    }
+    #[cfg(not(windows))]
+    let _ = path;
+}
+
+fn restore(path: &Path, preserved: &PreservedAttrs) {
+    #[cfg(windows)]
+    if let Some(attributes) = preserved.attributes
+        && let Err(err) = windows::set_attributes(path, attributes)
+    {
+        log::warn!("无法恢复文件属性 {}: {err}", path.display());
+    }
+    // 改名覆盖后的文件是新建的，扩展属性需要重新写上
+    #[cfg(target_os = "macos")]
+    for (name, value) in &preserved.xattrs {
+        if let Err(err) = xattr::set(path, name, value) {
+            log::warn!(
+                "无法恢复扩展属性 {} {}: {err}",
+                name.to_string_lossy(),
+                path.display()
+            );
+        }
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let _ = (path, preserved);
+}
@@ -0,0 +1,70 @@
+use crate::app_data::{self, path_key};
+use crate::options::CompressionOptions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const STORE_FILE_NAME: &str = "folder_settings.json";
+
+#[derive(Default, Serialize, Deserialize)]
+struct FolderSettingsStore {
+    folders: BTreeMap<String, FolderRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FolderRecord {
+    // 保存原始 JSON，读取时走 CompressionOptions 的版本迁移
+    options: Value,
+    last_run: u64,
+}
+
+/// 返回该文件夹上一次运行时使用的设置
+pub fn last_options(folder: &Path) -> Result<Option<CompressionOptions>> {
+    let store = load_store()?;
+    match store.folders.get(&path_key(folder)) {
+        Some(record) => Ok(Some(CompressionOptions::from_value(
+            record.options.clone(),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+/// 在锁内重新读出再写回，同时结束的任务不会抹掉彼此记下的文件夹
+pub fn remember(folder: &Path, options: &CompressionOptions) -> Result<()> {
+    let record = FolderRecord {
+        options: serde_json::to_value(options)?,
+        last_run: app_data::unix_now(),
+    };
+    let path = store_path()?;
+    app_data::with_file_lock(&path, || {
+        // 文件损坏时以本次内容为准
+        let mut store = read_store(&path).unwrap_or_else(|err| {
+            log::warn!("{err:#}");
+            FolderSettingsStore::default()
+        });
+        store.folders.insert(path_key(folder), record);
+        app_data::write_atomic(&path, serde_json::to_string_pretty(&store)?.as_bytes())
+            .context("无法写入文件夹设置")
+    })
+}
+
+fn load_store() -> Result<FolderSettingsStore> {
+    let path = store_path()?;
+    app_data::with_file_lock(&path, || read_store(&path))
+}
+
+fn read_store(path: &Path) -> Result<FolderSettingsStore> {
+    if !path.exists() {
+        return Ok(FolderSettingsStore::default());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("无法读取文件夹设置: {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("无法解析文件夹设置: {}", path.display()))
+}
+
+fn store_path() -> Result<PathBuf> {
+    Ok(app_data::data_dir()?.join(STORE_FILE_NAME))
+}
@@ -0,0 +1,349 @@
+//! 用 GPU 缩小大图：两个计算着色器先沿竖直方向、再沿水平方向重采样，取样范围和权重与
+//! image 的 resize_exact 相同，中间结果以半精度保存，输出只有舍入上的差别。
+//! 只处理每通道 8 位的图像；找不到 GPU、超出显卡限制或出错时返回错误，由调用方改用 CPU。
+//! 解码仍在 CPU 上进行：JPEG 的熵解码只能顺序进行，交给 GPU 并不会更快。
+
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, RgbaImage};
+use std::sync::{mpsc, OnceLock};
+use wgpu::util::DeviceExt;
+
+/// 像素数少于这个值时上传和读回的开销超过节省的时间，直接用 CPU
+pub const MIN_PIXELS: u64 = 8_000_000;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+const SHADER: &str = r#"
+struct Params {
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    lanczos: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src_pixels: array<u32>;
+@group(0) @binding(2) var<storage, read_write> mid_pixels: array<vec2<u32>>;
+@group(0) @binding(3) var<storage, read_write> dst_pixels: array<u32>;
+
+const PI: f32 = 3.14159265358979;
+
+fn sinc(t: f32) -> f32 {
+    if t == 0.0 {
+        return 1.0;
+    }
+    let a = t * PI;
+    return sin(a) / a;
+}
+
+fn kernel(x: f32) -> f32 {
+    if params.lanczos != 0u {
+        if abs(x) < 3.0 {
+            return sinc(x) * sinc(x / 3.0);
+        }
+        return 0.0;
+    }
+    return max(1.0 - abs(x), 0.0);
+}
+
+struct Window {
+    first: u32,
+    last: u32,
+    center: f32,
+    scale: f32,
+}
+
+fn window(index: u32, src_size: u32, dst_size: u32) -> Window {
+    let ratio = f32(src_size) / f32(dst_size);
+    let scale = max(ratio, 1.0);
+    var support = 1.0;
+    if params.lanczos != 0u {
+        support = 3.0;
+    }
+    support *= scale;
+    let center = (f32(index) + 0.5) * ratio;
+    let first = u32(clamp(floor(center - support), 0.0, f32(src_size - 1u)));
+    let last = u32(clamp(ceil(center + support), f32(first + 1u), f32(src_size)));
+    return Window(first, last, center - 0.5, scale);
+}
+
+@compute @workgroup_size(16, 16)
+fn vertical(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.src_width || id.y >= params.dst_height {
+        return;
+    }
+    let w = window(id.y, params.src_height, params.dst_height);
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = w.first; i < w.last; i++) {
+        let weight = kernel((f32(i) - w.center) / w.scale);
+        sum += unpack4x8unorm(src_pixels[i * params.src_width + id.x]) * 255.0 * weight;
+        total += weight;
+    }
+    let value = sum / total;
+    mid_pixels[id.y * params.src_width + id.x] =
+        vec2<u32>(pack2x16float(value.xy), pack2x16float(value.zw));
+}
+
+@compute @workgroup_size(16, 16)
+fn horizontal(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.dst_width || id.y >= params.dst_height {
+        return;
+    }
+    let w = window(id.x, params.src_width, params.dst_width);
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = w.first; i < w.last; i++) {
+        let weight = kernel((f32(i) - w.center) / w.scale);
+        let packed = mid_pixels[id.y * params.src_width + i];
+        sum += vec4<f32>(unpack2x16float(packed.x), unpack2x16float(packed.y)) * weight;
+        total += weight;
+    }
+    dst_pixels[id.y * params.dst_width + id.x] = pack4x8unorm(sum / total / 255.0);
+}
+"#;
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    vertical: wgpu::ComputePipeline,
+    horizontal: wgpu::ComputePipeline,
+}
+
+/// 第一次使用时初始化，之后所有线程共用；没有可用的 GPU 时为 None
+fn gpu() -> Option<&'static Gpu> {
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    GPU.get_or_init(|| match pollster::block_on(Gpu::new()) {
+        Ok(gpu) => {
+            log::info!("缩小大图使用 GPU: {}", gpu.device.adapter_info().name);
+            Some(gpu)
+        }
+        Err(err) => {
+            log::info!("没有可用的 GPU，缩小图像使用 CPU: {err:#}");
+            None
+        }
+    })
+    .as_ref()
+}
+
+/// 按 filter 把 image 缩放到 width x height，只支持 Lanczos3 和 Triangle
+pub fn resize(image: &RgbaImage, width: u32, height: u32, filter: FilterType) -> Result<RgbaImage> {
+    if !matches!(filter, FilterType::Lanczos3 | FilterType::Triangle) {
+        return Err(anyhow!("GPU 只支持 Lanczos3 和 Triangle 缩放"));
+    }
+    let gpu = gpu().ok_or_else(|| anyhow!("没有可用的 GPU"))?;
+    pollster::block_on(gpu.resize(image, width, height, filter))
+}
+
+impl Gpu {
+    async fn new() -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await?;
+        // 大图的缓冲区远超默认限制，按显卡实际支持的申请
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("compress_img"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await?;
+        device.on_uncaptured_error(std::sync::Arc::new(|err| {
+            log::warn!("GPU 出错: {err}");
+        }));
+
+        let validation = device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("resize"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let vertical = pipeline("vertical");
+        let horizontal = pipeline("horizontal");
+        if let Some(err) = validation.pop().await {
+            return Err(anyhow!("无法编译 GPU 着色器: {err}"));
+        }
+        Ok(Self {
+            device,
+            queue,
+            vertical,
+            horizontal,
+        })
+    }
+
+    async fn resize(
+        &self,
+        image: &RgbaImage,
+        width: u32,
+        height: u32,
+        filter: FilterType,
+    ) -> Result<RgbaImage> {
+        let (src_width, src_height) = image.dimensions();
+        let middle_size = src_width as u64 * height as u64 * 8;
+        let output_size = width as u64 * height as u64 * 4;
+        let limits = self.device.limits();
+        let largest = middle_size.max(image.as_raw().len() as u64);
+        if largest > limits.max_storage_buffer_binding_size || largest > limits.max_buffer_size {
+            return Err(anyhow!("图像超出 GPU 缓冲区的大小限制"));
+        }
+
+        let out_of_memory = self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        let validation = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let params: Vec<u8> = [
+            src_width,
+            src_height,
+            width,
+            height,
+            (filter == FilterType::Lanczos3) as u32,
+            0,
+            0,
+            0,
+        ]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: &params,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let source = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("source"),
+                contents: image.as_raw(),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let storage = |label, size| {
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        };
+        let middle = storage("middle", middle_size);
+        let target = storage("target", output_size);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = |pipeline: &wgpu::ComputePipeline, buffers: [(u32, &wgpu::Buffer); 3]| {
+            let entries: Vec<_> = buffers
+                .iter()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: *binding,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+        let vertical = bind_group(&self.vertical, [(0, &params), (1, &source), (2, &middle)]);
+        let horizontal = bind_group(&self.horizontal, [(0, &params), (2, &middle), (3, &target)]);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.vertical);
+            pass.set_bind_group(0, &vertical, &[]);
+            pass.dispatch_workgroups(
+                src_width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+            pass.set_pipeline(&self.horizontal);
+            pass.set_bind_group(0, &horizontal, &[]);
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&target, 0, &readback, 0, output_size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|err| anyhow!("等待 GPU 完成时出错: {err:?}"))?;
+        if let Some(err) = validation.pop().await {
+            return Err(anyhow!("GPU 缩放出错: {err}"));
+        }
+        if let Some(err) = out_of_memory.pop().await {
+            return Err(anyhow!("GPU 内存不足: {err}"));
+        }
+        receiver
+            .recv()
+            .context("GPU 没有返回结果")?
+            .map_err(|err| anyhow!("无法读取 GPU 结果: {err}"))?;
+
+        let pixels = slice.get_mapped_range().to_vec();
+        readback.unmap();
+        RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow!("GPU 返回的数据大小不对"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops;
+    use image::Rgba;
+
+    #[test]
+    fn matches_cpu_resize_exact() {
+        if gpu().is_none() {
+            eprintln!("没有可用的 GPU，跳过");
+            return;
+        }
+        // 渐变加上锐利的棋盘格边缘，覆盖权重为负的取样
+        let source = RgbaImage::from_fn(301, 203, |x, y| {
+            let checker = if (x / 7 + y / 5) % 2 == 0 { 255 } else { 0 };
+            Rgba([(x * 255 / 300) as u8, (y * 255 / 202) as u8, checker, 255])
+        });
+        for filter in [FilterType::Lanczos3, FilterType::Triangle] {
+            let on_gpu = resize(&source, 97, 61, filter).unwrap();
+            let on_cpu = imageops::resize(&source, 97, 61, filter);
+            let max_diff = on_gpu
+                .as_raw()
+                .iter()
+                .zip(on_cpu.as_raw())
+                .map(|(gpu, cpu)| gpu.abs_diff(*cpu))
+                .max()
+                .unwrap();
+            assert!(max_diff <= 1, "{filter:?} 与 CPU 结果最多差 {max_diff}");
+        }
+    }
+}
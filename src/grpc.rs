@@ -0,0 +1,186 @@
+//! gRPC 服务，接口定义见 proto/compress_img.proto。
+//! 服务设置了令牌时每个请求的元数据都要带上 `authorization: Bearer <令牌>`。
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::error::render_error;
+use crate::jobs::{JobEvent, JobManager};
+use crate::options::CompressionOptions;
+
+pub mod proto {
+    tonic::include_proto!("compress_img");
+}
+
+use proto::compress_service_server::{CompressService, CompressServiceServer};
+use proto::progress_event::Event;
+
+pub struct GrpcService {
+    jobs: Arc<JobManager>,
+}
+
+impl GrpcService {
+    pub fn new(jobs: Arc<JobManager>) -> Self {
+        Self { jobs }
+    }
+}
+
+type ProgressStream = Pin<Box<dyn Stream<Item = Result<proto::ProgressEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl CompressService for GrpcService {
+    async fn submit_job(
+        &self,
+        request: Request<proto::SubmitJobRequest>,
+    ) -> Result<Response<proto::SubmitJobResponse>, Status> {
+        let request = request.into_inner();
+        if request.folder.is_empty() {
+            return Err(Status::invalid_argument("folder 不能为空"));
+        }
+        let options = if request.options_json.trim().is_empty() {
+            CompressionOptions::default()
+        } else {
+            CompressionOptions::from_json(&request.options_json)
+                .map_err(|err| Status::invalid_argument(render_error(&err, self.jobs.language())))?
+        };
+        let job = self
+            .jobs
+            .submit_remote(PathBuf::from(request.folder), options)
+            .map_err(|err| Status::permission_denied(render_error(&err, self.jobs.language())))?;
+        Ok(Response::new(proto::SubmitJobResponse { job_id: job.id }))
+    }
+
+    type StreamProgressStream = ProgressStream;
+
+    async fn stream_progress(
+        &self,
+        request: Request<proto::StreamProgressRequest>,
+    ) -> Result<Response<Self::StreamProgressStream>, Status> {
+        let job_id = request.into_inner().job_id;
+        let job = self
+            .jobs
+            .get(job_id)
+            .ok_or_else(|| Status::not_found(format!("任务不存在: {job_id}")))?;
+        let stream = job.events().map(move |event| Ok(to_proto(job_id, event)));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<proto::CancelJobRequest>,
+    ) -> Result<Response<proto::CancelJobResponse>, Status> {
+        let job_id = request.into_inner().job_id;
+        let job = self
+            .jobs
+            .get(job_id)
+            .ok_or_else(|| Status::not_found(format!("任务不存在: {job_id}")))?;
+        Ok(Response::new(proto::CancelJobResponse {
+            cancelled: job.cancel(),
+        }))
+    }
+}
+
+pub async fn serve(addr: SocketAddr, jobs: Arc<JobManager>) -> anyhow::Result<()> {
+    let checked = jobs.clone();
+    tonic::transport::Server::builder()
+        .add_service(CompressServiceServer::with_interceptor(
+            GrpcService::new(jobs),
+            move |request| check_token(&checked, request),
+        ))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+fn check_token(jobs: &JobManager, request: Request<()>) -> Result<Request<()>, Status> {
+    let provided = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if jobs.access().token_matches(provided) {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("缺少令牌或令牌错误"))
+    }
+}
+
+fn to_proto(job_id: u64, event: JobEvent) -> proto::ProgressEvent {
+    let event = match event {
+        JobEvent::Queued => Event::Queued(proto::Queued {}),
+        JobEvent::Scanning {
+            dirs_visited,
+            files_found,
+            total_bytes,
+        } => Event::Scanning(proto::Scanning {
+            dirs_visited: dirs_visited as u32,
+            files_found: files_found as u32,
+            total_bytes,
+        }),
+        JobEvent::Scanned {
+            total,
+            total_bytes,
+            estimated_secs,
+            unchanged,
+            not_included,
+            too_recent,
+            not_targeted,
+            ignored,
+            already_converted,
+            cloud_only,
+            errors,
+        } => Event::Scanned(proto::Scanned {
+            total: total as u32,
+            errors,
+            unchanged: unchanged as u32,
+            not_included: not_included as u32,
+            too_recent: too_recent as u32,
+            not_targeted: not_targeted as u32,
+            ignored: ignored as u32,
+            already_converted: already_converted as u32,
+            cloud_only: cloud_only as u32,
+            total_bytes,
+            estimated_secs,
+        }),
+        JobEvent::File {
+            processed,
+            total,
+            path,
+            original_size,
+            new_size,
+            note,
+            error,
+        } => Event::File(proto::FileFinished {
+            processed: processed as u32,
+            total: total as u32,
+            path,
+            ok: error.is_none(),
+            original_size: original_size.unwrap_or_default(),
+            new_size: new_size.unwrap_or_default(),
+            error: error.unwrap_or_default(),
+            note: note.unwrap_or_default(),
+        }),
+        JobEvent::Finished {
+            succeeded,
+            failed,
+            total_saved,
+            cancelled,
+            error,
+        } => Event::Finished(proto::JobFinished {
+            succeeded: succeeded as u32,
+            failed: failed as u32,
+            total_saved,
+            cancelled,
+            error: error.unwrap_or_default(),
+        }),
+    };
+    proto::ProgressEvent {
+        job_id,
+        event: Some(event),
+    }
+}
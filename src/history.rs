@@ -0,0 +1,130 @@
+//! 运行历史：每次批处理结束后追加一条记录（JSON Lines），
+//! 用于历史统计和预估耗时。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::app_data::{self, path_key};
+
+const HISTORY_FILE_NAME: &str = "run_history.jsonl";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunRecord {
+    pub folder: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub cancelled: bool,
+    /// 本次失败的文件
+    pub failed_paths: Vec<PathBuf>,
+    /// 因刚被修改而推迟的文件
+    pub deferred_paths: Vec<PathBuf>,
+}
+
+impl RunRecord {
+    pub fn new(folder: &Path, started_at: u64) -> Self {
+        Self {
+            folder: path_key(folder),
+            started_at,
+            ..Self::default()
+        }
+    }
+}
+
+pub fn append(record: &RunRecord) -> Result<()> {
+    let path = history_path()?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("无法写入运行历史: {}", path.display()))
+}
+
+/// 按时间顺序返回全部记录，损坏的行会被跳过
+pub fn load_all() -> Result<Vec<RunRecord>> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("无法读取运行历史: {}", path.display()))?;
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn history_path() -> Result<PathBuf> {
+    Ok(app_data::data_dir()?.join(HISTORY_FILE_NAME))
+}
+
+/// 根据最近的运行记录估算处理速度（原始字节/秒），没有可用记录时返回 None
+pub fn estimated_throughput() -> Option<f64> {
+    let records = load_all().ok()?;
+    let (bytes, secs) = records
+        .iter()
+        .rev()
+        .filter(|record| record.bytes_before > 0 && record.finished_at > record.started_at)
+        .take(20)
+        .fold((0u64, 0u64), |(bytes, secs), record| {
+            (
+                bytes + record.bytes_before,
+                secs + (record.finished_at - record.started_at),
+            )
+        });
+    (secs > 0).then(|| bytes as f64 / secs as f64)
+}
+
+/// 按天（UTC）汇总的运行统计
+#[derive(Clone, Debug, Default)]
+pub struct DailyStats {
+    /// 距 1970-01-01 的天数
+    pub day: u64,
+    pub runs: usize,
+    pub files: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl DailyStats {
+    pub fn saved_bytes(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+
+    pub fn date_label(&self) -> String {
+        app_data::format_date(self.day * 86_400)
+    }
+}
+
+/// 按日期升序返回每天的汇总，没有运行的日期不出现
+pub fn daily_stats(records: &[RunRecord]) -> Vec<DailyStats> {
+    let mut days: Vec<DailyStats> = Vec::new();
+    let mut sorted: Vec<&RunRecord> = records.iter().collect();
+    sorted.sort_by_key(|record| record.finished_at);
+    for record in sorted {
+        let day = record.finished_at / 86_400;
+        if days.last().is_none_or(|last| last.day != day) {
+            days.push(DailyStats {
+                day,
+                ..DailyStats::default()
+            });
+        }
+        let stats = days.last_mut().expect("刚刚插入");
+        stats.runs += 1;
+        stats.files += record.succeeded + record.failed;
+        stats.bytes_before += record.bytes_before;
+        stats.bytes_after += record.bytes_after;
+    }
+    days
+}
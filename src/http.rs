@@ -0,0 +1,129 @@
+//! HTTP 接口，进度通过 Server-Sent Events 推送:
+//!
+//! - `POST /jobs` 提交任务，请求体 `{"folder": "...", "options": {...}}`，返回 `{"job_id": 1}`
+//! - `GET /jobs/{id}/events` 订阅进度事件流（SSE，事件名即 JobEvent 的 type）
+//! - `POST /jobs/{id}/cancel` 取消任务
+//!
+//! 服务设置了令牌时每个请求都要带上 `Authorization: Bearer <令牌>`
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::extract::{Path, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::error::render_error;
+use crate::jobs::{JobEvent, JobManager};
+use crate::options::CompressionOptions;
+
+#[derive(Deserialize)]
+struct SubmitRequest {
+    folder: String,
+    #[serde(default)]
+    options: Option<Value>,
+}
+
+type ApiError = (StatusCode, Json<Value>);
+
+pub fn router(jobs: Arc<JobManager>) -> Router {
+    Router::new()
+        .route("/jobs", post(submit_job))
+        .route("/jobs/{id}/events", get(job_events))
+        .route("/jobs/{id}/cancel", post(cancel_job))
+        .route_layer(middleware::from_fn_with_state(jobs.clone(), require_token))
+        .with_state(jobs)
+}
+
+pub async fn serve(addr: SocketAddr, jobs: Arc<JobManager>) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(jobs)).await?;
+    Ok(())
+}
+
+async fn require_token(
+    State(jobs): State<Arc<JobManager>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if jobs.access().token_matches(provided) {
+        next.run(request).await
+    } else {
+        api_error(StatusCode::UNAUTHORIZED, "缺少令牌或令牌错误").into_response()
+    }
+}
+
+async fn submit_job(
+    State(jobs): State<Arc<JobManager>>,
+    Json(request): Json<SubmitRequest>,
+) -> Result<Json<Value>, ApiError> {
+    if request.folder.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "folder 不能为空"));
+    }
+    let options = match request.options {
+        Some(value) => CompressionOptions::from_value(value).map_err(|err| {
+            api_error(
+                StatusCode::BAD_REQUEST,
+                &render_error(&err, jobs.language()),
+            )
+        })?,
+        None => CompressionOptions::default(),
+    };
+    let job = jobs
+        .submit_remote(PathBuf::from(request.folder), options)
+        .map_err(|err| api_error(StatusCode::FORBIDDEN, &render_error(&err, jobs.language())))?;
+    Ok(Json(json!({ "job_id": job.id })))
+}
+
+async fn job_events(
+    State(jobs): State<Arc<JobManager>>,
+    Path(id): Path<u64>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let job = jobs.get(id).ok_or_else(|| job_not_found(id))?;
+    let stream = job.events().map(|event| Ok(to_sse(&event)));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn cancel_job(
+    State(jobs): State<Arc<JobManager>>,
+    Path(id): Path<u64>,
+) -> Result<Json<Value>, ApiError> {
+    let job = jobs.get(id).ok_or_else(|| job_not_found(id))?;
+    Ok(Json(json!({ "cancelled": job.cancel() })))
+}
+
+fn to_sse(event: &JobEvent) -> Event {
+    let name = match event {
+        JobEvent::Queued => "queued",
+        JobEvent::Scanning { .. } => "scanning",
+        JobEvent::Scanned { .. } => "scanned",
+        JobEvent::File { .. } => "file",
+        JobEvent::Finished { .. } => "finished",
+    };
+    Event::default()
+        .event(name)
+        .data(serde_json::to_string(event).unwrap_or_default())
+}
+
+fn job_not_found(id: u64) -> ApiError {
+    api_error(StatusCode::NOT_FOUND, &format!("任务不存在: {id}"))
+}
+
+fn api_error(status: StatusCode, message: &str) -> ApiError {
+    (status, Json(json!({ "error": message })))
+}
@@ -0,0 +1,104 @@
+//! 完整性检查：完整解码每张图像并检查文件结尾，找出损坏或被截断的文件，
+//! 不修改任何文件。适合在压缩之前（或代替压缩）校验一批归档。
+
+use anyhow::{anyhow, Result};
+use image::ImageFormat;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::options::CompressionOptions;
+use crate::{codec, scan};
+
+pub struct IntegrityIssue {
+    pub path: PathBuf,
+    pub problem: String,
+}
+
+pub struct IntegrityReport {
+    pub scanned: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+/// on_progress 收到 (已检查的文件数, 文件总数)
+pub fn check_folder(
+    folder: &Path,
+    options: &CompressionOptions,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<IntegrityReport> {
+    let files = scan::scan_folder(folder, options).files;
+    if files.is_empty() {
+        return Err(anyhow!("文件夹中没有可检查的图像"));
+    }
+
+    let total = files.len();
+    let mut issues = Vec::new();
+    for (index, path) in files.into_iter().enumerate() {
+        if let Some(problem) = check_file(&path) {
+            issues.push(IntegrityIssue { path, problem });
+        }
+        on_progress(index + 1, total);
+    }
+    Ok(IntegrityReport {
+        scanned: total,
+        issues,
+    })
+}
+
+/// 文件完好时返回 None，否则返回问题描述
+pub fn check_file(path: &Path) -> Option<String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return Some(format!("无法读取: {err}")),
+    };
+    let format = match codec::decode_buffer(&bytes) {
+        Ok((format, _)) => format,
+        Err(err) => return Some(err.to_string()),
+    };
+    // 有的解码器遇到截断的数据会用灰色填满剩余部分而不报错，所以还要检查结尾
+    if is_truncated(format, &bytes) {
+        return Some("文件不完整，可能在复制或下载时被截断".to_string());
+    }
+    match ImageFormat::from_path(path) {
+        Ok(expected) if expected != format => Some(format!(
+            "扩展名与实际格式 {} 不符",
+            format.extensions_str()[0].to_uppercase()
+        )),
+        _ => None,
+    }
+}
+
+fn is_truncated(format: ImageFormat, bytes: &[u8]) -> bool {
+    match format {
+        // 以 EOI 标记结尾，有的设备会在后面补零
+        ImageFormat::Jpeg => {
+            let end = bytes
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |i| i + 1);
+            !bytes[..end].ends_with(&[0xFF, 0xD9])
+        }
+        // 最后一个块必须是 IEND
+        ImageFormat::Png => !bytes[bytes.len().saturating_sub(64)..]
+            .windows(4)
+            .any(|window| window == b"IEND"),
+        // RIFF 头中记录的长度不能超过实际文件
+        ImageFormat::WebP => bytes.get(4..8).is_none_or(|size| {
+            u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize + 8 > bytes.len()
+        }),
+        _ => false,
+    }
+}
+
+impl IntegrityReport {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "完整性检查: 检查 {} 张图像，发现 {} 个有问题的文件\n",
+            self.scanned,
+            self.issues.len()
+        );
+        for issue in &self.issues {
+            text.push_str(&format!("✖ {} | {}\n", issue.path.display(), issue.problem));
+        }
+        text
+    }
+}
@@ -0,0 +1,467 @@
+//! 服务模式下的后台任务管理，gRPC 与 HTTP 接口共用。
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::access::AccessPolicy;
+use crate::batch::{self, BatchEvent, FileOutcome};
+use crate::control::RunControl;
+use crate::crash;
+use crate::error::{self, Language};
+use crate::lock::LockPolicy;
+use crate::notify::{JobReport, Notifier};
+use crate::options::CompressionOptions;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+const STREAM_BUFFER: usize = 64;
+/// 每个任务最多保留的事件数，超出时丢弃最早的进度事件
+const HISTORY_LIMIT: usize = 1024;
+/// 已结束的任务保留这么久，供客户端查询结果
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+/// 最多保留的已结束任务数，超出时先移除最早结束的
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEvent {
+    Queued,
+    Scanning {
+        dirs_visited: usize,
+        files_found: usize,
+        total_bytes: u64,
+    },
+    Scanned {
+        total: usize,
+        total_bytes: u64,
+        estimated_secs: Option<u64>,
+        unchanged: usize,
+        not_included: usize,
+        too_recent: usize,
+        not_targeted: usize,
+        ignored: usize,
+        already_converted: usize,
+        cloud_only: usize,
+        errors: Vec<String>,
+    },
+    File {
+        processed: usize,
+        total: usize,
+        path: String,
+        original_size: Option<u64>,
+        new_size: Option<u64>,
+        /// 文件未改动时的原因，如源 JPEG 质量已低于目标
+        note: Option<String>,
+        error: Option<String>,
+    },
+    Finished {
+        succeeded: usize,
+        failed: usize,
+        total_saved: i64,
+        cancelled: bool,
+        error: Option<String>,
+    },
+}
+
+impl JobEvent {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobEvent::Finished { .. })
+    }
+}
+
+pub struct Job {
+    pub id: u64,
+    pub folder: PathBuf,
+    control: RunControl,
+    // 已发生的事件，供晚到的订阅者补发；进度事件只保留最近的一部分
+    history: Mutex<Vec<JobEvent>>,
+    sender: broadcast::Sender<JobEvent>,
+    finished_at: OnceLock<Instant>,
+}
+
+/// 订阅时刻之前的事件快照，加上之后的实时事件
+pub struct JobSubscription {
+    pub backlog: Vec<JobEvent>,
+    pub receiver: broadcast::Receiver<JobEvent>,
+}
+
+impl Job {
+    pub fn subscribe(&self) -> JobSubscription {
+        // 持锁订阅，保证快照与实时事件之间不漏也不重
+        let history = self.history.lock().unwrap();
+        JobSubscription {
+            backlog: history.clone(),
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    /// 依次产出任务的全部事件（含订阅前已发生的），在 Finished 之后结束。
+    /// 需要在 tokio 运行时中调用。
+    pub fn events(self: &Arc<Self>) -> ReceiverStream<JobEvent> {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        let JobSubscription {
+            backlog,
+            receiver: mut live,
+        } = self.subscribe();
+        tokio::spawn(async move {
+            for event in backlog {
+                let finished = event.is_finished();
+                if sender.send(event).await.is_err() || finished {
+                    return;
+                }
+            }
+            loop {
+                match live.recv().await {
+                    Ok(event) => {
+                        let finished = event.is_finished();
+                        if sender.send(event).await.is_err() || finished {
+                            return;
+                        }
+                    }
+                    // 订阅者太慢时跳过丢失的中间进度，不影响最终结果
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+        ReceiverStream::new(receiver)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.history
+            .lock()
+            .unwrap()
+            .last()
+            .is_some_and(JobEvent::is_finished)
+    }
+
+    pub fn cancel(&self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.control.cancel();
+        true
+    }
+
+    fn publish(&self, event: JobEvent) {
+        let mut history = self.history.lock().unwrap();
+        if event.is_finished() {
+            let _ = self.finished_at.set(Instant::now());
+        }
+        push_history(&mut history, event.clone());
+        // 没有订阅者时发送会失败，忽略即可
+        let _ = self.sender.send(event);
+    }
+}
+
+#[derive(Default)]
+pub struct JobManager {
+    jobs: Mutex<HashMap<u64, Arc<Job>>>,
+    next_id: AtomicU64,
+    notifier: Arc<Notifier>,
+    access: AccessPolicy,
+    /// 事件和报告中原因、错误的语言
+    language: Language,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 每个任务结束（含失败、取消）后按 notifier 的配置发送通知
+    pub fn with_notifier(notifier: Notifier) -> Self {
+        Self {
+            notifier: Arc::new(notifier),
+            ..Self::default()
+        }
+    }
+
+    /// 接口提交的任务按 access 检查路径和令牌
+    pub fn with_access(self, access: AccessPolicy) -> Self {
+        Self { access, ..self }
+    }
+
+    pub fn access(&self) -> &AccessPolicy {
+        &self.access
+    }
+
+    pub fn with_language(self, language: Language) -> Self {
+        Self { language, ..self }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// 接口提交的任务：先检查路径是否允许，解码时限制图像尺寸
+    pub fn submit_remote(
+        &self,
+        folder: PathBuf,
+        mut options: CompressionOptions,
+    ) -> anyhow::Result<Arc<Job>> {
+        self.access.check_job(&folder, &options)?;
+        options.untrusted_input = true;
+        Ok(self.submit(folder, options))
+    }
+
+    /// 在后台线程中启动任务，立即返回任务编号
+    pub fn submit(&self, folder: PathBuf, options: CompressionOptions) -> Arc<Job> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let job = Arc::new(Job {
+            id,
+            folder,
+            control: RunControl::default(),
+            history: Mutex::new(Vec::new()),
+            sender,
+            finished_at: OnceLock::new(),
+        });
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            prune_finished(&mut jobs, Instant::now());
+            jobs.insert(id, job.clone());
+        }
+
+        let worker = job.clone();
+        let notifier = self.notifier.clone();
+        let language = self.language;
+        thread::spawn(move || run_job(&worker, &options, &notifier, language));
+        job
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        prune_finished(&mut jobs, Instant::now());
+        jobs.get(&id).cloned()
+    }
+}
+
+/// 历史已满时丢弃最早的扫描或单个文件进度，保留 Queued、Scanned 和 Finished
+fn push_history(history: &mut Vec<JobEvent>, event: JobEvent) {
+    if history.len() >= HISTORY_LIMIT
+        && let Some(index) = history
+            .iter()
+            .position(|event| matches!(event, JobEvent::Scanning { .. } | JobEvent::File { .. }))
+    {
+        history.remove(index);
+    }
+    history.push(event);
+}
+
+/// 移除结束超过 FINISHED_JOB_TTL 的任务，并把已结束的任务数限制在 MAX_FINISHED_JOBS 以内
+fn prune_finished(jobs: &mut HashMap<u64, Arc<Job>>, now: Instant) {
+    jobs.retain(|_, job| {
+        job.finished_at
+            .get()
+            .is_none_or(|finished| now.duration_since(*finished) < FINISHED_JOB_TTL)
+    });
+    let mut finished: Vec<(Instant, u64)> = jobs
+        .values()
+        .filter_map(|job| job.finished_at.get().map(|at| (*at, job.id)))
+        .collect();
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort_unstable();
+        for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+fn run_job(job: &Job, options: &CompressionOptions, notifier: &Notifier, language: Language) {
+    crash::set_context(&job.folder, options);
+    let mut queued = false;
+    let result = batch::run_batch(
+        &job.folder,
+        options,
+        LockPolicy::Wait,
+        &job.control,
+        // 提交任务本身就是确认
+        |_| true,
+        |event| {
+            job.publish(match event {
+                BatchEvent::WaitingForLock if queued => return,
+                BatchEvent::Skipped { .. } => return,
+                BatchEvent::WaitingForLock => {
+                    queued = true;
+                    JobEvent::Queued
+                }
+                BatchEvent::Scanning(progress) => JobEvent::Scanning {
+                    dirs_visited: progress.dirs_visited,
+                    files_found: progress.files_found,
+                    total_bytes: progress.total_bytes,
+                },
+                BatchEvent::Scanned {
+                    total,
+                    total_bytes,
+                    estimated_secs,
+                    unchanged,
+                    not_included,
+                    too_recent,
+                    not_targeted,
+                    ignored,
+                    already_converted,
+                    cloud_only,
+                    errors,
+                } => JobEvent::Scanned {
+                    total,
+                    total_bytes,
+                    estimated_secs,
+                    unchanged,
+                    not_included,
+                    too_recent,
+                    not_targeted,
+                    ignored,
+                    already_converted,
+                    cloud_only,
+                    errors: errors
+                        .iter()
+                        .map(|err| error::render_error(err, language))
+                        .collect(),
+                },
+                BatchEvent::FileFinished {
+                    processed,
+                    total,
+                    path,
+                    outcome,
+                } => {
+                    let (original_size, new_size, note, error) = match outcome {
+                        FileOutcome::Compressed(stats) => (
+                            Some(stats.original_size),
+                            Some(stats.new_size),
+                            stats.kept_text(language),
+                            None,
+                        ),
+                        FileOutcome::Failed(failure) => {
+                            (None, None, None, Some(failure.render(language)))
+                        }
+                    };
+                    JobEvent::File {
+                        processed,
+                        total,
+                        path: path.display().to_string(),
+                        original_size,
+                        new_size,
+                        note,
+                        error,
+                    }
+                }
+            });
+        },
+    );
+
+    let mut report = JobReport::new(job.id, &job.folder);
+    match result {
+        Ok(summary) => {
+            report.succeeded = summary.succeeded;
+            report.failed = summary.failed;
+            report.total_saved = summary.total_saved;
+            report.cancelled = summary.cancelled;
+            report.error = summary.aborted.map(|reason| reason.render(language));
+        }
+        Err(err) => {
+            log::error!("任务 {} 失败 {}: {err:#}", job.id, job.folder.display());
+            report.error = Some(error::render_error(&err, language));
+        }
+    }
+    job.publish(JobEvent::Finished {
+        succeeded: report.succeeded,
+        failed: report.failed,
+        total_saved: report.total_saved,
+        cancelled: report.cancelled,
+        error: report.error.clone(),
+    });
+    // 在工作线程中同步发送，不影响订阅者收到 Finished
+    if !notifier.is_empty() {
+        notifier.notify(&report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_event(processed: usize) -> JobEvent {
+        JobEvent::File {
+            processed,
+            total: HISTORY_LIMIT * 2,
+            path: format!("{processed}.jpg"),
+            original_size: None,
+            new_size: None,
+            note: None,
+            error: None,
+        }
+    }
+
+    fn job(id: u64, finished_at: Option<Instant>) -> Arc<Job> {
+        let job = Job {
+            id,
+            folder: PathBuf::from("/photos"),
+            control: RunControl::default(),
+            history: Mutex::new(Vec::new()),
+            sender: broadcast::channel(1).0,
+            finished_at: OnceLock::new(),
+        };
+        if let Some(at) = finished_at {
+            job.finished_at.set(at).unwrap();
+        }
+        Arc::new(job)
+    }
+
+    #[test]
+    fn history_drops_oldest_progress_but_keeps_milestones() {
+        let mut history = vec![JobEvent::Queued];
+        for processed in 1..=HISTORY_LIMIT * 2 {
+            push_history(&mut history, file_event(processed));
+        }
+        push_history(
+            &mut history,
+            JobEvent::Finished {
+                succeeded: HISTORY_LIMIT * 2,
+                failed: 0,
+                total_saved: 0,
+                cancelled: false,
+                error: None,
+            },
+        );
+
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert!(matches!(history[0], JobEvent::Queued));
+        assert!(matches!(
+            history[history.len() - 2],
+            JobEvent::File { processed, .. } if processed == HISTORY_LIMIT * 2
+        ));
+        assert!(history.last().unwrap().is_finished());
+    }
+
+    #[test]
+    fn prune_removes_expired_and_excess_finished_jobs() {
+        let now = Instant::now() + FINISHED_JOB_TTL * 2;
+        let mut jobs = HashMap::new();
+        jobs.insert(1, job(1, None));
+        jobs.insert(
+            2,
+            job(2, Some(now - FINISHED_JOB_TTL - Duration::from_secs(1))),
+        );
+        for id in 3..3 + MAX_FINISHED_JOBS as u64 + 5 {
+            jobs.insert(id, job(id, Some(now - Duration::from_secs(1000 - id))));
+        }
+
+        prune_finished(&mut jobs, now);
+
+        assert!(jobs.contains_key(&1), "运行中的任务不应被移除");
+        assert!(!jobs.contains_key(&2), "过期的任务应被移除");
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        for id in 3..8 {
+            assert!(!jobs.contains_key(&id), "最早结束的任务应先被移除");
+        }
+    }
+}
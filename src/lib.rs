@@ -0,0 +1,660 @@
+//! 压缩核心：图形界面、命令行和其他语言绑定共用的编解码与配置逻辑。
+//! 文件系统相关的部分在 wasm32 上不可用，浏览器中只能使用 [`compress_buffer`]。
+
+#[cfg(feature = "server")]
+pub mod access;
+pub mod animation;
+pub mod classify;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cloud;
+pub mod codec;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod convert;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod crash;
+pub mod error;
+pub mod metadata;
+pub mod options;
+pub mod png_opt;
+pub mod preset;
+pub mod target_size;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod app_data;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod app_settings;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod backup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compare;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dedup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod estimate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod failures;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_attrs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod folder_settings;
+#[cfg(all(feature = "gpu", not(target_arch = "wasm32")))]
+pub mod gpu;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod history;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod integrity;
+#[cfg(feature = "server")]
+pub mod jobs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod lock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod manifest;
+#[cfg(feature = "server")]
+pub mod notify;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prefetch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod processed;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profile;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod retry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod review;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod scan;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod taskbar;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tuning;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod update;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
+
+use anyhow::Result;
+use error::{CompressError, Language};
+use options::CompressionOptions;
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    image::ImageFormat, profile::ContentProfile, std::collections::HashSet, std::fs,
+    std::path::Path, std::time::Instant,
+};
+
+/// 从内存中的图像数据压缩，开启转换时输出为转换的格式，否则与输入相同
+pub fn compress_buffer(input: &[u8], options: &CompressionOptions) -> Result<Vec<u8>> {
+    let (format, mut image) = codec::decode_for(input, options)?;
+
+    if !options.is_enabled(format) {
+        return Err(CompressError::FormatDisabled(format).into());
+    }
+    if animation::is_animation(input, format) {
+        let options = options.for_animation();
+        let options = options.for_source(format);
+        let target = options.output_format(format);
+        return animation::recompress(input, format, target, &options).map(|encoded| encoded.bytes);
+    }
+
+    let options = options.for_source(format);
+    let target = options.output_format(format);
+    let max_bytes = options.target_size.max_bytes();
+    let oriented = metadata::auto_orient(input, &mut image, &options.metadata);
+    let resized = codec::limit_dimensions(&image, &options.resize);
+    if !oriented
+        && resized.is_none()
+        && max_bytes.is_some_and(|max_bytes| target == format && input.len() as u64 <= max_bytes)
+    {
+        return Ok(input.to_vec());
+    }
+    if let Some(resized) = resized {
+        image = resized;
+    }
+    let metadata = metadata::prepare(input, target, &options.metadata);
+    match max_bytes {
+        Some(max_bytes) => {
+            target_size::encode_to_size(&image, target, &options, &metadata, max_bytes)
+                .map(|sized| sized.bytes)
+        }
+        None => codec::encode_with_metadata(&image, target, &options, &metadata),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compress_image(path: &Path, options: &CompressionOptions) -> Result<CompressionStats> {
+    compress_image_to(path, None, options)
+}
+
+/// destination 为 None 时写回原处（转换格式时写到同目录下的新文件）；
+/// 否则写到 destination，转换格式时替换其扩展名，原文件保持不动
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compress_image_to(
+    path: &Path,
+    destination: Option<&Path>,
+    options: &CompressionOptions,
+) -> Result<CompressionStats> {
+    let source = read_source(path, options)?;
+    compress_source_to(path, source, destination, options)
+}
+
+/// 已读入内存的源文件内容
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SourceBytes {
+    pub bytes: SourceData,
+    pub read_time: Duration,
+    /// 读取时重试的次数
+    pub retries: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub enum SourceData {
+    Owned(Vec<u8>),
+    /// 映射期间不能改写或删除原文件，写回之前必须先释放
+    Mapped(memmap2::Mmap),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::ops::Deref for SourceData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SourceData::Owned(bytes) => bytes,
+            SourceData::Mapped(map) => map,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_source(path: &Path, options: &CompressionOptions) -> Result<SourceBytes> {
+    let started = Instant::now();
+    let mut retries = 0;
+    let bytes = retry::with_retry(&options.retry, &mut retries, || {
+        let file = fs::File::open(path)?;
+        if file.metadata()?.len() < codec::MMAP_THRESHOLD {
+            return fs::read(path).map(SourceData::Owned);
+        }
+        // SAFETY: 本进程在释放映射之前不会改写这个文件（见 compress_source_to），
+        // 文件夹锁也阻止了其他实例同时处理它
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        // 后台预读时让系统提前把内容读进页缓存，解码时不必再等磁盘
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::WillNeed);
+        Ok(SourceData::Mapped(map))
+    })
+    .map_err(|source| CompressError::Read {
+        path: path.to_path_buf(),
+        source,
+        retries,
+    })?;
+    Ok(SourceBytes {
+        bytes,
+        read_time: started.elapsed(),
+        retries,
+    })
+}
+
+/// 与 compress_image_to 相同，但 path 的内容已由调用方读入，如批量处理时提前预读
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compress_source_to(
+    path: &Path,
+    source: SourceBytes,
+    destination: Option<&Path>,
+    options: &CompressionOptions,
+) -> Result<CompressionStats> {
+    process_source(path, source, destination, None, options, false)
+}
+
+/// 原地处理，转换格式时写到调用方用 convert::reserve_output 预留的 converted，不再自行选名
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compress_source_converted(
+    path: &Path,
+    source: SourceBytes,
+    converted: &Path,
+    options: &CompressionOptions,
+) -> Result<CompressionStats> {
+    process_source(path, source, None, Some(converted), options, false)
+}
+
+/// 预览：按与原地压缩完全相同的流程在内存中编码，返回的大小是准确的，
+/// 但不写入、不删除任何文件。output 为实际运行时会写到的新文件
+#[cfg(not(target_arch = "wasm32"))]
+pub fn preview_image(path: &Path, options: &CompressionOptions) -> Result<CompressionStats> {
+    let source = read_source(path, options)?;
+    process_source(path, source, None, None, options, true)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn process_source(
+    path: &Path,
+    source: SourceBytes,
+    destination: Option<&Path>,
+    converted: Option<&Path>,
+    options: &CompressionOptions,
+    dry_run: bool,
+) -> Result<CompressionStats> {
+    let mut timings = StageTimings {
+        read: source.read_time,
+        ..StageTimings::default()
+    };
+    let mut stage = Instant::now();
+    let mut lap = || {
+        let elapsed = stage.elapsed();
+        stage = Instant::now();
+        elapsed
+    };
+
+    let SourceBytes {
+        bytes: input,
+        mut retries,
+        ..
+    } = source;
+    let preserved = file_attrs::capture(path, options.output.preserve_modified_time);
+    timings.read += lap();
+    let (format, mut image) = codec::decode_for(&input, options).map_err(|err| err.at(path))?;
+    timings.decode = lap();
+
+    if !options.is_enabled(format) {
+        return Err(CompressError::FormatDisabled(format).into());
+    }
+    // 动图不缩小第一帧、不判断是否值得压缩，整个动画重新编码后按体积决定
+    let animated = animation::is_animation(&input, format);
+    let animation_options;
+    let options = if animated {
+        animation_options = options.for_animation();
+        &animation_options
+    } else {
+        options
+    };
+
+    // 按这张图的内容类型换用对应的质量参数
+    let tuned;
+    let (options, class) = if options.auto_tune.enabled && !animated {
+        let class = classify::classify(&image);
+        let mut adjusted = options.clone();
+        ContentProfile::from(class).bundle().apply_to(&mut adjusted);
+        tuned = adjusted;
+        (&tuned, Some(class))
+    } else {
+        (options, None)
+    };
+    let for_source = options.for_source(format);
+    let options = for_source.as_ref();
+    let target = options.output_format(format);
+    let labelled = |encoder: String| match class {
+        Some(class) => format!("[{}] {encoder}", class.label()),
+        None => encoder,
+    };
+
+    // 原文件不动；写到其他位置时原样复制过去
+    let keep_original =
+        |input: &[u8], reason: KeepReason, encoder: String, mut retries: u32, timings: StageTimings| {
+            if let Some(destination) = destination
+                && !dry_run
+            {
+                let replace = options.output.folder.is_none();
+                write_output(
+                    destination,
+                    input,
+                    &preserved,
+                    options,
+                    replace,
+                    &mut retries,
+                )?;
+            }
+            Ok(CompressionStats {
+                original_size: input.len() as u64,
+                new_size: input.len() as u64,
+                encoder,
+                output: destination.map(Path::to_path_buf),
+                kept_reason: Some(reason),
+                retries,
+                timings,
+            })
+        };
+
+    if !animated {
+        metadata::auto_orient(&input, &mut image, &options.metadata);
+    }
+    // 超过最大宽高时先缩小，缩小过的图像一定重新编码
+    let resized = if animated {
+        options.resize.fit(image.width(), image.height())
+    } else {
+        codec::limit_dimensions(&image, &options.resize).map(|smaller| {
+            image = smaller;
+            (image.width(), image.height())
+        })
+    };
+    let kept_reason = match resized {
+        Some(_) => None,
+        None if animated => None,
+        None => keep_reason(format, target, &input, &image, options),
+    };
+    timings.encode = lap();
+    if let Some(reason) = kept_reason {
+        let encoder = labelled(options.describe_format(format));
+        return keep_original(&input, reason, encoder, retries, timings);
+    }
+
+    let encoded = if animated {
+        animation::recompress(&input, format, target, options).map(|encoded| {
+            let encoder = options.describe_format(target);
+            (
+                encoded.bytes,
+                format!("{encoder} 动图 {} 帧", encoded.frames),
+            )
+        })
+    } else {
+        let metadata = metadata::prepare(&input, target, &options.metadata);
+        match options.target_size.max_bytes() {
+            Some(max_bytes) => target_size::encode_to_size(
+                &image, target, options, &metadata, max_bytes,
+            )
+            .map(|sized| {
+                let encoder = sized.describe(target, max_bytes);
+                (sized.bytes, encoder)
+            }),
+            None => codec::encode_with_metadata(&image, target, options, &metadata)
+                .map(|bytes| (bytes, options.describe_format(target))),
+        }
+    };
+    let (buffer, encoder) = encoded.map_err(|err| match err.downcast::<CompressError>() {
+        Ok(err) => err,
+        Err(err) => CompressError::Encode {
+            path: path.to_path_buf(),
+            detail: format!("{err:#}"),
+        },
+    })?;
+    let encoder = match resized {
+        Some((width, height)) => format!("{encoder} 缩小到 {width}x{height}"),
+        None => encoder,
+    };
+    timings.encode += lap();
+    // 不转换格式也没有缩小时，结果没有变小就不必改写
+    if target == format
+        && resized.is_none()
+        && !options.output.force_rewrite
+        && buffer.len() >= input.len()
+    {
+        let reason = KeepReason::NoGain {
+            before: input.len() as u64,
+            after: buffer.len() as u64,
+        };
+        return keep_original(&input, reason, labelled(encoder), retries, timings);
+    }
+    let original_size = input.len() as u64;
+    // 大文件是内存映射的，改写或删除原文件之前先释放
+    drop(input);
+
+    let output = match destination {
+        // 批量处理时输出位置已按预计的格式（含动图）取好扩展名，直接调用时不符才替换
+        Some(destination) if ImageFormat::from_path(destination).ok() == Some(target) => {
+            Some(destination.to_path_buf())
+        }
+        Some(destination) => Some(destination.with_extension(target.extensions_str()[0])),
+        None if target == format => None,
+        None => match converted {
+            Some(converted) => Some(converted.to_path_buf()),
+            None => Some(convert::resolve_output(
+                path,
+                &options.convert,
+                &HashSet::new(),
+            )?),
+        },
+    };
+    if dry_run {
+        return Ok(CompressionStats {
+            original_size,
+            new_size: buffer.len() as u64,
+            encoder: labelled(encoder),
+            output,
+            kept_reason: None,
+            retries,
+            timings,
+        });
+    }
+    match &output {
+        Some(output) => {
+            // 输出文件夹中的名字已避开现有文件，原地转换出的新文件只在覆盖规则下替换同名文件；
+            // 保留两份时的副本每次重新生成
+            let replace = match destination {
+                Some(_) => options.output.folder.is_none(),
+                None => options.convert.collision == options::CollisionRule::Overwrite,
+            };
+            write_output(output, &buffer, &preserved, options, replace, &mut retries)?
+        }
+        None => {
+            let mut attempts = 0;
+            retry::with_retry(&options.retry, &mut attempts, || {
+                file_attrs::write_preserving(path, &buffer, &preserved)
+            })
+            .map_err(|source| CompressError::Write {
+                path: path.to_path_buf(),
+                source,
+                retries: attempts,
+            })?;
+            retries += attempts;
+        }
+    }
+    if destination.is_none() && output.is_some() && options.convert.remove_original {
+        fs::remove_file(path).map_err(|source| CompressError::RemoveOriginal {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    }
+    timings.write = lap();
+
+    Ok(CompressionStats {
+        original_size,
+        new_size: buffer.len() as u64,
+        encoder: labelled(encoder),
+        output,
+        kept_reason: None,
+        retries,
+        timings,
+    })
+}
+
+/// 判断不值得重新编码时返回原因，只在不转换格式时判断
+#[cfg(not(target_arch = "wasm32"))]
+fn keep_reason(
+    format: ImageFormat,
+    target: ImageFormat,
+    input: &[u8],
+    image: &image::DynamicImage,
+    options: &CompressionOptions,
+) -> Option<KeepReason> {
+    if target != format {
+        return None;
+    }
+    // 目标大小模式只看体积，超过上限的文件必须重新编码
+    if let Some(max_bytes) = options.target_size.max_bytes() {
+        return (input.len() as u64 <= max_bytes).then_some(KeepReason::BelowTarget {
+            max_kb: options.target_size.max_kb,
+        });
+    }
+    // 源文件质量已经不高于目标时，再次有损编码只会叠加损失
+    if format == ImageFormat::Jpeg
+        && options.jpeg.keep_low_quality_sources
+        && let Some(source_quality) = codec::estimate_jpeg_quality(input)
+        && source_quality <= options.jpeg.quality
+    {
+        return Some(KeepReason::LowQualitySource {
+            source: source_quality,
+            target: options.jpeg.quality,
+        });
+    }
+    let threshold = options.probe.min_saving_percent;
+    // 试编码失败时照常完整编码，由完整编码报告错误
+    if threshold > 0
+        && let Ok(Some(predicted)) = estimate::probe_size(image, target, options)
+    {
+        let saving = savings_percent(input.len() as u64, predicted);
+        if saving < threshold as f64 {
+            return Some(KeepReason::SmallPredictedSaving { saving, threshold });
+        }
+    }
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_output(
+    path: &Path,
+    bytes: &[u8],
+    preserved: &file_attrs::PreservedAttrs,
+    options: &CompressionOptions,
+    replace: bool,
+    retries: &mut u32,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| CompressError::CreateDir {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    let mut attempts = 0;
+    let written = retry::with_retry(&options.retry, &mut attempts, || {
+        if replace {
+            file_attrs::write_preserving(path, bytes, preserved)
+        } else {
+            file_attrs::write_new(path, bytes, preserved)
+        }
+    });
+    *retries += attempts;
+    written.map_err(|source| {
+        CompressError::Write {
+            path: path.to_path_buf(),
+            source,
+            retries: attempts,
+        }
+        .into()
+    })
+}
+
+pub fn bytes_to_kb(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0
+}
+
+pub fn bytes_to_mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+pub fn savings_percent(before: u64, after: u64) -> f64 {
+    if before == 0 {
+        0.0
+    } else {
+        100.0 * (before as f64 - after as f64) / before as f64
+    }
+}
+
+pub struct CompressionStats {
+    pub original_size: u64,
+    pub new_size: u64,
+    /// 实际使用的编码参数，如“JPEG 质量 80”
+    pub encoder: String,
+    /// 写到原文件以外的位置（转换格式或输出文件夹）时的新文件，原地压缩时为 None
+    pub output: Option<PathBuf>,
+    /// 判断不值得重新编码、原文件未改动时的原因
+    pub kept_reason: Option<KeepReason>,
+    /// 读写遇到暂时性错误后重试的次数
+    pub retries: u32,
+    pub timings: StageTimings,
+}
+
+impl CompressionStats {
+    /// 保持原样时的说明，写到其他位置时注明原样复制
+    pub fn kept_text(&self, language: Language) -> Option<String> {
+        let reason = self.kept_reason.as_ref()?.render(language);
+        Some(match (language, self.output.is_some()) {
+            (Language::Chinese, true) => format!("{reason}，原样复制"),
+            (Language::Chinese, false) => format!("{reason}，保持原样"),
+            (Language::English, true) => format!("{reason}, copied unchanged"),
+            (Language::English, false) => format!("{reason}, left unchanged"),
+        })
+    }
+}
+
+/// 不值得重新编码、保留原文件内容的原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeepReason {
+    /// 目标大小模式下已不超过目标
+    BelowTarget { max_kb: u32 },
+    /// 源文件估计的 JPEG 质量不高于目标质量
+    LowQualitySource { source: u8, target: u8 },
+    /// 试编码预计的节省比例低于阈值
+    SmallPredictedSaving { saving: f64, threshold: u8 },
+    /// 重新编码后没有变小
+    NoGain { before: u64, after: u64 },
+}
+
+impl KeepReason {
+    pub fn render(&self, language: Language) -> String {
+        let zh = language == Language::Chinese;
+        match self {
+            KeepReason::BelowTarget { max_kb } if zh => format!("已小于目标大小 {max_kb} KB"),
+            KeepReason::BelowTarget { max_kb } => {
+                format!("already below the target size of {max_kb} KB")
+            }
+            KeepReason::LowQualitySource { source, target } if zh => {
+                format!("源文件质量约 {source}，不高于目标 {target}")
+            }
+            KeepReason::LowQualitySource { source, target } => {
+                format!("source quality is about {source}, not above the target {target}")
+            }
+            KeepReason::SmallPredictedSaving { saving, threshold } if zh => {
+                format!("试编码预计只能节省约 {saving:.1}%，低于 {threshold}%")
+            }
+            KeepReason::SmallPredictedSaving { saving, threshold } => format!(
+                "a trial encode predicts only about {saving:.1}% saving, below {threshold}%"
+            ),
+            KeepReason::NoGain { before, after } if zh => format!(
+                "没有收益（{:.1} KB → {:.1} KB）",
+                bytes_to_kb(*before),
+                bytes_to_kb(*after)
+            ),
+            KeepReason::NoGain { before, after } => format!(
+                "no gain ({:.1} KB → {:.1} KB)",
+                bytes_to_kb(*before),
+                bytes_to_kb(*after)
+            ),
+        }
+    }
+}
+
+/// 单个文件各阶段的耗时
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    pub read: Duration,
+    pub decode: Duration,
+    pub encode: Duration,
+    pub write: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.read + self.decode + self.encode + self.write
+    }
+}
@@ -0,0 +1,365 @@
+//! 跨进程的文件夹锁：在被处理的文件夹根目录放一个加了系统独占锁的文件，
+//! 防止两个实例（或界面与命令行）同时改写同一批图像。
+//! 上级或下级文件夹被锁住时同样视为占用：两者会改写相同的文件。
+//! 上级看各级文件夹中的锁文件；下级不遍历子目录，而是查数据目录中登记的已锁文件夹。
+
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use same_file::Handle;
+
+use crate::app_data;
+
+pub const LOCK_FILE_NAME: &str = ".compress_img.lock";
+/// 数据目录下登记已锁文件夹的目录，每个锁一个文件，内容为文件夹路径
+const REGISTRY_DIR_NAME: &str = "locks";
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 本进程持有或正在获取锁的文件夹（规范化后的路径）
+static HELD: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+/// 本进程中下一个登记文件的序号
+static NEXT_ENTRY: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// 文件夹被占用时直接报错
+    #[default]
+    Refuse,
+    /// 排队等待占用方结束
+    Wait,
+}
+
+pub struct FolderLock {
+    // 持有期间保持打开，关闭即释放系统锁
+    _file: File,
+    path: PathBuf,
+    key: PathBuf,
+    entry: PathBuf,
+}
+
+impl FolderLock {
+    /// 文件夹、其上级文件夹或其下的子文件夹正被其他任务处理时返回 Ok(None)
+    pub fn try_acquire(folder: &Path) -> Result<Option<FolderLock>> {
+        let key = fs::canonicalize(folder).unwrap_or_else(|_| folder.to_path_buf());
+        // 先在本进程内占位再做文件操作，期间不持有全局锁，也不会同时锁住父子两个文件夹
+        {
+            let mut held = held();
+            if held
+                .iter()
+                .any(|other| other.starts_with(&key) || key.starts_with(other))
+            {
+                return Ok(None);
+            }
+            held.push(key.clone());
+        }
+        let result = Self::acquire_reserved(folder, &key);
+        if !matches!(result, Ok(Some(_))) {
+            release(&key);
+        }
+        result
+    }
+
+    fn acquire_reserved(folder: &Path, key: &Path) -> Result<Option<FolderLock>> {
+        let path = folder.join(LOCK_FILE_NAME);
+        loop {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&path)
+                .with_context(|| format!("无法创建锁文件: {}", path.display()))?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Ok(None),
+                Err(TryLockError::Error(err)) => {
+                    return Err(err).with_context(|| format!("无法锁定: {}", path.display()));
+                }
+            }
+
+            // 上一个持有者可能在我们打开之后、加锁之前删除了锁文件，
+            // 此时锁住的是已删除的文件，需要重新来过
+            let still_current = Handle::from_path(&path)
+                .and_then(|on_disk| Ok(on_disk == Handle::from_file(file.try_clone()?)?))
+                .unwrap_or(false);
+            if !still_current {
+                continue;
+            }
+
+            // 先加锁、登记再检查上下级：两个进程同时锁父子文件夹时至少一方能看到对方，
+            // 最坏情况下双方都退让，排队的一方下一轮重试
+            let entry = match register(key) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    let _ = fs::remove_file(&path);
+                    return Err(err);
+                }
+            };
+            let nearby = locked_nearby(key, &entry);
+            if !matches!(nearby, Ok(false)) {
+                let _ = fs::remove_file(&entry);
+                let _ = fs::remove_file(&path);
+                return nearby.map(|_| None);
+            }
+
+            let _ = file.set_len(0);
+            let _ = writeln!(file, "pid={}", std::process::id());
+            return Ok(Some(FolderLock {
+                _file: file,
+                path,
+                key: key.to_path_buf(),
+                entry,
+            }));
+        }
+    }
+
+    pub fn acquire(folder: &Path) -> Result<FolderLock> {
+        Self::try_acquire(folder)?
+            .ok_or_else(|| anyhow!("文件夹正在被另一个压缩任务处理: {}", folder.display()))
+    }
+
+    /// 按策略获取锁。排队期间每轮调用一次 on_wait，cancel 置位时返回 Ok(None)
+    pub fn acquire_with(
+        folder: &Path,
+        policy: LockPolicy,
+        cancel: &AtomicBool,
+        mut on_wait: impl FnMut(),
+    ) -> Result<Option<FolderLock>> {
+        if policy == LockPolicy::Refuse {
+            return Self::acquire(folder).map(Some);
+        }
+        loop {
+            if let Some(lock) = Self::try_acquire(folder)? {
+                return Ok(Some(lock));
+            }
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            on_wait();
+            thread::sleep(WAIT_INTERVAL);
+        }
+    }
+}
+
+impl Drop for FolderLock {
+    fn drop(&mut self) {
+        // 先删除再随文件句柄关闭释放锁，等待方会发现文件已被替换并重试
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(&self.entry);
+        release(&self.key);
+    }
+}
+
+/// 某个任务 panic 时不应让之后所有的加锁都跟着 panic
+fn held() -> MutexGuard<'static, Vec<PathBuf>> {
+    HELD.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn release(key: &Path) {
+    held().retain(|other| other != key);
+}
+
+/// 在数据目录中登记 key 已被锁住，返回登记文件的路径
+fn register(key: &Path) -> Result<PathBuf> {
+    let dir = app_data::data_dir()?.join(REGISTRY_DIR_NAME);
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建目录: {}", dir.display()))?;
+    let entry = dir.join(format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_ENTRY.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&entry, key.to_string_lossy().as_bytes())
+        .with_context(|| format!("无法登记文件夹锁: {}", entry.display()))?;
+    Ok(entry)
+}
+
+/// 上级文件夹的锁文件被其他进程锁住，或登记中有仍被锁住的下级文件夹。
+/// 锁文件已不再被锁住的登记是中途退出的进程留下的，顺手删除
+fn locked_nearby(folder: &Path, own_entry: &Path) -> Result<bool> {
+    for ancestor in folder.ancestors().skip(1) {
+        if is_locked(&ancestor.join(LOCK_FILE_NAME))? {
+            return Ok(true);
+        }
+    }
+    let Some(dir) = own_entry.parent() else {
+        return Ok(false);
+    };
+    let entries = fs::read_dir(dir).with_context(|| format!("无法读取: {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let entry = entry.path();
+        if entry == own_entry {
+            continue;
+        }
+        let Ok(locked) = fs::read_to_string(&entry) else {
+            continue;
+        };
+        let locked = PathBuf::from(locked);
+        if !locked.starts_with(folder) {
+            continue;
+        }
+        if is_locked(&locked.join(LOCK_FILE_NAME))? {
+            return Ok(true);
+        }
+        let _ = fs::remove_file(&entry);
+    }
+    Ok(false)
+}
+
+fn is_locked(path: &Path) -> Result<bool> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(false),
+    };
+    match file.try_lock_shared() {
+        Ok(()) => Ok(false),
+        Err(TryLockError::WouldBlock) => Ok(true),
+        Err(TryLockError::Error(err)) => {
+            Err(err).with_context(|| format!("无法检查锁文件: {}", path.display()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::{Command, Stdio};
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("compress_img_lock_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("photos/2024")).unwrap();
+        fs::create_dir_all(root.join("photos2")).unwrap();
+        root
+    }
+
+    #[test]
+    fn locked_parent_blocks_child() {
+        let root = temp_folder("parent");
+        let parent = FolderLock::try_acquire(&root.join("photos")).unwrap();
+        assert!(parent.is_some());
+        assert!(FolderLock::try_acquire(&root.join("photos/2024"))
+            .unwrap()
+            .is_none());
+        drop(parent);
+        assert!(FolderLock::try_acquire(&root.join("photos/2024"))
+            .unwrap()
+            .is_some());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn locked_child_blocks_parent() {
+        let root = temp_folder("child");
+        let child = FolderLock::try_acquire(&root.join("photos/2024")).unwrap();
+        assert!(child.is_some());
+        assert!(FolderLock::try_acquire(&root.join("photos"))
+            .unwrap()
+            .is_none());
+        assert!(FolderLock::try_acquire(&root).unwrap().is_none());
+        drop(child);
+        assert!(FolderLock::try_acquire(&root.join("photos"))
+            .unwrap()
+            .is_some());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    /// 供 locked_in_other_process 在子进程中运行：锁住环境变量给出的文件夹，直到标准输入关闭
+    #[test]
+    #[ignore]
+    fn hold_lock_until_stdin_closes() {
+        let Some(folder) = std::env::var_os(CHILD_FOLDER_VAR) else {
+            return;
+        };
+        let _lock = FolderLock::acquire(Path::new(&folder)).unwrap();
+        println!("{CHILD_READY}");
+        let _ = std::io::stdin().read_to_end(&mut Vec::new());
+    }
+
+    const CHILD_FOLDER_VAR: &str = "COMPRESS_IMG_TEST_LOCK_FOLDER";
+    const CHILD_READY: &str = "compress_img: locked";
+
+    #[test]
+    fn locked_in_other_process() {
+        let root = temp_folder("process");
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "lock::tests::hold_lock_until_stdin_closes",
+                "--ignored",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(CHILD_FOLDER_VAR, root.join("photos/2024"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        while stdout.read_line(&mut line).unwrap() > 0 && !line.contains(CHILD_READY) {
+            line.clear();
+        }
+        assert!(line.contains(CHILD_READY), "子进程没有拿到锁");
+
+        assert!(FolderLock::try_acquire(&root.join("photos/2024"))
+            .unwrap()
+            .is_none());
+        assert!(FolderLock::try_acquire(&root.join("photos"))
+            .unwrap()
+            .is_none());
+        assert!(FolderLock::try_acquire(&root).unwrap().is_none());
+        assert!(FolderLock::try_acquire(&root.join("photos2"))
+            .unwrap()
+            .is_some());
+
+        drop(child.stdin.take());
+        // 读完剩下的输出，免得子进程写到已关闭的管道
+        stdout.read_to_string(&mut String::new()).unwrap();
+        assert!(child.wait().unwrap().success());
+        assert!(FolderLock::try_acquire(&root.join("photos"))
+            .unwrap()
+            .is_some());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn poisoned_registry_still_locks() {
+        let root = temp_folder("poison");
+        let _ = thread::spawn(|| {
+            let _held = HELD.lock();
+            panic!("持有登记时 panic");
+        })
+        .join();
+        let photos = FolderLock::try_acquire(&root.join("photos")).unwrap();
+        assert!(photos.is_some());
+        assert!(FolderLock::try_acquire(&root.join("photos/2024"))
+            .unwrap()
+            .is_none());
+        drop(photos);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn sibling_with_common_prefix_is_independent() {
+        let root = temp_folder("sibling");
+        let _photos = FolderLock::try_acquire(&root.join("photos"))
+            .unwrap()
+            .unwrap();
+        assert!(FolderLock::try_acquire(&root.join("photos2"))
+            .unwrap()
+            .is_some());
+        // 同一文件夹换一种写法也能认出来
+        assert!(FolderLock::try_acquire(&root.join("photos2/../photos"))
+            .unwrap()
+            .is_none());
+        let _ = fs::remove_dir_all(root);
+    }
+}
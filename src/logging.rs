@@ -0,0 +1,118 @@
+//! 程序诊断日志：写入数据目录下的 logs/compress_img.log，
+//! 超过大小上限时轮转，保留最近几份。与每次运行的结果日志分开。
+
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::app_data;
+
+const LOG_FILE_NAME: &str = "compress_img.log";
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+const KEEP_ROTATED: usize = 5;
+
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+/// 安装全局日志记录器和 panic 钩子，重复调用时忽略
+pub fn init() -> Result<PathBuf> {
+    let dir = app_data::data_dir()?.join("logs");
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建日志目录: {}", dir.display()))?;
+    let path = dir.join(LOG_FILE_NAME);
+    let logger = FileLogger {
+        file: Mutex::new(open_append(&path).ok()),
+        path: path.clone(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            log::error!("程序崩溃: {info}");
+            previous(info);
+        }));
+        log::info!("程序启动，版本 {}", env!("CARGO_PKG_VERSION"));
+    }
+    Ok(path)
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} [{}] {}: {}\n",
+            app_data::unix_now(),
+            level_label(record.level()),
+            record.target(),
+            record.args()
+        );
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if file
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .is_some_and(|metadata| metadata.len() >= MAX_LOG_BYTES)
+        {
+            *file = None;
+            rotate(&self.path);
+        }
+        if file.is_none() {
+            *file = open_append(&self.path).ok();
+        }
+        if let Some(file) = file.as_mut() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// 调试模式下额外记录 debug 级别的细节
+pub fn set_verbose(verbose: bool) {
+    log::set_max_level(if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    });
+}
+
+fn level_label(level: Level) -> &'static str {
+    match level {
+        Level::Error => "错误",
+        Level::Warn => "警告",
+        Level::Info => "信息",
+        Level::Debug => "调试",
+        Level::Trace => "跟踪",
+    }
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+// compress_img.log → .1 → .2 ... 最旧的一份被覆盖
+fn rotate(path: &Path) {
+    let rotated = |index: usize| PathBuf::from(format!("{}.{index}", path.display()));
+    for index in (1..KEEP_ROTATED).rev() {
+        let _ = fs::rename(rotated(index), rotated(index + 1));
+    }
+    let _ = fs::rename(path, rotated(1));
+}
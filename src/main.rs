@@ -2,290 +2,3265 @@
 
 slint::include_modules!();
 
-use anyhow::{anyhow, Context, Result};
-use image::codecs::jpeg::JpegEncoder;
-use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-use image::{ExtendedColorType, ImageEncoder, ImageFormat, ImageReader};
-use slint::{ComponentHandle, SharedString};
-use std::fs;
-use std::io::Cursor;
+use anyhow::Result;
+use compress_img::app_settings::AppSettings;
+use compress_img::backup::{self, BackupRun, BackupSession};
+use compress_img::batch::{
+    self, BatchEvent, BatchSummary, BatchTarget, FileOutcome, FileOverrides, PreRunInfo,
+    SkipReason, SkippedFiles,
+};
+use compress_img::control::RunControl;
+use compress_img::error::{self, Language};
+use compress_img::estimate::{self, SizeEstimate};
+use compress_img::failures::FailureStore;
+use compress_img::history::{self, DailyStats};
+use compress_img::lock::LockPolicy;
+use compress_img::manifest::{self, JobManifest};
+use compress_img::options::{
+    ChromaSubsampling, CollisionRule, CompressionOptions, FailureAction, JpegEncoderKind,
+    MetadataMode, OutputFormat, ResizeFilter, TargetMode,
+};
+use compress_img::preset::QualityPreset;
+use compress_img::profile::ContentProfile;
+use compress_img::report::{ReportEntry, ReportStatus, RunReport};
+use compress_img::review::{self, ReviewItem};
+use compress_img::taskbar::{TaskbarProgress, TaskbarState};
+use compress_img::watch::{FolderWatcher, WatchEvent};
+use compress_img::{
+    app_data, bench, bytes_to_kb, bytes_to_mb, cli, compare, crash, dedup, folder_settings,
+    integrity, logging, output, profile, savings_percent, scan, tuning, update,
+};
+use image::ImageFormat;
+use slint::winit_030::winit::event::WindowEvent;
+use slint::winit_030::{WinitWindowAccessor, WinitWindowEventResult};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, StandardListViewItem, VecModel};
+use std::cell::{Cell, RefCell};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use walkdir::WalkDir;
+use std::time::Instant;
+
+/// 界面只有中文，核心返回的原因和错误都按中文渲染
+const UI_LANGUAGE: Language = Language::Chinese;
 
 fn main() -> Result<()> {
+    // 日志只用于排查问题，初始化失败不影响使用
+    crash::install(logging::init().ok());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // 命令行模式的输出跟随系统语言
+    if !args.is_empty() {
+        #[cfg(windows)]
+        attach_parent_console();
+        let language = Language::detect();
+        if let Err(err) = cli::run(&args, language) {
+            eprintln!("{}", error::render_error(&err, language));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let app = AppWindow::new()?;
+    let settings = Rc::new(RefCell::new(AppSettings::load().unwrap_or_else(|err| {
+        log::warn!("{err:#}");
+        AppSettings::default()
+    })));
+    // 恢复上次关闭时的文件夹和设置
+    apply_options_to_ui(&app, &settings.borrow().last_options().unwrap_or_default());
+    if let Some(folder) = settings
+        .borrow()
+        .last_folder
+        .as_ref()
+        .filter(|f| f.is_dir())
+    {
+        app.set_selected_folder(folder.display().to_string().into());
+    }
+    app.set_rename_template_help(output::TEMPLATE_HELP.into());
+    app.set_gpu_supported(cfg!(feature = "gpu"));
 
-    let ui_weak = app.as_weak();
+    let stats_window = StatsWindow::new()?;
+    let restore_window = RestoreWindow::new()?;
+    setup_restore_window(&restore_window);
+    let ignore_window = IgnoreWindow::new()?;
+    setup_ignore_window(&ignore_window);
+    let review_window = ReviewWindow::new()?;
+    setup_review_window(&review_window);
+    let estimate_window = EstimateWindow::new()?;
+    // 最近一次预估使用的设置，单独改某个文件的输出格式后按它重新预估
+    let estimate_options = Rc::new(RefCell::new(CompressionOptions::default()));
+    setup_estimate_window(&estimate_window, estimate_options.clone());
+    let quality_window = QualityWindow::new()?;
+    setup_quality_window(&quality_window, &app);
+    setup_drop_targets(&app);
+    setup_named_presets(&app, settings.clone());
+    setup_folder_pickers(&app);
+    setup_content_presets(&app);
+    setup_config_files(&app);
+    setup_folder_tools(&app);
+    setup_run_history(&app, &stats_window, &restore_window);
+    setup_taskbar(&app);
+    setup_ignore_actions(&app, &ignore_window);
+    setup_preview_windows(
+        &app,
+        &review_window,
+        &estimate_window,
+        estimate_options,
+        &quality_window,
+    );
+
+    // 结果表格的完整内容，运行和监视中逐个加入
+    let results: SharedResults = Arc::default();
+    setup_result_table(&app, Arc::clone(&results));
+    setup_compress_run(&app, &estimate_window, Arc::clone(&results));
+    setup_folder_watch(&app, Arc::clone(&results));
+    setup_update_check(&app, settings.clone());
+    offer_crash_report();
+
+    app.run()?;
+
+    let mut settings = settings.borrow_mut();
+    let folder = app.get_selected_folder();
+    settings.last_folder = (!folder.is_empty()).then(|| PathBuf::from(folder.as_str()));
+    // 保存失败只影响下次启动时的默认值，不作为错误退出
+    if let Err(err) = settings
+        .set_last_options(&options_from_ui(&app))
+        .and_then(|()| settings.save())
+    {
+        log::warn!("保存程序设置失败: {err:#}");
+    }
+    Ok(())
+}
 
+/// 选择源文件夹时恢复它上次使用的设置，并在后台分析内容给出建议配置
+fn setup_folder_pickers(app: &AppWindow) {
     app.on_pick_folder({
-        let ui_weak = ui_weak.clone();
+        let ui_weak = app.as_weak();
         move || {
-            if let Some(selected) = rfd::FileDialog::new().pick_folder() {
-                if let Some(ui) = ui_weak.upgrade() {
-                    let path_text: SharedString = selected.display().to_string().into();
-                    ui.set_selected_folder(path_text.clone());
-                    ui.set_status_text(format!("已选择文件夹: {}", path_text).into());
+            if let Some(selected) = rfd::FileDialog::new().pick_folder()
+                && let Some(ui) = ui_weak.upgrade()
+            {
+                let path_text: SharedString = selected.display().to_string().into();
+                ui.set_selected_folder(path_text.clone());
+                ui.set_status_text(format!("已选择文件夹: {}", path_text).into());
+                ui.set_suggested_profile("".into());
+                ui.set_suggestion_text("正在分析文件夹内容...".into());
+
+                if let Ok(Some(previous)) = folder_settings::last_options(&selected) {
+                    apply_options_to_ui(&ui, &previous);
+                    reset_preset(&ui);
+                    ui.set_status_text(
+                        format!("已选择文件夹: {}（已恢复上次使用的设置）", path_text).into(),
+                    );
                 }
+
+                let options = options_from_ui(&ui);
+                let ui_weak = ui_weak.clone();
+                thread::spawn(move || {
+                    let result = profile::suggest_profile(&selected, &options);
+                    let _ = slint::invoke_from_event_loop(move || {
+                        let Some(ui) = ui_weak.upgrade() else {
+                            return;
+                        };
+                        // 分析期间用户可能又换了文件夹
+                        if ui.get_selected_folder().as_str() != selected.display().to_string() {
+                            return;
+                        }
+                        match result {
+                            Ok(suggestion) => {
+                                ui.set_suggested_profile(suggestion.profile.key().into());
+                                ui.set_suggestion_text(suggestion.summary().into());
+                            }
+                            Err(err) => ui.set_suggestion_text(format!("{err}").into()),
+                        }
+                    });
+                });
             }
         }
     });
 
-    app.on_start_compress({
-        let ui_weak = ui_weak.clone();
+    app.on_pick_output_folder({
+        let ui_weak = app.as_weak();
+        move || {
+            if let Some(selected) = rfd::FileDialog::new().pick_folder()
+                && let Some(ui) = ui_weak.upgrade()
+            {
+                let source = PathBuf::from(ui.get_selected_folder().as_str());
+                if same_file::is_same_file(&source, &selected).unwrap_or(false) {
+                    ui.set_status_text("输出文件夹不能是源文件夹本身".into());
+                    return;
+                }
+                ui.set_output_folder(selected.display().to_string().into());
+            }
+        }
+    });
+
+    app.on_pick_backup_folder({
+        let ui_weak = app.as_weak();
+        move || {
+            if let Some(selected) = rfd::FileDialog::new().pick_folder()
+                && let Some(ui) = ui_weak.upgrade()
+            {
+                ui.set_backup_folder(selected.display().to_string().into());
+            }
+        }
+    });
+}
+
+/// 建议配置和内置质量预设只修改界面上的设置，不直接开始处理
+fn setup_content_presets(app: &AppWindow) {
+    app.on_apply_suggestion({
+        let ui_weak = app.as_weak();
         move || {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
+            let Some(profile) = ContentProfile::from_key(ui.get_suggested_profile().as_str())
+            else {
+                return;
+            };
+            let mut options = options_from_ui(&ui);
+            profile.bundle().apply_to(&mut options);
+            apply_options_to_ui(&ui, &options);
+            reset_preset(&ui);
+            ui.set_status_text(format!("已应用建议配置: {}", profile.label()).into());
+        }
+    });
 
-            if ui.get_busy() {
+    app.on_apply_preset({
+        let ui_weak = app.as_weak();
+        move |index| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(preset) = usize::try_from(index - 1)
+                .ok()
+                .and_then(|i| QualityPreset::ALL.get(i).copied())
+            else {
+                ui.set_preset_description("".into());
+                return;
+            };
+            let mut options = options_from_ui(&ui);
+            preset.bundle().apply_to(&mut options);
+            apply_options_to_ui(&ui, &options);
+            ui.set_preset_description(preset.description().into());
+            ui.set_status_text(format!("已应用预设: {}", preset.label()).into());
+        }
+    });
+}
+
+/// 导入导出配置文件，保存和打开任务清单
+fn setup_config_files(app: &AppWindow) {
+    app.on_import_options({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("配置文件", &["json"])
+                .pick_file()
+            else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
                 return;
+            };
+            match CompressionOptions::load(&path) {
+                Ok(options) => {
+                    apply_options_to_ui(&ui, &options);
+                    reset_preset(&ui);
+                    ui.set_status_text(format!("已导入配置: {}", path.display()).into());
+                }
+                Err(err) => ui.set_status_text(format!("导入配置失败: {err:#}").into()),
             }
+        }
+    });
 
-            let folder: String = ui.get_selected_folder().as_str().into();
-            if folder.is_empty() {
-                ui.set_status_text("请先选择文件夹".into());
+    app.on_export_options({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("配置文件", &["json"])
+                .set_file_name("compress_img.json")
+                .save_file()
+            else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            match options_from_ui(&ui).save(&path) {
+                Ok(()) => ui.set_status_text(format!("已导出配置: {}", path.display()).into()),
+                Err(err) => ui.set_status_text(format!("导出配置失败: {err:#}").into()),
+            }
+        }
+    });
+
+    app.on_save_job({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let file_name = folder
+                .file_name()
+                .map(|name| format!("{}.{}", name.to_string_lossy(), manifest::JOB_EXTENSION))
+                .unwrap_or_else(|| format!("compress_img.{}", manifest::JOB_EXTENSION));
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("任务清单", &[manifest::JOB_EXTENSION])
+                .set_file_name(file_name)
+                .save_file()
+            else {
                 return;
+            };
+            match JobManifest::new(vec![folder], options_from_ui(&ui)).save(&path) {
+                Ok(()) => ui.set_status_text(format!("已保存任务: {}", path.display()).into()),
+                Err(err) => ui.set_status_text(format!("保存任务失败: {err:#}").into()),
             }
+        }
+    });
+
+    app.on_open_job({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("任务清单", &[manifest::JOB_EXTENSION])
+                .pick_file()
+            else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let job = match JobManifest::load(&path) {
+                Ok(job) => job,
+                Err(err) => {
+                    ui.set_status_text(format!("打开任务失败: {err:#}").into());
+                    return;
+                }
+            };
+            apply_options_to_ui(&ui, &job.options);
+            reset_preset(&ui);
+            ui.set_selected_folder(job.folders[0].display().to_string().into());
+            ui.set_suggested_profile("".into());
+            ui.set_suggestion_text("".into());
+            // 界面一次只处理一个文件夹
+            let status = match job.folders.len() {
+                1 => format!("已打开任务: {}", path.display()),
+                count => format!(
+                    "已打开任务: {}（界面只处理第一个文件夹，全部 {count} 个请用 --job 在命令行运行）",
+                    path.display()
+                ),
+            };
+            ui.set_status_text(status.into());
+        }
+    });
+}
 
-            let quality = ui.get_jpeg_quality().round().clamp(1.0, 100.0) as u8;
+/// 基准测试、重复检测、完整性检查和输出对比，在后台线程中运行，都不修改图像
+fn setup_folder_tools(app: &AppWindow) {
+    app.on_start_benchmark({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if ui.get_busy() {
+                return;
+            }
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
 
             ui.set_busy(true);
-            ui.set_status_text("正在扫描图像文件...".into());
+            ui.set_status_text("正在运行基准测试...".into());
             ui.set_log_text("".into());
             ui.set_processed_files(0);
             ui.set_total_files(0);
             ui.set_progress(0.0);
 
-            let ui_weak_for_thread = ui_weak.clone();
+            let ui_weak = ui_weak.clone();
             thread::spawn(move || {
-                if let Err(err) = process_folder(ui_weak_for_thread.clone(), folder, quality) {
-                    let message = format!("压缩失败: {err}");
+                let progress_ui = ui_weak.clone();
+                let result = bench::run_benchmark(&folder, &options, |done, total| {
+                    let ui_weak = progress_ui.clone();
                     let _ = slint::invoke_from_event_loop(move || {
-                        if let Some(ui) = ui_weak_for_thread.upgrade() {
-                            let mut log = ui.get_log_text().to_string();
-                            if !log.is_empty() {
-                                log.push('\n');
-                            }
-                            log.push_str(&message);
-                            ui.set_busy(false);
-                            ui.set_status_text(message.clone().into());
-                            ui.set_log_text(log.into());
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_processed_files(done as i32);
+                            ui.set_total_files(total as i32);
+                            ui.set_progress(done as f32 / total as f32);
                         }
                     });
-                }
+                });
+                let (status, log) = match result {
+                    Ok(report) => ("基准测试完成".to_string(), report.to_text()),
+                    Err(err) => {
+                        let message = format!("基准测试失败: {err}");
+                        (message.clone(), message)
+                    }
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(status.into());
+                        ui.set_log_text(log.into());
+                        ui.set_busy(false);
+                    }
+                });
             });
         }
     });
 
-    app.run()?;
-    Ok(())
-}
+    app.on_find_duplicates({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if ui.get_busy() {
+                return;
+            }
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
 
-fn process_folder(ui_weak: slint::Weak<AppWindow>, folder: String, quality: u8) -> Result<()> {
-    let folder_path = PathBuf::from(&folder);
-    if !folder_path.exists() {
-        return Err(anyhow!("路径不存在: {folder}"));
-    }
-    if !folder_path.is_dir() {
-        return Err(anyhow!("选择的路径不是文件夹: {folder}"));
-    }
+            ui.set_busy(true);
+            ui.set_status_text("正在计算图像哈希...".into());
+            ui.set_log_text("".into());
+            ui.set_processed_files(0);
+            ui.set_total_files(0);
+            ui.set_progress(0.0);
 
-    let mut files: Vec<PathBuf> = Vec::new();
-    let mut log_builder = String::new();
+            let ui_weak = ui_weak.clone();
+            thread::spawn(move || {
+                let progress_ui = ui_weak.clone();
+                let result = dedup::find_duplicates(&folder, &options, |done, total| {
+                    let ui_weak = progress_ui.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_processed_files(done as i32);
+                            ui.set_total_files(total as i32);
+                            ui.set_progress(done as f32 / total as f32);
+                        }
+                    });
+                });
+                let (status, log) = match result {
+                    Ok(report) => (
+                        format!("重复检测完成，共 {} 组", report.groups.len()),
+                        report.to_text(),
+                    ),
+                    Err(err) => {
+                        let message = format!("重复检测失败: {err}");
+                        (message.clone(), message)
+                    }
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(status.into());
+                        ui.set_log_text(log.into());
+                        ui.set_busy(false);
+                    }
+                });
+            });
+        }
+    });
 
-    for entry in WalkDir::new(&folder_path).into_iter() {
-        match entry {
-            Ok(e) => {
-                if e.file_type().is_file() && is_supported_image(e.path()) {
-                    files.push(e.into_path());
-                }
-            }
-            Err(err) => {
-                log_builder.push_str(&format!("遍历时出错: {err}\n"));
+    app.on_check_integrity({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if ui.get_busy() {
+                return;
             }
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
+
+            ui.set_busy(true);
+            ui.set_status_text("正在检查图像完整性（不会修改任何文件）...".into());
+            ui.set_log_text("".into());
+            ui.set_processed_files(0);
+            ui.set_total_files(0);
+            ui.set_progress(0.0);
+
+            let ui_weak = ui_weak.clone();
+            thread::spawn(move || {
+                let progress_ui = ui_weak.clone();
+                let result = integrity::check_folder(&folder, &options, |done, total| {
+                    let ui_weak = progress_ui.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_processed_files(done as i32);
+                            ui.set_total_files(total as i32);
+                            ui.set_progress(done as f32 / total as f32);
+                        }
+                    });
+                });
+                let (status, log) = match result {
+                    Ok(report) => (
+                        format!("完整性检查完成，{} 个文件有问题", report.issues.len()),
+                        report.to_text(),
+                    ),
+                    Err(err) => {
+                        let message = format!("完整性检查失败: {err}");
+                        (message.clone(), message)
+                    }
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(status.into());
+                        ui.set_log_text(log.into());
+                        ui.set_busy(false);
+                    }
+                });
+            });
         }
-    }
+    });
 
-    let total = files.len();
-    {
-        let status = if total > 0 {
-            format!("找到 {total} 个图像文件")
-        } else {
-            "未找到可压缩的图像".to_string()
-        };
-        let log_snapshot = log_builder.clone();
-        let ui_weak = ui_weak.clone();
-        let _ = slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_total_files(total as i32);
-                ui.set_processed_files(0);
-                ui.set_progress(0.0);
-                ui.set_log_text(log_snapshot.into());
-                ui.set_status_text(status.into());
+    app.on_compare_output({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if ui.get_busy() {
+                return;
             }
-        });
-    }
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
 
-    if total == 0 {
-        let ui_weak = ui_weak.clone();
-        let _ = slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_busy(false);
-            }
-        });
-        return Ok(());
-    }
+            ui.set_busy(true);
+            ui.set_status_text("正在对比源文件夹和输出文件夹（不会修改任何文件）...".into());
+            ui.set_log_text("".into());
 
-    let mut total_saved: i64 = 0;
+            let ui_weak = ui_weak.clone();
+            thread::spawn(move || {
+                let (status, log) = match compare::compare_trees(&folder, &options) {
+                    Ok(comparison) => (
+                        format!(
+                            "对比完成：{} 个缺少输出，{} 个变大",
+                            comparison.missing().count(),
+                            comparison.grown().count()
+                        ),
+                        comparison.to_text(),
+                    ),
+                    Err(err) => {
+                        let message = format!("对比失败: {err}");
+                        (message.clone(), message)
+                    }
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(status.into());
+                        ui.set_log_text(log.into());
+                        ui.set_busy(false);
+                    }
+                });
+            });
+        }
+    });
+}
 
-    for (index, path) in files.iter().enumerate() {
-        let display_path = path.display().to_string();
-        match compress_image(path, quality) {
-            Ok(stats) => {
-                let saved = stats.original_size.saturating_sub(stats.new_size) as i64;
-                total_saved += saved;
-                log_builder.push_str(&format!(
-                    "✔ {} | {:.2} KB → {:.2} KB (节省 {:.2}%)\n",
-                    display_path,
-                    bytes_to_kb(stats.original_size),
-                    bytes_to_kb(stats.new_size),
-                    savings_percent(stats.original_size, stats.new_size)
-                ));
+/// 统计图表、备份列表和撤销上次运行
+fn setup_run_history(app: &AppWindow, stats_window: &StatsWindow, restore_window: &RestoreWindow) {
+    app.on_show_statistics({
+        let ui_weak = app.as_weak();
+        let stats_weak = stats_window.as_weak();
+        move || {
+            let (Some(ui), Some(stats)) = (ui_weak.upgrade(), stats_weak.upgrade()) else {
+                return;
+            };
+            match history::load_all() {
+                Ok(records) => {
+                    apply_statistics(&stats, &history::daily_stats(&records));
+                    let _ = stats.show();
+                }
+                Err(err) => ui.set_status_text(format!("读取运行历史失败: {err:#}").into()),
             }
-            Err(err) => {
-                log_builder.push_str(&format!("✖ {} | 失败: {}\n", display_path, err));
+        }
+    });
+
+    app.on_show_backups({
+        let restore_weak = restore_window.as_weak();
+        move || {
+            if let Some(restore) = restore_weak.upgrade() {
+                restore.invoke_refresh();
+                let _ = restore.show();
             }
         }
+    });
 
-        let processed = index + 1;
-        let progress = processed as f32 / total as f32;
-        let log_snapshot = log_builder.clone();
-        let status = format!("正在处理: {} ({}/{})", display_path, processed, total);
-        let ui_weak = ui_weak.clone();
-        let _ = slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_processed_files(processed as i32);
-                ui.set_progress(progress);
-                ui.set_log_text(log_snapshot.into());
-                ui.set_status_text(status.clone().into());
+    app.on_undo_last_run({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let run = match backup::latest_run() {
+                Ok(Some(run)) => run,
+                Ok(None) => {
+                    ui.set_status_text("没有可撤销的运行".into());
+                    return;
+                }
+                Err(err) => {
+                    ui.set_status_text(format!("读取备份失败: {err:#}").into());
+                    return;
+                }
+            };
+            let confirmed = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title("撤销上次运行")
+                .set_description(format!(
+                    "将用备份覆盖 {} 中的 {} 个文件，并删除转换格式时新建的文件，确定继续吗？",
+                    run.folder,
+                    run.entries.len()
+                ))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                == rfd::MessageDialogResult::Yes;
+            if !confirmed {
+                return;
             }
-        });
-    }
 
-    let final_status = if total_saved >= 0 {
-        format!(
-            "完成: 共处理 {total} 个图像，累计节省 {:.2} MB",
-            bytes_to_mb(total_saved as u64)
-        )
-    } else {
-        format!(
-            "完成: 共处理 {total} 个图像，文件总体增大 {:.2} MB",
-            bytes_to_mb((-total_saved) as u64)
-        )
-    };
-    let log_snapshot = log_builder.clone();
-    let ui_weak = ui_weak.clone();
-    let _ = slint::invoke_from_event_loop(move || {
-        if let Some(ui) = ui_weak.upgrade() {
-            ui.set_status_text(final_status.into());
-            ui.set_log_text(log_snapshot.into());
-            ui.set_progress(1.0);
-            ui.set_busy(false);
+            ui.set_status_text("正在撤销上次运行...".into());
+            let ui_weak = ui_weak.clone();
+            thread::spawn(move || {
+                let (restored, errors) = backup::restore_run(&run);
+                for err in &errors {
+                    log::warn!("{err}");
+                }
+                let status = if errors.is_empty() {
+                    format!("已撤销上次运行，恢复 {restored} 个文件")
+                } else {
+                    format!(
+                        "已恢复 {restored} 个文件，{} 个失败（详见诊断日志）",
+                        errors.len()
+                    )
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(status.into());
+                    }
+                });
+            });
         }
     });
-
-    Ok(())
 }
 
-fn is_supported_image(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| match ext.to_ascii_lowercase().as_str() {
-            "jpg" | "jpeg" | "png" => true,
-            _ => false,
-        })
-        .unwrap_or(false)
-}
-
-fn compress_image(path: &Path, quality: u8) -> Result<CompressionStats> {
-    let mut reader = ImageReader::open(path)
-        .with_context(|| format!("无法打开图像: {}", path.display()))?;
-    reader.no_limits();
-    reader = reader
-        .with_guessed_format()
-        .with_context(|| format!("无法识别图像格式: {}", path.display()))?;
-
-    let format = reader
-        .format()
-        .ok_or_else(|| anyhow!("无法确定图像格式: {}", path.display()))?;
-
-    let image = reader
-        .decode()
-        .with_context(|| format!("无法解码图像: {}", path.display()))?;
-
-    let mut cursor = Cursor::new(Vec::new());
-    match format {
-        ImageFormat::Jpeg => {
-            let mut encoder = JpegEncoder::new_with_quality(&mut cursor, quality.max(1));
-            encoder
-                .encode_image(&image)
-                .with_context(|| format!("无法重新编码图像: {}", path.display()))?;
-        }
-        ImageFormat::Png => {
-            let rgba = image.to_rgba8();
-            let (width, height) = rgba.dimensions();
-            let mut encoder = PngEncoder::new_with_quality(
-                &mut cursor,
-                CompressionType::Best,
-                FilterType::Adaptive,
-            );
-            encoder
-                .write_image(
-                    rgba.as_raw(),
-                    width,
-                    height,
-                    ExtendedColorType::Rgba8,
-                )
-                .with_context(|| format!("无法重新编码图像: {}", path.display()))?;
-        }
-        other => {
-            return Err(anyhow!("暂不支持重新编码 {:?} 格式", other));
+fn setup_taskbar(app: &AppWindow) {
+    app.on_taskbar_changed({
+        let ui_weak = app.as_weak();
+        // 第一次更新时窗口已经显示，这时才能拿到系统窗口
+        let taskbar = RefCell::new(None);
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let state = match ui.get_taskbar_state() {
+                1 => TaskbarState::Indeterminate,
+                2 => TaskbarState::Normal,
+                3 => TaskbarState::Error,
+                4 => TaskbarState::Paused,
+                _ => TaskbarState::None,
+            };
+            taskbar
+                .borrow_mut()
+                .get_or_insert_with(|| TaskbarProgress::new(ui.window()))
+                .update(state, ui.get_progress());
         }
-    }
-    let buffer = cursor.into_inner();
-
-    let original_size = fs::metadata(path)
-        .with_context(|| format!("无法读取原文件大小: {}", path.display()))?
-        .len();
+    });
+}
 
-    fs::write(path, &buffer)
-        .with_context(|| format!("无法写回压缩结果: {}", path.display()))?;
+fn setup_ignore_actions(app: &AppWindow, ignore_window: &IgnoreWindow) {
+    app.on_ignore_result({
+        let ui_weak = app.as_weak();
+        move |folder| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(path) = selected_result(&ui) else {
+                return;
+            };
+            let target = if folder {
+                path.parent().map(Path::to_path_buf).unwrap_or(path)
+            } else {
+                path
+            };
+            let status = match add_to_ignore_list(std::slice::from_ref(&target)) {
+                Ok(()) => format!("已加入忽略列表，以后不再处理: {}", target.display()),
+                Err(err) => format!("加入忽略列表失败: {err:#}"),
+            };
+            ui.set_status_text(status.into());
+        }
+    });
 
-    let new_size = buffer.len() as u64;
-    Ok(CompressionStats {
-        original_size,
-        new_size,
-    })
+    app.on_show_ignore_list({
+        let ignore_weak = ignore_window.as_weak();
+        move || {
+            if let Some(ignore) = ignore_weak.upgrade() {
+                ignore.invoke_refresh();
+                let _ = ignore.show();
+            }
+        }
+    });
 }
 
-fn bytes_to_kb(bytes: u64) -> f64 {
-    bytes as f64 / 1024.0
+/// 打开复查、预估、预览和质量对比窗口，它们的内容都按主窗口当前的文件夹和设置生成
+fn setup_preview_windows(
+    app: &AppWindow,
+    review_window: &ReviewWindow,
+    estimate_window: &EstimateWindow,
+    estimate_options: Rc<RefCell<CompressionOptions>>,
+    quality_window: &QualityWindow,
+) {
+    app.on_show_review({
+        let ui_weak = app.as_weak();
+        let review_weak = review_window.as_weak();
+        move || {
+            let (Some(ui), Some(window)) = (ui_weak.upgrade(), review_weak.upgrade()) else {
+                return;
+            };
+            let _ = window.show();
+            if window.get_working() {
+                return;
+            }
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
+            window.set_folder(folder.display().to_string().into());
+            window.set_backup_enabled(options.backup.enabled);
+            window.set_backup_folder(
+                options
+                    .backup
+                    .location(&folder)
+                    .map(|location| location.display().to_string())
+                    .unwrap_or_default()
+                    .into(),
+            );
+            window.set_working(true);
+            window.set_status_text("正在查找待确认的压缩副本...".into());
+
+            let window_weak = window.as_weak();
+            thread::spawn(move || {
+                let items = review::find_pending(&folder, &options);
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(window) = window_weak.upgrade() {
+                        apply_review_items(&window, &folder, &items);
+                    }
+                });
+            });
+        }
+    });
+
+    app.on_estimate_sizes({
+        let ui_weak = app.as_weak();
+        let estimate_weak = estimate_window.as_weak();
+        let estimate_options = estimate_options.clone();
+        move || {
+            if let (Some(ui), Some(window)) = (ui_weak.upgrade(), estimate_weak.upgrade()) {
+                start_estimate(&ui, &window, &estimate_options, false);
+            }
+        }
+    });
+
+    app.on_preview_run({
+        let ui_weak = app.as_weak();
+        let estimate_weak = estimate_window.as_weak();
+        move || {
+            if let (Some(ui), Some(window)) = (ui_weak.upgrade(), estimate_weak.upgrade()) {
+                start_estimate(&ui, &window, &estimate_options, true);
+            }
+        }
+    });
+
+    app.on_show_quality_preview({
+        let ui_weak = app.as_weak();
+        let quality_weak = quality_window.as_weak();
+        move || {
+            let (Some(ui), Some(window)) = (ui_weak.upgrade(), quality_weak.upgrade()) else {
+                return;
+            };
+            let _ = window.show();
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
+            window.set_files(ModelRc::default());
+            window.set_paths(ModelRc::default());
+            window.set_current_file(-1);
+            window.set_status_text("正在扫描图像文件...".into());
+
+            let window_weak = window.as_weak();
+            thread::spawn(move || {
+                let files = scan::scan_folder(&folder, &options).files;
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(window) = window_weak.upgrade() {
+                        apply_quality_files(&window, &folder, &files);
+                    }
+                });
+            });
+        }
+    });
+}
+
+/// 开始、暂停和取消压缩，运行结束后可保存逐文件报告
+fn setup_compress_run(app: &AppWindow, estimate_window: &EstimateWindow, results: SharedResults) {
+    // 当前压缩任务的取消和暂停，开始新的任务时替换
+    let current_run: Rc<RefCell<Arc<RunControl>>> = Rc::default();
+    // 上次运行的逐文件结果，运行结束后才写入
+    let last_report: Arc<Mutex<RunReport>> = Arc::default();
+
+    app.on_save_report({
+        let ui_weak = app.as_weak();
+        let last_report = Arc::clone(&last_report);
+        move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .add_filter("JSON", &["json"])
+                .set_file_name("compress_img_report.csv")
+                .save_file()
+            else {
+                return;
+            };
+            let (Some(ui), Ok(report)) = (ui_weak.upgrade(), last_report.lock()) else {
+                return;
+            };
+            match report.save(&path) {
+                Ok(()) => ui.set_status_text(format!("已保存报告: {}", path.display()).into()),
+                Err(err) => ui.set_status_text(format!("保存报告失败: {err:#}").into()),
+            }
+        }
+    });
+
+    app.on_toggle_pause({
+        let ui_weak = app.as_weak();
+        let current_run = Rc::clone(&current_run);
+        // 暂停前的任务栏状态，继续时恢复（可能已因失败变红）
+        let resumed_state = Cell::new(2);
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let control = current_run.borrow();
+            if control.is_cancelled() {
+                return;
+            }
+            let paused = !control.is_paused();
+            control.set_paused(paused);
+            ui.set_paused(paused);
+            if paused {
+                resumed_state.set(ui.get_taskbar_state());
+                ui.set_taskbar_state(4);
+                ui.set_status_text("已暂停：正在处理的文件完成后不再开始新的文件".into());
+            } else {
+                ui.set_taskbar_state(resumed_state.get());
+                ui.set_status_text("继续处理...".into());
+            }
+        }
+    });
+
+    app.on_cancel_run({
+        let ui_weak = app.as_weak();
+        let current_run = Rc::clone(&current_run);
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            current_run.borrow().cancel();
+            ui.set_paused(false);
+            ui.set_status_text("正在取消：等待正在处理的文件完成...".into());
+        }
+    });
+
+    app.on_start_compress({
+        let ui_weak = app.as_weak();
+        let estimate_weak = estimate_window.as_weak();
+        let current_run = Rc::clone(&current_run);
+        let last_report = Arc::clone(&last_report);
+        let results = Arc::clone(&results);
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+
+            if ui.get_busy() {
+                return;
+            }
+
+            let folder: String = ui.get_selected_folder().as_str().into();
+            // 待处理列表不为空时处理其中的文件和文件夹，否则处理选择的文件夹
+            let queued: Vec<PathBuf> = ui
+                .get_queued_paths()
+                .iter()
+                .map(|item| PathBuf::from(item.text.as_str()))
+                .collect();
+            let targets = if queued.is_empty() {
+                if folder.is_empty() {
+                    ui.set_status_text("请先选择文件夹，或把文件夹和图像拖到窗口中".into());
+                    return;
+                }
+                batch::group_targets(&[PathBuf::from(&folder)])
+            } else {
+                batch::group_targets(&queued)
+            };
+            let Some(first) = targets.first() else {
+                ui.set_status_text("待处理列表中的文件和文件夹都已不存在".into());
+                return;
+            };
+            let folder = first.folder.display().to_string();
+
+            let options = options_from_ui(&ui);
+            if !options.jpeg.enabled
+                && !options.png.enabled
+                && !options.webp.enabled
+                && !options.avif.enabled
+                && !options.animation.gif_enabled
+            {
+                ui.set_status_text("请至少启用一种图像格式".into());
+                return;
+            }
+
+            let changes = targets.iter().find_map(|target| {
+                let previous = folder_settings::last_options(&target.folder).ok()??;
+                let changes = options.lossier_than(&previous);
+                (!changes.is_empty()).then_some(changes)
+            });
+            if let Some(changes) = changes
+                && !confirm_lossier_settings(&changes)
+            {
+                ui.set_status_text("已取消：设置比上次更有损".into());
+                return;
+            }
+
+            let debug = ui.get_debug_mode();
+            // 只有预估的正是这个文件夹时，其中对单个文件的调整才算数
+            let overrides = estimate_weak
+                .upgrade()
+                .filter(|window| queued.is_empty() && window.get_folder().as_str() == folder)
+                .map(|window| estimate_overrides(&window))
+                .unwrap_or_default();
+            logging::set_verbose(debug);
+            crash::set_context(Path::new(&folder), &options);
+
+            let control = Arc::new(RunControl::default());
+            *current_run.borrow_mut() = Arc::clone(&control);
+
+            ui.set_busy(true);
+            ui.set_running(true);
+            ui.set_taskbar_state(1);
+            ui.set_status_text("正在扫描图像文件...".into());
+            ui.set_log_text("".into());
+            clear_results(&ui, &results);
+            ui.set_live_saved_commands("".into());
+            ui.set_live_rate_commands("".into());
+            ui.set_processed_files(0);
+            ui.set_total_files(0);
+            ui.set_progress(0.0);
+            ui.set_has_report(false);
+
+            let ui_weak_for_thread = ui_weak.clone();
+            let last_report = Arc::clone(&last_report);
+            let results = Arc::clone(&results);
+            thread::spawn(move || {
+                let folder_path = PathBuf::from(&folder);
+                let applied = options.clone();
+                let folders: Vec<PathBuf> = targets
+                    .iter()
+                    .filter(|target| target.files.is_none())
+                    .map(|target| target.folder.clone())
+                    .collect();
+                let mut report = RunReport::default();
+                let result = process_folder(
+                    ui_weak_for_thread.clone(),
+                    targets,
+                    options,
+                    overrides,
+                    &control,
+                    debug,
+                    ResultSinks {
+                        report: &mut report,
+                        table: &results,
+                    },
+                );
+                // 中途出错时也保留已处理文件的结果
+                if !report.is_empty()
+                    && let Ok(mut last_report) = last_report.lock()
+                {
+                    *last_report = report;
+                    let ui_weak = ui_weak_for_thread.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_has_report(true);
+                        }
+                    });
+                }
+                match result {
+                    Ok(summary) => {
+                        // 只拖入了其中几个文件的文件夹不记住设置
+                        if summary.processed() > 0 {
+                            for folder in &folders {
+                                if let Err(err) = folder_settings::remember(folder, &applied) {
+                                    log::warn!("记住文件夹设置失败: {err:#}");
+                                }
+                            }
+                        }
+                        // 保留两份时运行结束后直接打开复查窗口
+                        if applied.output.keep_both
+                            && applied.output.folder.is_none()
+                            && summary.succeeded > 0
+                        {
+                            let _ = slint::invoke_from_event_loop(move || {
+                                if let Some(ui) = ui_weak_for_thread.upgrade() {
+                                    ui.invoke_show_review();
+                                }
+                            });
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("处理文件夹失败 {}: {err:#}", folder_path.display());
+                        let message = format!("压缩失败: {err}");
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak_for_thread.upgrade() {
+                                let mut log = ui.get_log_text().to_string();
+                                if !log.is_empty() {
+                                    log.push('\n');
+                                }
+                                log.push_str(&message);
+                                ui.set_busy(false);
+                                ui.set_status_text(message.clone().into());
+                                ui.set_log_text(log.into());
+                            }
+                        });
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// 发布版是 windows 子系统程序，没有自己的控制台；命令行模式下接到启动它的终端上，
+/// 否则进度和错误都看不到。从资源管理器启动时没有父控制台，失败时照常运行
+#[cfg(windows)]
+fn attach_parent_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // SAFETY: 没有指针参数
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+/// 命名预设保存完整的设置，但不含输出文件夹：应用时保留当前选择的位置
+fn setup_named_presets(app: &AppWindow, settings: Rc<RefCell<AppSettings>>) {
+    let show_presets = |ui: &AppWindow, settings: &AppSettings| {
+        let names: Vec<SharedString> = settings
+            .presets
+            .iter()
+            .map(|preset| preset.name.as_str().into())
+            .collect();
+        ui.set_saved_presets(ModelRc::new(VecModel::from(names)));
+    };
+    show_presets(app, &settings.borrow());
+
+    app.on_apply_saved_preset({
+        let ui_weak = app.as_weak();
+        let settings = settings.clone();
+        move |index| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let settings = settings.borrow();
+            let Some(preset) = usize::try_from(index)
+                .ok()
+                .and_then(|index| settings.presets.get(index))
+            else {
+                return;
+            };
+            match preset.options() {
+                Ok(mut options) => {
+                    options.output.folder = options_from_ui(&ui).output.folder;
+                    apply_options_to_ui(&ui, &options);
+                    reset_preset(&ui);
+                    ui.set_preset_name(preset.name.as_str().into());
+                    ui.set_status_text(format!("已应用预设: {}", preset.name).into());
+                }
+                Err(err) => ui.set_status_text(format!("{err:#}").into()),
+            }
+        }
+    });
+
+    app.on_save_preset({
+        let ui_weak = app.as_weak();
+        let settings = settings.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let name = ui.get_preset_name().trim().to_string();
+            if name.is_empty() {
+                return;
+            }
+            let mut options = options_from_ui(&ui);
+            options.output.folder = None;
+            let mut settings = settings.borrow_mut();
+            let saved = settings
+                .save_preset(&name, &options)
+                .and_then(|()| settings.save());
+            match saved {
+                Ok(()) => {
+                    show_presets(&ui, &settings);
+                    let index = settings.presets.iter().position(|p| p.name == name);
+                    ui.set_saved_preset(index.map_or(-1, |index| index as i32));
+                    ui.set_status_text(format!("已保存预设: {name}").into());
+                }
+                Err(err) => ui.set_status_text(format!("保存预设失败: {err:#}").into()),
+            }
+        }
+    });
+
+    app.on_delete_preset({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut settings = settings.borrow_mut();
+            let Some(name) = usize::try_from(ui.get_saved_preset())
+                .ok()
+                .and_then(|index| settings.presets.get(index))
+                .map(|preset| preset.name.clone())
+            else {
+                return;
+            };
+            settings.remove_preset(&name);
+            match settings.save() {
+                Ok(()) => ui.set_status_text(format!("已删除预设: {name}").into()),
+                Err(err) => ui.set_status_text(format!("删除预设失败: {err:#}").into()),
+            }
+            show_presets(&ui, &settings);
+            ui.set_saved_preset(-1);
+        }
+    });
+}
+
+/// 拖到窗口上的文件和文件夹加入待处理列表，重复拖入的只保留一份
+fn setup_drop_targets(app: &AppWindow) {
+    let queued = Rc::new(VecModel::<StandardListViewItem>::default());
+    app.set_queued_paths(ModelRc::from(queued.clone()));
+
+    app.window().on_winit_window_event({
+        let ui_weak = app.as_weak();
+        let queued = queued.clone();
+        move |_, event| {
+            if let WindowEvent::DroppedFile(path) = event {
+                let text = SharedString::from(path.display().to_string());
+                if !queued.iter().any(|item| item.text == text) {
+                    queued.push(StandardListViewItem::from(text));
+                }
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(format!("已加入待处理列表: {}", path.display()).into());
+                }
+            }
+            WinitWindowEventResult::Propagate
+        }
+    });
+
+    app.on_remove_queued({
+        let ui_weak = app.as_weak();
+        let queued = queued.clone();
+        move || {
+            if let Some(ui) = ui_weak.upgrade()
+                && let Ok(index) = usize::try_from(ui.get_current_queued())
+                && index < queued.row_count()
+            {
+                queued.remove(index);
+                ui.set_current_queued(-1);
+            }
+        }
+    });
+
+    app.on_clear_queue({
+        let ui_weak = app.as_weak();
+        move || {
+            queued.set_vec(Vec::new());
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_current_queued(-1);
+            }
+        }
+    });
+}
+
+/// 上次运行崩溃时提示用户打开报告目录，每份报告只提示一次
+fn offer_crash_report() {
+    let Some(report) = crash::pending_reports()
+        .ok()
+        .and_then(|reports| reports.into_iter().next())
+    else {
+        return;
+    };
+    let _ = crash::mark_seen(&report);
+    let open = rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("程序曾意外退出")
+        .set_description(format!(
+            "上次运行时程序崩溃，已生成崩溃报告（路径已脱敏）：\n{}\n\n是否打开报告所在文件夹？",
+            report.display()
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    if open == rfd::MessageDialogResult::Yes {
+        let _ = open::that(&report);
+    }
+}
+
+/// 按开始监视时的设置自动压缩所选文件夹中新增的图像，监视期间不能修改设置或手动运行
+fn setup_folder_watch(app: &AppWindow, results: SharedResults) {
+    let watcher: Rc<RefCell<Option<FolderWatcher>>> = Rc::default();
+    app.on_toggle_watch({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if let Some(watcher) = watcher.borrow_mut().take() {
+                watcher.stop();
+                ui.set_watching(false);
+                ui.set_busy(false);
+                ui.set_status_text("已停止监视，正在处理的文件会完整写完".into());
+                return;
+            }
+
+            let folder = PathBuf::from(ui.get_selected_folder().as_str());
+            let options = options_from_ui(&ui);
+            let (mut processed, mut saved) = (0, 0);
+            let ui_weak = ui_weak.clone();
+            let shared_results = Arc::clone(&results);
+            let started = FolderWatcher::start(&folder, &options, move |event| {
+                let (entry, line, status) = match event {
+                    WatchEvent::FileFinished { path, outcome } => (
+                        Some(ReportEntry::new(&path, &outcome, UI_LANGUAGE)),
+                        None,
+                        None,
+                    ),
+                    WatchEvent::BatchFinished(summary) => {
+                        processed += summary.processed();
+                        saved += summary.total_saved;
+                        let status = format!(
+                            "正在监视: 已自动处理 {processed} 个图像，累计节省 {:.2} MB",
+                            bytes_to_mb(saved.max(0) as u64)
+                        );
+                        (None, Some(summary.status_text(UI_LANGUAGE)), Some(status))
+                    }
+                    WatchEvent::Error(message) => (None, Some(message), None),
+                };
+                let results = Arc::clone(&shared_results);
+                let ui_weak = ui_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        if let Some(entry) = entry {
+                            push_result(&ui, &results, entry);
+                        }
+                        if let Some(line) = line {
+                            let mut log = ui.get_log_text().to_string();
+                            log.push_str(&line);
+                            log.push('\n');
+                            ui.set_log_text(log.into());
+                        }
+                        if let Some(status) = status {
+                            ui.set_status_text(status.into());
+                        }
+                    }
+                });
+            });
+            match started {
+                Ok(started) => {
+                    *watcher.borrow_mut() = Some(started);
+                    ui.set_watching(true);
+                    ui.set_busy(true);
+                    ui.set_log_text("".into());
+                    clear_results(&ui, &results);
+                    ui.set_status_text(
+                        format!("正在监视: {}，新增的图像会自动压缩", folder.display()).into(),
+                    );
+                }
+                Err(err) => ui.set_status_text(format!("{err:#}").into()),
+            }
+        }
+    });
+}
+
+/// 开启后在后台检查新版本，有更新时显示横幅；失败只写日志，不打扰用户
+fn setup_update_check(app: &AppWindow, settings: Rc<RefCell<AppSettings>>) {
+    app.set_check_updates(settings.borrow().check_updates);
+
+    app.on_check_updates_changed({
+        let ui_weak = app.as_weak();
+        let settings = settings.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut settings = settings.borrow_mut();
+            settings.check_updates = ui.get_check_updates();
+            if let Err(err) = settings.save() {
+                ui.set_status_text(format!("保存设置失败: {err:#}").into());
+            }
+        }
+    });
+
+    app.on_open_update({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if let Err(err) = open::that(ui.get_update_url().as_str()) {
+                ui.set_status_text(format!("无法打开浏览器: {err}").into());
+            }
+        }
+    });
+
+    app.on_skip_update({
+        let ui_weak = app.as_weak();
+        let settings = settings.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut settings = settings.borrow_mut();
+            settings.skipped_version = Some(ui.get_update_version().to_string());
+            let _ = settings.save();
+            ui.set_update_version("".into());
+        }
+    });
+
+    let settings = settings.borrow();
+    if !settings.check_updates {
+        return;
+    }
+    let skipped = settings.skipped_version.clone();
+    let ui_weak = app.as_weak();
+    thread::spawn(move || {
+        let update = match update::check_for_update() {
+            Ok(Some(update)) if skipped.as_deref() != Some(update.version.as_str()) => update,
+            Ok(_) => return,
+            Err(err) => {
+                log::info!("检查更新失败: {err:#}");
+                return;
+            }
+        };
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_update_url(update.url.into());
+                ui.set_update_version(update.version.into());
+            }
+        });
+    });
+}
+
+#[derive(Default)]
+struct RestoreState {
+    runs: Vec<BackupRun>,
+    selected_run: Option<usize>,
+    // 当前列表中显示的条目在所选运行 entries 中的下标
+    visible_files: Vec<usize>,
+}
+
+fn setup_restore_window(window: &RestoreWindow) {
+    let state = Rc::new(RefCell::new(RestoreState::default()));
+
+    window.on_refresh({
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let mut state = state.borrow_mut();
+            match backup::list_runs() {
+                Ok(runs) => state.runs = runs,
+                Err(err) => {
+                    window.set_status_text(format!("读取备份失败: {err:#}").into());
+                    return;
+                }
+            }
+            let total: u64 = state.runs.iter().map(BackupRun::total_bytes).sum();
+            window.set_status_text(
+                format!(
+                    "共 {} 次运行的备份，占用 {:.2} MB",
+                    state.runs.len(),
+                    bytes_to_mb(total)
+                )
+                .into(),
+            );
+            let items: Vec<StandardListViewItem> = state
+                .runs
+                .iter()
+                .map(|run| {
+                    StandardListViewItem::from(SharedString::from(format!(
+                        "{} | {} | {} 个文件，{:.2} MB",
+                        app_data::format_datetime(run.created_at),
+                        run.folder,
+                        run.entries.len(),
+                        bytes_to_mb(run.total_bytes())
+                    )))
+                })
+                .collect();
+            window.set_runs(ModelRc::new(VecModel::from(items)));
+            window.set_current_run(-1);
+            state.selected_run = None;
+            show_backup_files(&window, &mut state);
+        }
+    });
+
+    window.on_run_selected({
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        move |index| {
+            if let Some(window) = window_weak.upgrade() {
+                let mut state = state.borrow_mut();
+                state.selected_run = usize::try_from(index).ok();
+                show_backup_files(&window, &mut state);
+            }
+        }
+    });
+
+    window.on_search_edited({
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        move || {
+            if let Some(window) = window_weak.upgrade() {
+                show_backup_files(&window, &mut state.borrow_mut());
+            }
+        }
+    });
+
+    window.on_restore_file({
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let state = state.borrow();
+            let entry = state.selected_run.and_then(|run| {
+                let index = *state
+                    .visible_files
+                    .get(usize::try_from(window.get_current_file()).ok()?)?;
+                state.runs.get(run)?.entries.get(index)
+            });
+            let Some(entry) = entry else {
+                return;
+            };
+            let status = match backup::restore_entry(entry) {
+                Ok(()) => format!("已恢复: {}", entry.original.display()),
+                Err(err) => format!("恢复失败: {err:#}"),
+            };
+            window.set_status_text(status.into());
+        }
+    });
+
+    window.on_restore_run({
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let state = state.borrow();
+            let Some(run) = state.selected_run.and_then(|run| state.runs.get(run)) else {
+                return;
+            };
+            let confirmed = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title("恢复整次运行")
+                .set_description(format!(
+                    "将用备份覆盖 {} 中的 {} 个文件，确定继续吗？",
+                    run.folder,
+                    run.entries.len()
+                ))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                == rfd::MessageDialogResult::Yes;
+            if !confirmed {
+                return;
+            }
+
+            let run = run.clone();
+            let window_weak = window_weak.clone();
+            window.set_status_text("正在恢复...".into());
+            thread::spawn(move || {
+                let (restored, errors) = backup::restore_run(&run);
+                for err in &errors {
+                    log::warn!("{err}");
+                }
+                let status = if errors.is_empty() {
+                    format!("已恢复 {restored} 个文件")
+                } else {
+                    format!(
+                        "已恢复 {restored} 个文件，{} 个失败（详见诊断日志）",
+                        errors.len()
+                    )
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(window) = window_weak.upgrade() {
+                        window.set_status_text(status.into());
+                    }
+                });
+            });
+        }
+    });
+
+    let cleanup = |window_weak: slint::Weak<RestoreWindow>, by_age: bool| {
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let result = if by_age {
+                backup::cleanup(Some(window.get_max_age_days().max(1) as u32), None)
+            } else {
+                let limit = window.get_max_total_mb().max(0) as u64 * 1024 * 1024;
+                backup::cleanup(None, Some(limit))
+            };
+            window.invoke_refresh();
+            let status = match result {
+                Ok((removed, freed)) => format!(
+                    "已删除 {removed} 次运行的备份，释放 {:.2} MB",
+                    bytes_to_mb(freed)
+                ),
+                Err(err) => format!("清理失败: {err:#}"),
+            };
+            window.set_status_text(status.into());
+        }
+    };
+    window.on_cleanup_by_age(cleanup(window.as_weak(), true));
+    window.on_cleanup_by_size(cleanup(window.as_weak(), false));
+}
+
+const ESTIMATE_INCLUDE: &str = "处理";
+const ESTIMATE_SKIP: &str = "跳过";
+// 各列的下标
+const ESTIMATE_PREDICTED_COLUMN: usize = 2;
+const ESTIMATE_SAVING_COLUMN: usize = 3;
+const ESTIMATE_CHOICE_COLUMN: usize = 4;
+const ESTIMATE_FORMAT_COLUMN: usize = 5;
+const ESTIMATE_FORMAT_DEFAULT: &str = "默认";
+const ESTIMATE_FORMAT_KEEP: &str = "保持原格式";
+
+/// 输出格式下拉框的选项：跟随全局设置、保持原格式，然后是 OutputFormat::ALL
+fn format_choices() -> Vec<&'static str> {
+    [ESTIMATE_FORMAT_DEFAULT, ESTIMATE_FORMAT_KEEP]
+        .into_iter()
+        .chain(OutputFormat::ALL.iter().map(|format| format.label()))
+        .collect()
+}
+
+/// 下拉框第 index 项对应的单独设置，跟随全局设置时为 None
+fn format_override(index: usize) -> Option<Option<OutputFormat>> {
+    match index {
+        0 => None,
+        1 => Some(None),
+        _ => OutputFormat::ALL.get(index - 2).copied().map(Some),
+    }
+}
+
+fn setup_estimate_window(window: &EstimateWindow, options: Rc<RefCell<CompressionOptions>>) {
+    let choices: Vec<SharedString> = format_choices().into_iter().map(Into::into).collect();
+    window.set_format_choices(ModelRc::new(VecModel::from(choices)));
+
+    let set_choice = |window: &EstimateWindow, index: usize, choice: Option<&str>| {
+        let Some(row) = window.get_rows().row_data(index) else {
+            return;
+        };
+        let choice = choice.unwrap_or(match row.row_data(ESTIMATE_CHOICE_COLUMN) {
+            Some(item) if item.text == ESTIMATE_SKIP => ESTIMATE_INCLUDE,
+            _ => ESTIMATE_SKIP,
+        });
+        row.set_row_data(
+            ESTIMATE_CHOICE_COLUMN,
+            StandardListViewItem::from(SharedString::from(choice)),
+        );
+    };
+
+    window.on_toggle_selected({
+        let window_weak = window.as_weak();
+        move || {
+            if let Some(window) = window_weak.upgrade()
+                && let Ok(index) = usize::try_from(window.get_current_row())
+            {
+                set_choice(&window, index, None);
+            }
+        }
+    });
+
+    window.on_include_all({
+        let window_weak = window.as_weak();
+        move || {
+            if let Some(window) = window_weak.upgrade() {
+                for index in 0..window.get_rows().row_count() {
+                    set_choice(&window, index, Some(ESTIMATE_INCLUDE));
+                }
+            }
+        }
+    });
+
+    window.on_row_selected({
+        let window_weak = window.as_weak();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let current = usize::try_from(window.get_current_row())
+                .ok()
+                .and_then(|index| window.get_rows().row_data(index))
+                .and_then(|row| row.row_data(ESTIMATE_FORMAT_COLUMN))
+                .and_then(|item| {
+                    format_choices()
+                        .iter()
+                        .position(|&choice| item.text == choice)
+                });
+            window.set_row_format(current.unwrap_or(0) as i32);
+        }
+    });
+
+    // 改了输出格式的文件按新格式重新预估
+    window.on_set_format({
+        let window_weak = window.as_weak();
+        move |choice| {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let (Ok(index), Ok(choice)) = (
+                usize::try_from(window.get_current_row()),
+                usize::try_from(choice),
+            ) else {
+                return;
+            };
+            let (Some(row), Some(path)) = (
+                window.get_rows().row_data(index),
+                window.get_paths().row_data(index),
+            ) else {
+                return;
+            };
+            row.set_row_data(
+                ESTIMATE_FORMAT_COLUMN,
+                StandardListViewItem::from(SharedString::from(format_choices()[choice])),
+            );
+            row.set_row_data(
+                ESTIMATE_PREDICTED_COLUMN,
+                StandardListViewItem::from(SharedString::from("正在预估...")),
+            );
+            row.set_row_data(ESTIMATE_SAVING_COLUMN, StandardListViewItem::default());
+
+            let path = PathBuf::from(path.as_str());
+            let mut overrides = FileOverrides::default();
+            if let Some(format) = format_override(choice) {
+                overrides.formats.insert(path.clone(), format);
+            }
+            let options = overrides.options_for(&path, &options.borrow()).into_owned();
+            let window_weak = window_weak.clone();
+            let exact = window.get_exact();
+            thread::spawn(move || {
+                let estimate = if exact {
+                    estimate::preview_file(&path, &options).0
+                } else {
+                    estimate::estimate_file(&path, &options)
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(window) = window_weak.upgrade() else {
+                        return;
+                    };
+                    // 预估期间列表可能已被重新生成
+                    if window.get_paths().row_data(index).as_deref()
+                        != Some(path.display().to_string().as_str())
+                    {
+                        return;
+                    }
+                    if let Some(row) = window.get_rows().row_data(index) {
+                        let (predicted, saving) = estimate_cells(&estimate);
+                        row.set_row_data(
+                            ESTIMATE_PREDICTED_COLUMN,
+                            StandardListViewItem::from(SharedString::from(predicted)),
+                        );
+                        row.set_row_data(
+                            ESTIMATE_SAVING_COLUMN,
+                            StandardListViewItem::from(SharedString::from(saving)),
+                        );
+                    }
+                });
+            });
+        }
+    });
+}
+
+fn estimate_cells(estimate: &SizeEstimate) -> (String, String) {
+    match &estimate.predicted {
+        Ok(size) => (
+            format!("{:.2} KB", bytes_to_kb(*size)),
+            format!("{:.1}%", savings_percent(estimate.original_size, *size)),
+        ),
+        Err(_) => ("无法预估".to_string(), String::new()),
+    }
+}
+
+/// exact 为 true 时是预览：完整编码每个文件，结果同时写到主窗口的日志中
+fn start_estimate(
+    ui: &AppWindow,
+    window: &EstimateWindow,
+    estimate_options: &RefCell<CompressionOptions>,
+    exact: bool,
+) {
+    let _ = window.show();
+    if window.get_running() {
+        return;
+    }
+    let folder = ui.get_selected_folder();
+    let options = options_from_ui(ui);
+    *estimate_options.borrow_mut() = options.clone();
+    window.set_exact(exact);
+    window.set_folder(folder.clone());
+    window.set_rows(ModelRc::default());
+    window.set_paths(ModelRc::default());
+    window.set_running(true);
+    window.set_status_text("正在扫描图像文件...".into());
+    if exact {
+        ui.set_log_text("".into());
+        ui.set_status_text("正在预览（不会修改任何文件）...".into());
+    }
+
+    let window_weak = window.as_weak();
+    let ui_weak = exact.then(|| ui.as_weak());
+    let folder = PathBuf::from(folder.as_str());
+    thread::spawn(move || run_estimate(window_weak, ui_weak, folder, options));
+}
+
+fn run_estimate(
+    window_weak: slint::Weak<EstimateWindow>,
+    preview_ui: Option<slint::Weak<AppWindow>>,
+    folder: PathBuf,
+    options: CompressionOptions,
+) {
+    let files = scan::scan_folder(&folder, &options).files;
+    let total = files.len();
+    let mut estimates = Vec::with_capacity(total);
+    let mut log = String::new();
+    for (index, path) in files.iter().enumerate() {
+        if preview_ui.is_some() {
+            let (estimate, outcome) = estimate::preview_file(path, &options);
+            log.push_str(&format!("[预览] {}\n", outcome.log_line(path, UI_LANGUAGE)));
+            estimates.push(estimate);
+        } else {
+            estimates.push(estimate::estimate_file(path, &options));
+        }
+        let verb = if preview_ui.is_some() {
+            "正在预览"
+        } else {
+            "正在预估"
+        };
+        let status = format!("{verb}: {}/{total}", index + 1);
+        let window_weak = window_weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(window) = window_weak.upgrade() {
+                window.set_status_text(status.into());
+            }
+        });
+    }
+    estimates.sort_by_key(|estimate| std::cmp::Reverse(estimate.predicted_saving()));
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak.upgrade() {
+            apply_estimates(&window, &folder, &estimates);
+            if let Some(ui) = preview_ui.and_then(|ui| ui.upgrade()) {
+                let status = window.get_status_text();
+                log.push_str(&format!("预览完成，未写入任何文件。{status}\n"));
+                ui.set_log_text(log.into());
+                ui.set_status_text(status);
+            }
+        }
+    });
+}
+
+fn apply_estimates(window: &EstimateWindow, folder: &Path, estimates: &[SizeEstimate]) {
+    let cell = |text: String| StandardListViewItem::from(SharedString::from(text));
+    let rows: Vec<ModelRc<StandardListViewItem>> = estimates
+        .iter()
+        .map(|estimate| {
+            let relative = estimate.path.strip_prefix(folder).unwrap_or(&estimate.path);
+            let (predicted, saving) = estimate_cells(estimate);
+            // 预计不会变小的文件默认跳过
+            let choice = if estimate.predicted_saving() > 0 {
+                ESTIMATE_INCLUDE
+            } else {
+                ESTIMATE_SKIP
+            };
+            ModelRc::new(VecModel::from(vec![
+                cell(relative.display().to_string()),
+                cell(format!("{:.2} KB", bytes_to_kb(estimate.original_size))),
+                cell(predicted),
+                cell(saving),
+                cell(choice.to_string()),
+                cell(ESTIMATE_FORMAT_DEFAULT.to_string()),
+            ]))
+        })
+        .collect();
+    let paths: Vec<SharedString> = estimates
+        .iter()
+        .map(|estimate| estimate.path.display().to_string().into())
+        .collect();
+
+    let before: u64 = estimates
+        .iter()
+        .map(|estimate| estimate.original_size)
+        .sum();
+    let saved: u64 = estimates.iter().map(SizeEstimate::predicted_saving).sum();
+    let no_gain = estimates
+        .iter()
+        .filter(|estimate| estimate.predicted_saving() == 0)
+        .count();
+    let expected = if window.get_exact() { "" } else { "预计" };
+    window.set_status_text(
+        format!(
+            "共 {} 个文件，{:.2} MB，{expected}处理后 {:.2} MB（节省 {:.1}%），其中 {no_gain} 个{expected}不会变小",
+            estimates.len(),
+            bytes_to_mb(before),
+            bytes_to_mb(before - saved),
+            savings_percent(before, before - saved)
+        )
+        .into(),
+    );
+    window.set_rows(ModelRc::new(VecModel::from(rows)));
+    window.set_paths(ModelRc::new(VecModel::from(paths)));
+    window.set_current_row(-1);
+    window.set_running(false);
+}
+
+/// 预估结果中选择本次跳过或单独指定了输出格式的文件
+fn estimate_overrides(window: &EstimateWindow) -> FileOverrides {
+    let choices = format_choices();
+    let mut overrides = FileOverrides::default();
+    for (row, path) in window.get_rows().iter().zip(window.get_paths().iter()) {
+        let path = PathBuf::from(path.as_str());
+        let cell = |column| {
+            row.row_data(column)
+                .map(|item| item.text)
+                .unwrap_or_default()
+        };
+        let format = cell(ESTIMATE_FORMAT_COLUMN);
+        if let Some(format) = choices
+            .iter()
+            .position(|&choice| format == choice)
+            .and_then(format_override)
+        {
+            overrides.formats.insert(path.clone(), format);
+        }
+        if cell(ESTIMATE_CHOICE_COLUMN) == ESTIMATE_SKIP {
+            overrides.excluded.insert(path);
+        }
+    }
+    overrides
+}
+
+const REVIEW_PENDING: &str = "待定";
+const REVIEW_ACCEPT: &str = "接受";
+const REVIEW_REJECT: &str = "拒绝";
+// “决定”列的下标
+const REVIEW_DECISION_COLUMN: usize = 4;
+// 预览截取图像中央的边长
+const REVIEW_PREVIEW_SIZE: u32 = 320;
+
+fn setup_review_window(window: &ReviewWindow) {
+    let set_decision = |window: &ReviewWindow, index: usize, decision: &str| {
+        if let Some(row) = window.get_rows().row_data(index) {
+            row.set_row_data(
+                REVIEW_DECISION_COLUMN,
+                StandardListViewItem::from(SharedString::from(decision)),
+            );
+        }
+    };
+    let decision_text = |accept: bool| if accept { REVIEW_ACCEPT } else { REVIEW_REJECT };
+
+    window.on_decide({
+        let window_weak = window.as_weak();
+        move |accept| {
+            if let Some(window) = window_weak.upgrade()
+                && let Ok(index) = usize::try_from(window.get_current_row())
+            {
+                set_decision(&window, index, decision_text(accept));
+            }
+        }
+    });
+
+    window.on_decide_all({
+        let window_weak = window.as_weak();
+        move |accept| {
+            if let Some(window) = window_weak.upgrade() {
+                for index in 0..window.get_rows().row_count() {
+                    set_decision(&window, index, decision_text(accept));
+                }
+            }
+        }
+    });
+
+    window.on_row_selected({
+        let window_weak = window.as_weak();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            window.set_original_preview(slint::Image::default());
+            window.set_compressed_preview(slint::Image::default());
+            let Ok(index) = usize::try_from(window.get_current_row()) else {
+                window.set_preview_text("".into());
+                return;
+            };
+            let (Some(original), Some(copy)) = (
+                window.get_originals().row_data(index),
+                window.get_copies().row_data(index),
+            ) else {
+                return;
+            };
+            window.set_preview_text("正在加载预览...".into());
+            let window_weak = window_weak.clone();
+            thread::spawn(move || {
+                let load = |path: &str| {
+                    review::preview(Path::new(path), REVIEW_PREVIEW_SIZE).map(|image| {
+                        slint::SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
+                            image.as_raw(),
+                            image.width(),
+                            image.height(),
+                        )
+                    })
+                };
+                let previews = load(&original).and_then(|o| Ok((o, load(&copy)?)));
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(window) = window_weak.upgrade() else {
+                        return;
+                    };
+                    // 加载期间可能已选中了其他行
+                    if window.get_current_row() != index as i32 {
+                        return;
+                    }
+                    match previews {
+                        Ok((original, compressed)) => {
+                            window.set_preview_text(
+                                format!(
+                                    "图像中央 {}×{} 像素的区域，按原始像素显示",
+                                    original.width(),
+                                    original.height()
+                                )
+                                .into(),
+                            );
+                            window.set_original_preview(slint::Image::from_rgba8(original));
+                            window.set_compressed_preview(slint::Image::from_rgba8(compressed));
+                        }
+                        Err(err) => {
+                            window.set_preview_text(format!("无法加载预览: {err:#}").into())
+                        }
+                    }
+                });
+            });
+        }
+    });
+
+    window.on_apply({
+        let window_weak = window.as_weak();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let folder = PathBuf::from(window.get_folder().as_str());
+            let mut backup = None;
+            let (mut accepted, mut rejected) = (0, 0);
+            let mut errors = Vec::new();
+            let rows = window.get_rows();
+            let originals = window.get_originals();
+            let copies = window.get_copies();
+            for ((row, original), copy) in rows.iter().zip(originals.iter()).zip(copies.iter()) {
+                let item = ReviewItem {
+                    original: PathBuf::from(original.as_str()),
+                    copy: PathBuf::from(copy.as_str()),
+                };
+                let decision = row
+                    .row_data(REVIEW_DECISION_COLUMN)
+                    .map(|item| item.text)
+                    .unwrap_or_default();
+                let result = if decision == REVIEW_ACCEPT {
+                    // 接受时才覆盖原文件，备份在第一次覆盖前开始
+                    if window.get_backup_enabled() && backup.is_none() {
+                        let location = Some(PathBuf::from(window.get_backup_folder().as_str()))
+                            .filter(|location| !location.as_os_str().is_empty());
+                        match BackupSession::start(
+                            &folder,
+                            app_data::unix_now(),
+                            location.as_deref(),
+                        ) {
+                            Ok(session) => backup = Some(session),
+                            Err(err) => {
+                                errors.push(format!("{err:#}"));
+                                break;
+                            }
+                        }
+                    }
+                    review::accept(&item, backup.as_mut()).map(|_| accepted += 1)
+                } else if decision == REVIEW_REJECT {
+                    review::reject(&item).map(|()| rejected += 1)
+                } else {
+                    Ok(())
+                };
+                if let Err(err) = result {
+                    errors.push(format!("{err:#}"));
+                }
+            }
+            if let Some(backup) = backup {
+                backup.finish();
+            }
+
+            let mut status = format!("已用 {accepted} 个副本替换原文件，删除 {rejected} 个副本");
+            for err in &errors {
+                status.push_str(&format!("\n{err}"));
+            }
+            // 只保留副本还在的行：仍待定的，以及处理失败的
+            let remaining: Vec<usize> = copies
+                .iter()
+                .enumerate()
+                .filter(|(_, copy)| Path::new(copy.as_str()).exists())
+                .map(|(index, _)| index)
+                .collect();
+            let keep = |model: ModelRc<SharedString>| -> Vec<SharedString> {
+                remaining
+                    .iter()
+                    .filter_map(|&index| model.row_data(index))
+                    .collect()
+            };
+            let remaining_originals = keep(originals);
+            let remaining_copies = keep(copies);
+            let remaining_rows: Vec<ModelRc<StandardListViewItem>> = remaining
+                .iter()
+                .filter_map(|&index| rows.row_data(index))
+                .collect();
+            window.set_rows(ModelRc::new(VecModel::from(remaining_rows)));
+            window.set_originals(ModelRc::new(VecModel::from(remaining_originals)));
+            window.set_copies(ModelRc::new(VecModel::from(remaining_copies)));
+            window.set_current_row(-1);
+            window.set_status_text(status.into());
+        }
+    });
+}
+
+fn apply_review_items(window: &ReviewWindow, folder: &Path, items: &[ReviewItem]) {
+    let cell = |text: String| StandardListViewItem::from(SharedString::from(text));
+    let (mut before, mut after) = (0, 0);
+    let rows: Vec<ModelRc<StandardListViewItem>> = items
+        .iter()
+        .map(|item| {
+            let (original_size, copy_size) = item.sizes();
+            before += original_size;
+            after += copy_size;
+            let relative = item.original.strip_prefix(folder).unwrap_or(&item.original);
+            ModelRc::new(VecModel::from(vec![
+                cell(relative.display().to_string()),
+                cell(format!("{:.2} KB", bytes_to_kb(original_size))),
+                cell(format!("{:.2} KB", bytes_to_kb(copy_size))),
+                cell(format!("{:.1}%", savings_percent(original_size, copy_size))),
+                cell(REVIEW_PENDING.to_string()),
+            ]))
+        })
+        .collect();
+    let path_model = |paths: Vec<SharedString>| ModelRc::new(VecModel::from(paths));
+    window.set_originals(path_model(
+        items
+            .iter()
+            .map(|item| item.original.display().to_string().into())
+            .collect(),
+    ));
+    window.set_copies(path_model(
+        items
+            .iter()
+            .map(|item| item.copy.display().to_string().into())
+            .collect(),
+    ));
+    window.set_rows(ModelRc::new(VecModel::from(rows)));
+    window.set_current_row(-1);
+    window.set_status_text(if items.is_empty() {
+        "没有待确认的压缩副本".into()
+    } else {
+        format!(
+            "共 {} 个待确认的副本，全部接受后 {:.2} MB → {:.2} MB（节省 {:.1}%）。未决定的副本保留到下次复查",
+            items.len(),
+            bytes_to_mb(before),
+            bytes_to_mb(after),
+            savings_percent(before, after)
+        )
+        .into()
+    });
+    window.set_working(false);
+}
+
+struct QualityRequest {
+    path: PathBuf,
+    options: CompressionOptions,
+    zoom: u32,
+}
+
+fn setup_quality_window(window: &QualityWindow, app: &AppWindow) {
+    // 当前图像会编码成的格式，滑块调整的是它的质量
+    let target: Rc<Cell<Option<ImageFormat>>> = Rc::default();
+    let selected_path = |window: &QualityWindow| {
+        let index = usize::try_from(window.get_current_file()).ok()?;
+        window
+            .get_paths()
+            .row_data(index)
+            .map(|path| PathBuf::from(path.as_str()))
+    };
+
+    // 拖动滑块时请求来得比编码快，每次只处理最新的一个
+    let (sender, receiver) = mpsc::channel::<QualityRequest>();
+    let window_weak = window.as_weak();
+    thread::spawn(move || {
+        while let Ok(mut request) = receiver.recv() {
+            while let Ok(newer) = receiver.try_recv() {
+                request = newer;
+            }
+            let result =
+                tuning::compare(&request.path, &request.options, request.zoom).map(|comparison| {
+                    let buffer = |image: &image::RgbaImage| {
+                        slint::SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
+                            image.as_raw(),
+                            image.width(),
+                            image.height(),
+                        )
+                    };
+                    let region = if request.zoom > 1 {
+                        format!("图像中央 1/{} 的区域。", request.zoom)
+                    } else {
+                        String::new()
+                    };
+                    let text = format!(
+                        "{region}{:.1} KB → {:.1} KB（节省 {:.1}%），{}",
+                        bytes_to_kb(comparison.original_size),
+                        bytes_to_kb(comparison.new_size),
+                        savings_percent(comparison.original_size, comparison.new_size),
+                        request.options.describe_format(comparison.format)
+                    );
+                    (
+                        buffer(&comparison.original),
+                        buffer(&comparison.compressed),
+                        text,
+                    )
+                });
+            let window_weak = window_weak.clone();
+            let path = request.path;
+            let _ = slint::invoke_from_event_loop(move || {
+                let Some(window) = window_weak.upgrade() else {
+                    return;
+                };
+                // 编码期间可能已选中了其他图像
+                if selected_path(&window).as_ref() != Some(&path) {
+                    return;
+                }
+                match result {
+                    Ok((original, compressed, text)) => {
+                        window.set_original_preview(slint::Image::from_rgba8(original));
+                        window.set_compressed_preview(slint::Image::from_rgba8(compressed));
+                        window.set_size_text(text.into());
+                    }
+                    Err(err) => window.set_size_text(format!("无法生成对比: {err:#}").into()),
+                }
+            });
+        }
+    });
+
+    window.on_file_selected({
+        let window_weak = window.as_weak();
+        let ui_weak = app.as_weak();
+        let target = Rc::clone(&target);
+        move || {
+            let (Some(window), Some(ui)) = (window_weak.upgrade(), ui_weak.upgrade()) else {
+                return;
+            };
+            window.set_original_preview(slint::Image::default());
+            window.set_compressed_preview(slint::Image::default());
+            let Some(path) = selected_path(&window) else {
+                window.set_size_text("".into());
+                return;
+            };
+            // 滑块从主窗口中对应格式的质量开始
+            let options = options_from_ui(&ui);
+            let quality = match tuning::target_format(&path, &options) {
+                Ok((source, format)) => {
+                    target.set(Some(format));
+                    options.for_source(source).quality(format)
+                }
+                Err(_) => {
+                    target.set(None);
+                    None
+                }
+            };
+            window.set_has_quality(quality.is_some());
+            if let Some(quality) = quality {
+                window.set_quality(quality as f32);
+            }
+            window.set_size_text("正在压缩...".into());
+            window.invoke_render();
+        }
+    });
+
+    window.on_render({
+        let window_weak = window.as_weak();
+        let ui_weak = app.as_weak();
+        let target = Rc::clone(&target);
+        move || {
+            let (Some(window), Some(ui)) = (window_weak.upgrade(), ui_weak.upgrade()) else {
+                return;
+            };
+            let Some(path) = selected_path(&window) else {
+                return;
+            };
+            let mut options = options_from_ui(&ui);
+            if window.get_has_quality()
+                && let Some(format) = target.get()
+            {
+                options.set_quality(format, slider_value(window.get_quality(), 1, 100));
+            }
+            let zoom = 1 << window.get_zoom_index().clamp(0, 3);
+            let _ = sender.send(QualityRequest {
+                path,
+                options,
+                zoom,
+            });
+        }
+    });
+
+    window.on_apply_quality({
+        let window_weak = window.as_weak();
+        let ui_weak = app.as_weak();
+        move || {
+            let (Some(window), Some(ui), Some(format)) =
+                (window_weak.upgrade(), ui_weak.upgrade(), target.get())
+            else {
+                return;
+            };
+            let quality = slider_value(window.get_quality(), 1, 100);
+            let mut options = options_from_ui(&ui);
+            options.set_quality(format, quality);
+            apply_options_to_ui(&ui, &options);
+            reset_preset(&ui);
+            ui.set_status_text(
+                format!("已应用质量对比的设置: {}", options.describe_format(format)).into(),
+            );
+        }
+    });
+}
+
+fn apply_quality_files(window: &QualityWindow, folder: &Path, files: &[PathBuf]) {
+    let names: Vec<StandardListViewItem> = files
+        .iter()
+        .map(|path| {
+            let name = path
+                .strip_prefix(folder)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            StandardListViewItem::from(SharedString::from(name))
+        })
+        .collect();
+    let paths: Vec<SharedString> = files
+        .iter()
+        .map(|path| path.display().to_string().into())
+        .collect();
+    window.set_files(ModelRc::new(VecModel::from(names)));
+    window.set_paths(ModelRc::new(VecModel::from(paths)));
+    window.set_status_text(if files.is_empty() {
+        "未找到可压缩的图像".into()
+    } else {
+        format!("共 {} 个图像，选择一个查看压缩效果", files.len()).into()
+    });
+}
+
+fn add_to_ignore_list(paths: &[PathBuf]) -> Result<()> {
+    let mut store = FailureStore::load()?;
+    for path in paths {
+        store.ignore(path, "手动设为不再处理");
+    }
+    store.save()
+}
+
+fn setup_ignore_window(window: &IgnoreWindow) {
+    // 列表中各行对应的忽略列表键
+    let keys = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    window.on_refresh({
+        let window_weak = window.as_weak();
+        let keys = keys.clone();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let store = match FailureStore::load() {
+                Ok(store) => store,
+                Err(err) => {
+                    window.set_status_text(format!("读取忽略列表失败: {err:#}").into());
+                    return;
+                }
+            };
+            let mut keys = keys.borrow_mut();
+            keys.clear();
+            let items: Vec<StandardListViewItem> = store
+                .ignored()
+                .map(|(key, file)| {
+                    keys.push(key.to_string());
+                    StandardListViewItem::from(SharedString::from(format!(
+                        "{key} | {} | {}",
+                        file.reason,
+                        app_data::format_datetime(file.added_at)
+                    )))
+                })
+                .collect();
+            window.set_status_text(
+                format!(
+                    "共 {} 项，列表中的文件以及文件夹下的所有图像都不会被处理",
+                    items.len()
+                )
+                .into(),
+            );
+            window.set_entries(ModelRc::new(VecModel::from(items)));
+            window.set_current_entry(-1);
+        }
+    });
+
+    let add = |window_weak: slint::Weak<IgnoreWindow>, folder: bool| {
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let dialog = rfd::FileDialog::new();
+            let picked = if folder {
+                dialog.pick_folder().into_iter().collect()
+            } else {
+                dialog.pick_files().unwrap_or_default()
+            };
+            if picked.is_empty() {
+                return;
+            }
+            let result = add_to_ignore_list(&picked);
+            window.invoke_refresh();
+            if let Err(err) = result {
+                window.set_status_text(format!("加入忽略列表失败: {err:#}").into());
+            }
+        }
+    };
+    window.on_add_files(add(window.as_weak(), false));
+    window.on_add_folder(add(window.as_weak(), true));
+
+    window.on_remove_entry({
+        let window_weak = window.as_weak();
+        let keys = keys.clone();
+        move || {
+            let Some(window) = window_weak.upgrade() else {
+                return;
+            };
+            let Some(key) = usize::try_from(window.get_current_entry())
+                .ok()
+                .and_then(|index| keys.borrow().get(index).cloned())
+            else {
+                return;
+            };
+            let result = FailureStore::load().and_then(|mut store| {
+                store.unignore(&key);
+                store.save()
+            });
+            window.invoke_refresh();
+            if let Err(err) = result {
+                window.set_status_text(format!("移出忽略列表失败: {err:#}").into());
+            }
+        }
+    });
+}
+
+fn show_backup_files(window: &RestoreWindow, state: &mut RestoreState) {
+    let search = window.get_search().to_lowercase();
+    let entries = state
+        .selected_run
+        .and_then(|run| state.runs.get(run))
+        .map(|run| run.entries.as_slice())
+        .unwrap_or_default();
+    state.visible_files = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            search.is_empty()
+                || entry
+                    .original
+                    .display()
+                    .to_string()
+                    .to_lowercase()
+                    .contains(&search)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    let items: Vec<StandardListViewItem> = state
+        .visible_files
+        .iter()
+        .map(|&index| {
+            let entry = &entries[index];
+            StandardListViewItem::from(SharedString::from(format!(
+                "{} ({:.2} KB)",
+                entry.original.display(),
+                bytes_to_kb(entry.size)
+            )))
+        })
+        .collect();
+    window.set_files(ModelRc::new(VecModel::from(items)));
+    window.set_current_file(-1);
+}
+
+fn apply_statistics(window: &StatsWindow, days: &[DailyStats]) {
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        window.set_summary_text("暂无运行记录，完成一次压缩后这里会显示统计".into());
+        window.set_date_range("".into());
+        window.set_saved_commands("".into());
+        window.set_files_commands("".into());
+        window.set_savings_commands("".into());
+        return;
+    };
+
+    let mut saved = Vec::with_capacity(days.len());
+    let mut files = Vec::with_capacity(days.len());
+    let mut savings = Vec::with_capacity(days.len());
+    let (mut total_saved, mut total_files, mut total_runs) = (0u64, 0usize, 0usize);
+    let (mut total_before, mut total_after) = (0u64, 0u64);
+    for day in days {
+        total_saved += day.saved_bytes();
+        total_files += day.files;
+        total_runs += day.runs;
+        total_before += day.bytes_before;
+        total_after += day.bytes_after;
+        saved.push(bytes_to_mb(total_saved));
+        files.push(total_files as f64);
+        savings.push(savings_percent(day.bytes_before, day.bytes_after).max(0.0));
+    }
+
+    window.set_summary_text(
+        format!(
+            "共 {total_runs} 次运行，处理 {total_files} 个文件，累计节省 {:.2} MB（平均 {:.2}%）",
+            bytes_to_mb(total_saved),
+            savings_percent(total_before, total_after)
+        )
+        .into(),
+    );
+    window.set_date_range(format!("{} 至 {}", first.date_label(), last.date_label()).into());
+
+    let (commands, max) = chart_commands(&saved);
+    window.set_saved_commands(commands.into());
+    window.set_saved_max(format!("{max:.2} MB").into());
+    let (commands, max) = chart_commands(&files);
+    window.set_files_commands(commands.into());
+    window.set_files_max(format!("{max:.0} 个").into());
+    let (commands, max) = chart_commands(&savings);
+    window.set_savings_commands(commands.into());
+    window.set_savings_max(format!("{max:.1}%").into());
+}
+
+/// 把数据点缩放到 100x100 的视图框内，返回折线的 Path 命令和最大值
+fn chart_commands(values: &[f64]) -> (String, f64) {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { max } else { 1.0 };
+    let y = |value: f64| 100.0 - value / scale * 100.0;
+    let commands = match values {
+        [] => String::new(),
+        // 只有一天的数据时画一条水平线
+        [value] => format!("M 0 {0:.2} L 100 {0:.2}", y(*value)),
+        _ => {
+            let step = 100.0 / (values.len() - 1) as f64;
+            values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let command = if index == 0 { "M" } else { "L" };
+                    format!("{command} {:.2} {:.2}", index as f64 * step, y(*value))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    };
+    (commands, max)
+}
+
+// 实时图表按时间均匀取这么多段
+const LIVE_CHART_SEGMENTS: usize = 30;
+
+/// 本次运行中每个文件完成时的累计数据，用于实时图表
+struct RunTimeline {
+    started: Instant,
+    // (开始后的秒数, 累计节省的字节数, 累计处理的原始字节数)
+    samples: Vec<(f64, u64, u64)>,
+}
+
+impl RunTimeline {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, original_size: u64, new_size: u64) {
+        let (_, saved, processed) = self.samples.last().copied().unwrap_or_default();
+        self.samples.push((
+            self.started.elapsed().as_secs_f64(),
+            saved + original_size.saturating_sub(new_size),
+            processed + original_size,
+        ));
+    }
+
+    /// 按时间均匀取点，返回累计节省（MB）和各段内每分钟处理的数据量（MB）
+    fn series(&self) -> (Vec<f64>, Vec<f64>) {
+        let step = self.started.elapsed().as_secs_f64().max(0.001) / LIVE_CHART_SEGMENTS as f64;
+        let at = |time: f64| {
+            let count = self.samples.partition_point(|&(t, _, _)| t <= time);
+            count
+                .checked_sub(1)
+                .map_or((0, 0), |last| (self.samples[last].1, self.samples[last].2))
+        };
+        let mut saved = Vec::with_capacity(LIVE_CHART_SEGMENTS + 1);
+        let mut rate = Vec::with_capacity(LIVE_CHART_SEGMENTS);
+        let mut previous = 0;
+        for index in 0..=LIVE_CHART_SEGMENTS {
+            let (saved_bytes, processed) = at(step * index as f64);
+            saved.push(bytes_to_mb(saved_bytes));
+            if index > 0 {
+                rate.push(bytes_to_mb(processed - previous) / step * 60.0);
+            }
+            previous = processed;
+        }
+        (saved, rate)
+    }
+}
+
+// 在工作线程中调用：对话框交给事件循环线程显示，再把结果传回来
+fn confirm_overwrite(
+    ui_weak: &slint::Weak<AppWindow>,
+    info: &PreRunInfo,
+    options: &CompressionOptions,
+) -> bool {
+    let backup_note = if options.output.folder.is_some() {
+        ""
+    } else if options.backup.enabled {
+        "原文件会先备份，可在“恢复备份”中找回。"
+    } else {
+        "未开启备份，此操作无法撤销。"
+    };
+    let output_note;
+    let action = if let Some(folder) = &options.output.folder {
+        output_note = format!("结果写入 {}，原文件保持不变。", folder.display());
+        output_note.as_str()
+    } else if !options.convert.enabled {
+        "压缩结果将直接覆盖原文件，"
+    } else if options.convert.remove_original {
+        "转换后将删除原文件，"
+    } else {
+        "转换结果写入新文件，原文件保留；已是目标格式的文件将直接覆盖，"
+    };
+    let description = format!(
+        "{}\n设置: {}\n\n{action}{backup_note}确定开始吗？",
+        batch::pre_run_summary(
+            info.total,
+            info.total_bytes,
+            info.estimated_secs,
+            UI_LANGUAGE,
+        ),
+        options.describe()
+    );
+    let (sender, receiver) = mpsc::channel();
+    let ui_weak = ui_weak.clone();
+    let shown = slint::invoke_from_event_loop(move || {
+        let ui = ui_weak.upgrade();
+        // 对话框显示期间事件循环不运行，直接更新任务栏而不等 changed 回调
+        if let Some(ui) = &ui {
+            ui.set_taskbar_state(4);
+            ui.invoke_taskbar_changed();
+        }
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title("确认覆盖原文件")
+            .set_description(description)
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show()
+            == rfd::MessageDialogResult::Yes;
+        if let Some(ui) = &ui {
+            ui.set_taskbar_state(2);
+        }
+        let _ = sender.send(confirmed);
+    });
+    shown.is_ok() && receiver.recv().unwrap_or(false)
 }
 
-fn bytes_to_mb(bytes: u64) -> f64 {
-    bytes as f64 / (1024.0 * 1024.0)
+fn confirm_lossier_settings(changes: &[String]) -> bool {
+    let description = format!(
+        "当前设置比上次处理该文件夹时更有损：\n{}\n\n再次有损压缩会叠加画质损失，确定继续吗？",
+        changes.join("\n")
+    );
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("设置比上次更有损")
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        == rfd::MessageDialogResult::Yes
 }
 
-fn savings_percent(before: u64, after: u64) -> f64 {
-    if before == 0 {
-        0.0
+fn options_from_ui(ui: &AppWindow) -> CompressionOptions {
+    let mut options = CompressionOptions::default();
+    options.jpeg.enabled = ui.get_jpeg_enabled();
+    options.jpeg.quality = slider_value(ui.get_jpeg_quality(), 1, 100);
+    options.jpeg.keep_low_quality_sources = ui.get_jpeg_keep_low_quality();
+    options.jpeg.encoder = match ui.get_jpeg_encoder() {
+        1 => JpegEncoderKind::Baseline,
+        _ => JpegEncoderKind::Mozjpeg,
+    };
+    options.jpeg.progressive = ui.get_jpeg_progressive();
+    options.jpeg.subsampling = match ui.get_jpeg_subsampling() {
+        1 => ChromaSubsampling::S444,
+        _ => ChromaSubsampling::S420,
+    };
+    options.jpeg.trellis = ui.get_jpeg_trellis();
+    options.png.enabled = ui.get_png_enabled();
+    options.png.effort = slider_value(ui.get_png_effort(), 1, 6);
+    options.png.lossy_level = slider_value(ui.get_png_lossy_level(), 0, 100);
+    options.webp.enabled = ui.get_webp_enabled();
+    options.webp.quality = slider_value(ui.get_webp_quality(), 1, 100);
+    options.webp.lossless_png_sources = ui.get_webp_lossless_png();
+    options.target_size.max_kb = ui.get_target_size_kb().max(0) as u32;
+    options.target_size.allow_downscale = ui.get_target_size_downscale();
+    options.resize.max_width = ui.get_resize_max_width().max(0) as u32;
+    options.resize.max_height = ui.get_resize_max_height().max(0) as u32;
+    options.resize.filter = match ui.get_resize_filter() {
+        1 => ResizeFilter::Triangle,
+        _ => ResizeFilter::Lanczos3,
+    };
+    options.resize.gpu = ui.get_resize_gpu();
+    options.avif.enabled = ui.get_avif_enabled();
+    options.avif.quality = slider_value(ui.get_avif_quality(), 1, 100);
+    options.animation.gif_enabled = ui.get_gif_enabled();
+    options.animation.to_webp = ui.get_animation_to_webp();
+    options.denoise.strength = slider_value(ui.get_denoise_strength(), 0, 100);
+    options.auto_tune.enabled = ui.get_auto_tune();
+    options.scan.incremental = ui.get_incremental();
+    options.scan.skip_processed = !ui.get_force_recompress();
+    options.scan.excluded_dirs = split_list(&ui.get_excluded_dirs());
+    options.scan.include_patterns = split_list(&ui.get_include_patterns());
+    options.scan.exclude_patterns = split_list(&ui.get_exclude_patterns());
+    options.scan.min_size_kb = ui.get_min_size_kb().max(0) as u32;
+    options.scan.max_depth = ui.get_max_depth().max(0) as u32;
+    options.scan.follow_symlinks = ui.get_follow_symlinks();
+    options.scan.respect_gitignore = ui.get_respect_gitignore();
+    options.scan.download_cloud_files = ui.get_download_cloud_files();
+    options.backup.enabled = ui.get_backup_enabled();
+    let backup_folder = ui.get_backup_folder();
+    let backup_folder = backup_folder.trim();
+    options.backup.folder = (!backup_folder.is_empty()).then(|| PathBuf::from(backup_folder));
+    options.failures.action = match ui.get_failure_action() {
+        1 => FailureAction::MoveToReview,
+        2 => FailureAction::Ignore,
+        _ => FailureAction::Report,
+    };
+    options.failures.threshold = ui.get_failure_threshold().max(1) as u32;
+    options.retry.max_retries = ui.get_max_retries().max(0) as u32;
+    options.failures.abort_percent = ui.get_abort_percent().clamp(0, 100) as u8;
+    options.probe.min_saving_percent = ui.get_probe_min_saving().clamp(0, 100) as u8;
+    options.parallel.workers = ui.get_workers().max(0) as u32;
+    let output_folder = ui.get_output_folder();
+    options.output.folder =
+        (!output_folder.is_empty()).then(|| PathBuf::from(output_folder.as_str()));
+    options.output.rename_template = ui.get_rename_template().trim().to_string();
+    options.output.keep_both = ui.get_keep_both();
+    options.output.force_rewrite = ui.get_force_rewrite();
+    options.output.preserve_modified_time = ui.get_preserve_modified_time();
+    options.metadata.mode = if ui.get_strip_metadata() {
+        MetadataMode::StripAll
+    } else if ui.get_strip_gps() {
+        MetadataMode::StripGps
     } else {
-        100.0 * (before as f64 - after as f64) / before as f64
+        MetadataMode::KeepAll
+    };
+    options.metadata.auto_orient = ui.get_auto_orient();
+    options.output.keep_both_suffix = ui.get_keep_both_suffix().trim().to_string();
+    options.convert.enabled = ui.get_convert_enabled();
+    options.convert.format = usize::try_from(ui.get_convert_format())
+        .ok()
+        .and_then(|index| OutputFormat::ALL.get(index).copied())
+        .unwrap_or_default();
+    options.convert.remove_original = ui.get_convert_remove_original();
+    options.convert.collision = match ui.get_convert_collision() {
+        1 => CollisionRule::Overwrite,
+        2 => CollisionRule::Rename,
+        _ => CollisionRule::Skip,
+    };
+    options.scan.min_age_minutes = ui.get_min_age_minutes().round().max(0.0) as u32;
+    let target_value = ui.get_target_value().max(1);
+    options.scan.target = match ui.get_target_mode() {
+        1 => TargetMode::LargestCount {
+            count: target_value as usize,
+        },
+        2 => TargetMode::LargestShare {
+            percent: target_value.min(100) as u8,
+        },
+        _ => TargetMode::All,
+    };
+    options
+}
+
+fn apply_options_to_ui(ui: &AppWindow, options: &CompressionOptions) {
+    ui.set_jpeg_enabled(options.jpeg.enabled);
+    ui.set_jpeg_quality(options.jpeg.quality as f32);
+    ui.set_jpeg_keep_low_quality(options.jpeg.keep_low_quality_sources);
+    ui.set_jpeg_encoder(match options.jpeg.encoder {
+        JpegEncoderKind::Mozjpeg => 0,
+        JpegEncoderKind::Baseline => 1,
+    });
+    ui.set_jpeg_progressive(options.jpeg.progressive);
+    ui.set_jpeg_subsampling(match options.jpeg.subsampling {
+        ChromaSubsampling::S420 => 0,
+        ChromaSubsampling::S444 => 1,
+    });
+    ui.set_jpeg_trellis(options.jpeg.trellis);
+    ui.set_png_enabled(options.png.enabled);
+    ui.set_png_effort(options.png.effort as f32);
+    ui.set_png_lossy_level(options.png.lossy_level as f32);
+    ui.set_webp_enabled(options.webp.enabled);
+    ui.set_webp_quality(options.webp.quality as f32);
+    ui.set_webp_lossless_png(options.webp.lossless_png_sources);
+    ui.set_target_size_kb(options.target_size.max_kb.min(100_000) as i32);
+    ui.set_target_size_downscale(options.target_size.allow_downscale);
+    ui.set_resize_max_width(options.resize.max_width.min(50_000) as i32);
+    ui.set_resize_max_height(options.resize.max_height.min(50_000) as i32);
+    ui.set_resize_filter(match options.resize.filter {
+        ResizeFilter::Lanczos3 => 0,
+        ResizeFilter::Triangle => 1,
+    });
+    ui.set_resize_gpu(options.resize.gpu);
+    ui.set_avif_enabled(options.avif.enabled);
+    ui.set_avif_quality(options.avif.quality as f32);
+    ui.set_gif_enabled(options.animation.gif_enabled);
+    ui.set_animation_to_webp(options.animation.to_webp);
+    ui.set_denoise_strength(options.denoise.strength as f32);
+    ui.set_auto_tune(options.auto_tune.enabled);
+    ui.set_incremental(options.scan.incremental);
+    ui.set_force_recompress(!options.scan.skip_processed);
+    ui.set_excluded_dirs(options.scan.excluded_dirs.join(", ").into());
+    ui.set_include_patterns(options.scan.include_patterns.join(", ").into());
+    ui.set_exclude_patterns(options.scan.exclude_patterns.join(", ").into());
+    ui.set_min_size_kb(options.scan.min_size_kb.min(102400) as i32);
+    ui.set_max_depth(options.scan.max_depth.min(100) as i32);
+    ui.set_follow_symlinks(options.scan.follow_symlinks);
+    ui.set_respect_gitignore(options.scan.respect_gitignore);
+    ui.set_download_cloud_files(options.scan.download_cloud_files);
+    ui.set_backup_enabled(options.backup.enabled);
+    ui.set_backup_folder(
+        options
+            .backup
+            .folder
+            .as_ref()
+            .map(|folder| folder.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.set_failure_action(match options.failures.action {
+        FailureAction::Report => 0,
+        FailureAction::MoveToReview => 1,
+        FailureAction::Ignore => 2,
+    });
+    ui.set_failure_threshold(options.failures.threshold.min(i32::MAX as u32) as i32);
+    ui.set_max_retries(options.retry.max_retries.min(i32::MAX as u32) as i32);
+    ui.set_abort_percent(options.failures.abort_percent.min(100) as i32);
+    ui.set_probe_min_saving(options.probe.min_saving_percent.min(100) as i32);
+    ui.set_workers(options.parallel.workers.min(64) as i32);
+    ui.set_output_folder(
+        options
+            .output
+            .folder
+            .as_ref()
+            .map(|folder| folder.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.set_rename_template(options.output.rename_template.clone().into());
+    ui.set_keep_both(options.output.keep_both);
+    ui.set_force_rewrite(options.output.force_rewrite);
+    ui.set_preserve_modified_time(options.output.preserve_modified_time);
+    ui.set_strip_metadata(options.metadata.mode == MetadataMode::StripAll);
+    ui.set_strip_gps(options.metadata.mode == MetadataMode::StripGps);
+    ui.set_auto_orient(options.metadata.auto_orient);
+    ui.set_keep_both_suffix(options.output.keep_both_suffix.clone().into());
+    ui.set_convert_enabled(options.convert.enabled);
+    ui.set_convert_format(
+        OutputFormat::ALL
+            .iter()
+            .position(|format| *format == options.convert.format)
+            .unwrap_or_default() as i32,
+    );
+    ui.set_convert_remove_original(options.convert.remove_original);
+    ui.set_convert_collision(match options.convert.collision {
+        CollisionRule::Skip => 0,
+        CollisionRule::Overwrite => 1,
+        CollisionRule::Rename => 2,
+    });
+    ui.set_min_age_minutes(options.scan.min_age_minutes as f32);
+    let (mode, value) = match options.scan.target {
+        TargetMode::All => (0, ui.get_target_value()),
+        TargetMode::LargestCount { count } => (1, count.min(i32::MAX as usize) as i32),
+        TargetMode::LargestShare { percent } => (2, percent as i32),
+    };
+    ui.set_target_mode(mode);
+    ui.set_target_value(value);
+}
+
+// 界面上的列表用逗号（或分号）分隔
+fn split_list(text: &str) -> Vec<String> {
+    text.split([',', '，', ';'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn reset_preset(ui: &AppWindow) {
+    ui.set_quality_preset(0);
+    ui.set_preset_description("".into());
+}
+
+fn slider_value(value: f32, min: u8, max: u8) -> u8 {
+    value.round().clamp(min as f32, max as f32) as u8
+}
+
+/// 依次处理 targets 中的文件夹，只在第一次覆盖前确认；处理多个时单个文件夹出错不影响其余的
+/// 结果表格的完整内容，表格中只显示按筛选和排序整理后的行
+#[derive(Default)]
+struct ResultTable {
+    entries: Vec<ReportEntry>,
+    /// 排序的列和是否升序，None 为完成的顺序
+    sort: Option<(i32, bool)>,
+}
+
+type SharedResults = Arc<Mutex<ResultTable>>;
+
+/// 逐个文件的结果写到哪里：report 在运行结束后供保存，table 在运行中实时显示
+struct ResultSinks<'a> {
+    report: &'a mut RunReport,
+    table: &'a SharedResults,
+}
+
+fn result_row(entry: &ReportEntry) -> ModelRc<StandardListViewItem> {
+    let status = match entry.status {
+        ReportStatus::Ok => "完成".to_string(),
+        ReportStatus::Skipped => format!("保持原样: {}", entry.detail),
+        ReportStatus::Error => format!("失败: {}", entry.detail),
+    };
+    let cells: Vec<StandardListViewItem> = [
+        entry.path.display().to_string(),
+        format!("{:.1} KB", bytes_to_kb(entry.original_size)),
+        format!("{:.1} KB", bytes_to_kb(entry.new_size)),
+        format!("{:.1}%", entry.saved_percent),
+        status,
+    ]
+    .into_iter()
+    .map(|text| StandardListViewItem::from(SharedString::from(text)))
+    .collect();
+    ModelRc::new(VecModel::from(cells))
+}
+
+fn compare_results(a: &ReportEntry, b: &ReportEntry, column: i32) -> std::cmp::Ordering {
+    match column {
+        1 => a.original_size.cmp(&b.original_size),
+        2 => a.new_size.cmp(&b.new_size),
+        3 => a.saved_percent.total_cmp(&b.saved_percent),
+        4 => (a.status as u8, &a.detail).cmp(&(b.status as u8, &b.detail)),
+        _ => a.path.cmp(&b.path),
+    }
+}
+
+/// 按当前的筛选和排序重建表格
+fn show_results(ui: &AppWindow, table: &ResultTable) {
+    let failed_only = ui.get_results_failed_only();
+    let mut entries: Vec<&ReportEntry> = table
+        .entries
+        .iter()
+        .filter(|entry| !failed_only || entry.status == ReportStatus::Error)
+        .collect();
+    if let Some((column, ascending)) = table.sort {
+        entries.sort_by(|a, b| {
+            let ordering = compare_results(a, b, column);
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+    let rows: Vec<_> = entries.into_iter().map(result_row).collect();
+    ui.set_results(ModelRc::new(VecModel::from(rows)));
+    ui.set_current_result(-1);
+}
+
+fn clear_results(ui: &AppWindow, results: &SharedResults) {
+    if let Ok(mut table) = results.lock() {
+        *table = ResultTable::default();
+    }
+    ui.set_results(ModelRc::new(
+        VecModel::<ModelRc<StandardListViewItem>>::default(),
+    ));
+    ui.set_current_result(-1);
+    ui.set_failed_results(0);
+}
+
+/// 新完成的文件按筛选条件加到表格末尾，运行中不重新排序
+fn push_result(ui: &AppWindow, results: &SharedResults, entry: ReportEntry) {
+    let failed = entry.status == ReportStatus::Error;
+    if failed {
+        ui.set_failed_results(ui.get_failed_results() + 1);
+    }
+    if (failed || !ui.get_results_failed_only())
+        && let Some(rows) = ui
+            .get_results()
+            .as_any()
+            .downcast_ref::<VecModel<ModelRc<StandardListViewItem>>>()
+    {
+        rows.push(result_row(&entry));
+    }
+    if let Ok(mut table) = results.lock() {
+        table.entries.push(entry);
     }
 }
 
-struct CompressionStats {
-    original_size: u64,
-    new_size: u64,
+/// 表格中选中的行对应的文件
+fn selected_result(ui: &AppWindow) -> Option<PathBuf> {
+    let row = ui
+        .get_results()
+        .row_data(usize::try_from(ui.get_current_result()).ok()?)?;
+    Some(PathBuf::from(row.row_data(0)?.text.as_str()))
+}
+
+fn setup_result_table(app: &AppWindow, results: SharedResults) {
+    app.on_sort_results({
+        let ui_weak = app.as_weak();
+        let results = Arc::clone(&results);
+        move |column, ascending| {
+            let (Some(ui), Ok(mut table)) = (ui_weak.upgrade(), results.lock()) else {
+                return;
+            };
+            table.sort = Some((column, ascending));
+            show_results(&ui, &table);
+        }
+    });
+
+    app.on_filter_results({
+        let ui_weak = app.as_weak();
+        let results = Arc::clone(&results);
+        move || {
+            if let (Some(ui), Ok(table)) = (ui_weak.upgrade(), results.lock()) {
+                show_results(&ui, &table);
+            }
+        }
+    });
+
+    app.on_open_result_folder({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(folder) =
+                selected_result(&ui).and_then(|path| path.parent().map(Path::to_path_buf))
+            else {
+                return;
+            };
+            if let Err(err) = open::that(&folder) {
+                ui.set_status_text(format!("无法打开文件夹 {}: {err}", folder.display()).into());
+            }
+        }
+    });
+
+    // 失败的文件放进待处理列表，按当前设置重新开始
+    app.on_retry_failed({
+        let ui_weak = app.as_weak();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let failed: Vec<PathBuf> = match results.lock() {
+                Ok(table) => table
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.status == ReportStatus::Error && entry.path.is_file())
+                    .map(|entry| entry.path.clone())
+                    .collect(),
+                Err(_) => return,
+            };
+            if failed.is_empty() {
+                ui.set_status_text("失败的文件都已不存在或被移走".into());
+                return;
+            }
+            let queued = ui.get_queued_paths();
+            let Some(queued) = queued
+                .as_any()
+                .downcast_ref::<VecModel<StandardListViewItem>>()
+            else {
+                return;
+            };
+            queued.set_vec(
+                failed
+                    .iter()
+                    .map(|path| {
+                        StandardListViewItem::from(SharedString::from(path.display().to_string()))
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            ui.invoke_start_compress();
+        }
+    });
+}
+
+fn process_folder(
+    ui_weak: slint::Weak<AppWindow>,
+    targets: Vec<BatchTarget>,
+    options: CompressionOptions,
+    overrides: FileOverrides,
+    control: &RunControl,
+    debug: bool,
+    sinks: ResultSinks,
+) -> Result<BatchSummary> {
+    let ResultSinks { report, table } = sinks;
+    let mut log_builder = String::new();
+    let mut declined = false;
+    let mut confirmed = false;
+    let mut skipped = SkippedFiles::default();
+    let timeline = RefCell::new(RunTimeline::new());
+    let mut summary = BatchSummary::default();
+    let several = targets.len() > 1;
+
+    for target in targets {
+        if declined || control.is_cancelled() || summary.aborted.is_some() {
+            break;
+        }
+        if several {
+            log_builder.push_str(&format!("处理: {}\n", target.folder.display()));
+        }
+        // 拖入的单个文件只需扫描所在文件夹这一层
+        let mut target_options = options.clone();
+        if target.files.is_some() {
+            target_options.scan.recursive = false;
+        }
+        let target_overrides = FileOverrides {
+            only: target.files,
+            ..overrides.clone()
+        };
+        let result = batch::run_batch_with(
+            &target.folder,
+            &target_options,
+            LockPolicy::Refuse,
+            control,
+            &target_overrides,
+            |info| {
+                if confirmed {
+                    return true;
+                }
+                confirmed = confirm_overwrite(&ui_weak, info, &target_options);
+                declined = !confirmed;
+                // 等待确认的时间不计入处理速度
+                *timeline.borrow_mut() = RunTimeline::new();
+                confirmed
+            },
+            |event| match event {
+                BatchEvent::WaitingForLock => {
+                    let ui_weak = ui_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_status_text("文件夹正被其他任务处理，等待中...".into());
+                        }
+                    });
+                }
+                BatchEvent::Scanning(progress) => {
+                    let status = format!(
+                        "正在扫描: 已访问 {} 个文件夹，找到 {} 个图像（{:.2} MB）",
+                        progress.dirs_visited,
+                        progress.files_found,
+                        bytes_to_mb(progress.total_bytes)
+                    );
+                    let ui_weak = ui_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_status_text(status.into());
+                        }
+                    });
+                }
+                BatchEvent::Scanned {
+                    total,
+                    total_bytes,
+                    estimated_secs,
+                    unchanged,
+                    not_included,
+                    too_recent,
+                    not_targeted,
+                    ignored,
+                    already_converted,
+                    cloud_only,
+                    errors,
+                } => {
+                    for err in &errors {
+                        log_builder.push_str(&format!("遍历时出错: {err:#}\n"));
+                    }
+                    if not_included > 0 {
+                        log_builder.push_str(&format!(
+                            "包含规则: 跳过 {not_included} 个不在指定路径下的文件\n"
+                        ));
+                    }
+                    if too_recent > 0 {
+                        log_builder.push_str(&format!(
+                        "跳过 {too_recent} 个最近刚修改的文件（可能仍在写入），下次运行再处理\n"
+                    ));
+                    }
+                    if not_targeted > 0 {
+                        log_builder.push_str(&format!(
+                            "处理范围: 只处理体积最大的文件，跳过其余 {not_targeted} 个\n"
+                        ));
+                    }
+                    if ignored > 0 {
+                        log_builder.push_str(&format!("跳过 {ignored} 个在忽略列表中的文件\n"));
+                    }
+                    if already_converted > 0 {
+                        log_builder.push_str(&format!(
+                            "格式转换: 跳过 {already_converted} 个已转换过的文件\n"
+                        ));
+                    }
+                    if cloud_only > 0 {
+                        log_builder.push_str(&if options.scan.download_cloud_files {
+                            format!("同步盘: {cloud_only} 个文件只在云端，处理时将自动下载\n")
+                        } else {
+                            format!("同步盘: 跳过 {cloud_only} 个只在云端、未下载到本地的文件\n")
+                        });
+                    }
+                    let deselected = skipped.count(SkipReason::Deselected);
+                    if deselected > 0 {
+                        log_builder.push_str(&format!("预估结果: 本次跳过 {deselected} 个文件\n"));
+                    }
+                    if unchanged > 0 {
+                        log_builder
+                            .push_str(&format!("增量模式: 跳过 {unchanged} 个未修改的文件\n"));
+                    }
+                    let processed = skipped.count(SkipReason::AlreadyProcessed);
+                    if processed > 0 {
+                        log_builder.push_str(&format!(
+                            "跳过 {processed} 个以前压缩过、之后未改动的文件（可勾选“强制再次压缩”）\n"
+                        ));
+                    }
+                    let status = if total > 0 {
+                        let summary =
+                            batch::pre_run_summary(total, total_bytes, estimated_secs, UI_LANGUAGE);
+                        log_builder.push_str(&format!("{summary}\n"));
+                        summary
+                    } else if unchanged > 0 {
+                        "自上次运行以来没有新增或修改的图像".to_string()
+                    } else if processed > 0 {
+                        "图像都已压缩过，之后没有改动".to_string()
+                    } else {
+                        "未找到可压缩的图像".to_string()
+                    };
+                    let log_snapshot = log_builder.clone();
+                    let ui_weak = ui_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_taskbar_state(if total > 0 { 2 } else { 0 });
+                            ui.set_total_files(total as i32);
+                            ui.set_processed_files(0);
+                            ui.set_progress(0.0);
+                            ui.set_log_text(log_snapshot.into());
+                            ui.set_status_text(status.into());
+                        }
+                    });
+                }
+                BatchEvent::Skipped { path, reason } => {
+                    report.record_skipped(&path, reason, UI_LANGUAGE);
+                    skipped.record(path, reason);
+                }
+                BatchEvent::FileFinished {
+                    processed,
+                    total,
+                    path,
+                    outcome,
+                } => {
+                    let entry = ReportEntry::new(&path, &outcome, UI_LANGUAGE);
+                    report.entries.push(entry.clone());
+                    // 每个文件的结果只进表格，调试信息在运行结束时随日志一起显示
+                    if debug && let Some(details) = outcome.details(UI_LANGUAGE) {
+                        log::debug!("{} | {details}", path.display());
+                        log_builder.push_str(&format!("{}\n    {details}\n", path.display()));
+                    }
+
+                    let progress = processed as f32 / total as f32;
+                    let failed = matches!(outcome, FileOutcome::Failed(_));
+                    let mut timeline = timeline.borrow_mut();
+                    if let FileOutcome::Compressed(stats) = &outcome {
+                        timeline.record(stats.original_size, stats.new_size);
+                    }
+                    let (saved, rate) = timeline.series();
+                    let (saved_commands, saved_max) = chart_commands(&saved);
+                    let (rate_commands, rate_max) = chart_commands(&rate);
+                    let status = if control.is_cancelled() {
+                        format!("正在取消：等待正在处理的文件完成 ({processed}/{total})")
+                    } else if control.is_paused() {
+                        format!(
+                            "已暂停 ({processed}/{total})：正在处理的文件完成后不再开始新的文件"
+                        )
+                    } else {
+                        format!("正在处理: {} ({}/{})", path.display(), processed, total)
+                    };
+                    let results = Arc::clone(table);
+                    let ui_weak = ui_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            push_result(&ui, &results, entry);
+                            // 出现失败后本次运行剩下的时间里一直显示红色
+                            if failed {
+                                ui.set_taskbar_state(3);
+                            }
+                            ui.set_processed_files(processed as i32);
+                            ui.set_progress(progress);
+                            ui.set_live_saved_commands(saved_commands.into());
+                            ui.set_live_saved_max(format!("{saved_max:.2} MB").into());
+                            ui.set_live_rate_commands(rate_commands.into());
+                            ui.set_live_rate_max(format!("{rate_max:.1} MB/分钟").into());
+                            ui.set_status_text(status.clone().into());
+                        }
+                    });
+                }
+            },
+        );
+        match result {
+            Ok(result) => summary.merge(result),
+            Err(err) if several => {
+                log_builder.push_str(&format!("处理失败: {}: {err:#}\n", target.folder.display()));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    log_builder.push_str(&skipped.to_text(UI_LANGUAGE));
+    if summary.total == 0 || declined {
+        let log_snapshot = log_builder.clone();
+        let ui_weak = ui_weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                if declined {
+                    ui.set_status_text("已取消：未确认覆盖原文件".into());
+                }
+                ui.set_log_text(log_snapshot.into());
+                ui.set_busy(false);
+            }
+        });
+        return Ok(summary);
+    }
+
+    let final_status = summary.status_text(UI_LANGUAGE);
+    let log_snapshot = log_builder.clone();
+    let ui_weak = ui_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_status_text(final_status.into());
+            ui.set_log_text(log_snapshot.into());
+            ui.set_progress(1.0);
+            ui.set_busy(false);
+        }
+    });
+
+    Ok(summary)
 }
@@ -0,0 +1,76 @@
+//! 任务清单（.job 文件）：把要处理的文件夹和全部设置（格式、过滤、输出方式等）
+//! 存成一个文件，下次在界面中打开或交给命令行 `--job` 直接运行。
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::options::CompressionOptions;
+
+pub const JOB_EXTENSION: &str = "job";
+
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobManifest {
+    pub version: u32,
+    /// 相对路径按清单文件所在目录解析，清单可以和图片一起移动
+    pub folders: Vec<PathBuf>,
+    pub options: CompressionOptions,
+}
+
+impl JobManifest {
+    pub fn new(folders: Vec<PathBuf>, options: CompressionOptions) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            folders,
+            options,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("无法读取任务清单: {}", path.display()))?;
+        Self::from_json(&text, path.parent().unwrap_or(Path::new("")))
+            .with_context(|| format!("无法解析任务清单: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self).context("无法序列化任务清单")?;
+        fs::write(path, text).with_context(|| format!("无法写入任务清单: {}", path.display()))
+    }
+
+    fn from_json(text: &str, base: &Path) -> Result<Self> {
+        let mut value: Value = serde_json::from_str(text).context("任务清单不是有效的 JSON")?;
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+        if version == 0 || version > MANIFEST_VERSION {
+            return Err(anyhow!(
+                "任务清单版本 {version} 不受支持（当前程序支持版本 {MANIFEST_VERSION}）"
+            ));
+        }
+        let folders: Vec<PathBuf> = serde_json::from_value(
+            value
+                .get_mut("folders")
+                .map(Value::take)
+                .unwrap_or_default(),
+        )
+        .context("folders 字段无效")?;
+        if folders.is_empty() {
+            return Err(anyhow!("任务清单中没有要处理的文件夹"));
+        }
+        // 设置部分走 CompressionOptions 的版本迁移，旧清单在新版本中仍可使用
+        let options = match value.get_mut("options").map(Value::take) {
+            Some(options) => CompressionOptions::from_value(options)?,
+            None => CompressionOptions::default(),
+        };
+        Ok(Self::new(
+            folders
+                .into_iter()
+                .map(|folder| base.join(folder))
+                .collect(),
+            options,
+        ))
+    }
+}
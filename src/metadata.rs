@@ -0,0 +1,282 @@
+//! 重新编码时保留源文件的 EXIF 和 ICC 色彩配置。只有 JPEG 和 PNG 能写入；
+//! EXIF 中的方向默认在解码后直接应用到像素上，写入输出的 EXIF 方向改为正常，
+//! 手机照片无论输出是否带 EXIF 都按拍摄时的朝向显示。
+
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::io::Cursor;
+
+use crate::options::{MetadataMode, MetadataOptions};
+
+/// GPS 子目录在 IFD0 中的指针标签
+const GPS_IFD_TAG: u16 = 0x8825;
+const ORIENTATION_TAG: u16 = 0x0112;
+const IFD_ENTRY_SIZE: usize = 12;
+
+#[derive(Clone, Debug, Default)]
+pub struct ImageMetadata {
+    pub icc: Option<Vec<u8>>,
+    /// TIFF 结构的 EXIF 数据，不含 JPEG APP1 段的 `Exif\0\0` 前缀
+    pub exif: Option<Vec<u8>>,
+}
+
+/// 读取源文件中的元数据，读不出来时当作没有
+pub fn read(bytes: &[u8]) -> ImageMetadata {
+    let decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok());
+    let Some(mut decoder) = decoder else {
+        return ImageMetadata::default();
+    };
+    ImageMetadata {
+        icc: decoder.icc_profile().ok().flatten(),
+        exif: decoder.exif_metadata().ok().flatten(),
+    }
+}
+
+/// 开启自动旋转时把源文件 EXIF 中的方向应用到 image 上，返回是否有改动。
+/// 在缩小之前调用，最大宽高才按显示的方向计算
+pub fn auto_orient(input: &[u8], image: &mut DynamicImage, options: &MetadataOptions) -> bool {
+    if !options.auto_orient {
+        return false;
+    }
+    let orientation = read(input)
+        .exif
+        .as_deref()
+        .and_then(Orientation::from_exif_chunk)
+        .unwrap_or(Orientation::NoTransforms);
+    if orientation == Orientation::NoTransforms {
+        return false;
+    }
+    image.apply_orientation(orientation);
+    true
+}
+
+/// 按设置决定写入 target 的元数据；像素已由 auto_orient 旋转过时把其中的方向改为正常
+pub fn prepare(input: &[u8], target: ImageFormat, options: &MetadataOptions) -> ImageMetadata {
+    let mut metadata = read(input);
+    if options.auto_orient
+        && let Some(exif) = &mut metadata.exif
+    {
+        reset_orientation(exif);
+    }
+
+    match options.mode {
+        MetadataMode::KeepAll => {}
+        MetadataMode::StripGps => {
+            if let Some(exif) = &mut metadata.exif
+                && strip_gps(exif)
+            {
+                log::debug!("已去除 GPS 信息");
+            }
+        }
+        MetadataMode::StripAll => metadata = ImageMetadata::default(),
+    }
+    if !matches!(target, ImageFormat::Jpeg | ImageFormat::Png) {
+        metadata = ImageMetadata::default();
+    }
+    metadata
+}
+
+/// 把 IFD0 中的方向标签改为 1（正常），没有该标签时不做改动
+fn reset_orientation(exif: &mut [u8]) {
+    let big_endian = match exif.get(..4) {
+        Some([0x49, 0x49, 42, 0]) => false,
+        Some([0x4d, 0x4d, 0, 42]) => true,
+        _ => return,
+    };
+    let Some(ifd0) = read_u32(exif, 4, big_endian).map(|offset| offset as usize) else {
+        return;
+    };
+    let Some(count) = read_u16(exif, ifd0, big_endian) else {
+        return;
+    };
+    let entry = (0..count as usize)
+        .map(|index| ifd0 + 2 + index * IFD_ENTRY_SIZE)
+        .find(|&entry| read_u16(exif, entry, big_endian) == Some(ORIENTATION_TAG));
+    // SHORT 类型的值直接放在条目的最后 4 字节中
+    if let Some(value) = entry.and_then(|entry| exif.get_mut(entry + 8..entry + 10)) {
+        value.copy_from_slice(&if big_endian {
+            1u16.to_be_bytes()
+        } else {
+            1u16.to_le_bytes()
+        });
+    }
+}
+
+/// 清空 EXIF 中的 GPS 子目录：条目和它们指向的数据都填零，条目数置 0，
+/// 其他标签的偏移保持不变。返回是否找到了 GPS 信息
+pub fn strip_gps(exif: &mut [u8]) -> bool {
+    let big_endian = match exif.get(..4) {
+        Some([0x49, 0x49, 42, 0]) => false,
+        Some([0x4d, 0x4d, 0, 42]) => true,
+        _ => return false,
+    };
+    let gps = read_u32(exif, 4, big_endian).and_then(|ifd0| {
+        let count = read_u16(exif, ifd0 as usize, big_endian)? as usize;
+        (0..count)
+            .map(|index| ifd0 as usize + 2 + index * IFD_ENTRY_SIZE)
+            .find(|&entry| read_u16(exif, entry, big_endian) == Some(GPS_IFD_TAG))
+            .and_then(|entry| read_u32(exif, entry + 8, big_endian))
+    });
+    let Some(gps) = gps.map(|offset| offset as usize) else {
+        return false;
+    };
+    let Some(count) = read_u16(exif, gps, big_endian) else {
+        return false;
+    };
+
+    for index in 0..count as usize {
+        let entry = gps + 2 + index * IFD_ENTRY_SIZE;
+        let (Some(kind), Some(values)) = (
+            read_u16(exif, entry + 2, big_endian),
+            read_u32(exif, entry + 4, big_endian),
+        ) else {
+            break;
+        };
+        // 超过 4 字节的值存放在别处，条目中只有偏移
+        let size = type_size(kind).saturating_mul(values as usize);
+        if size > 4
+            && let Some(offset) = read_u32(exif, entry + 8, big_endian)
+        {
+            zero(exif, offset as usize, size);
+        }
+        zero(exif, entry, IFD_ENTRY_SIZE);
+    }
+    zero(exif, gps, 2);
+    true
+}
+
+fn type_size(kind: u16) -> usize {
+    match kind {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    }
+}
+
+fn read_u16(data: &[u8], at: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(at..at.checked_add(2)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(data: &[u8], at: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn zero(data: &mut [u8], at: usize, len: usize) {
+    let end = at.saturating_add(len).min(data.len());
+    if let Some(range) = data.get_mut(at..end) {
+        range.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPS_IFD: usize = 56;
+    const MAKE_DATA: usize = 50;
+    const LATITUDE_DATA: usize = 86;
+
+    /// IFD0 含厂商、方向（6，顺时针 90 度）和 GPS 指针；GPS 子目录含纬度方向（放在条目内）
+    /// 和纬度（放在条目外）
+    fn sample_exif(big_endian: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        let u16_bytes = |value: u16| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let entry = |data: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: [u8; 4]| {
+            data.extend_from_slice(&u16_bytes(tag));
+            data.extend_from_slice(&u16_bytes(kind));
+            data.extend_from_slice(&u32_bytes(count));
+            data.extend_from_slice(&value);
+        };
+        data.extend_from_slice(if big_endian { b"MM\0*" } else { b"II*\0" });
+        data.extend_from_slice(&u32_bytes(8));
+
+        data.extend_from_slice(&u16_bytes(3));
+        entry(&mut data, 0x010F, 2, 6, u32_bytes(MAKE_DATA as u32));
+        let mut orientation = [0; 4];
+        orientation[..2].copy_from_slice(&u16_bytes(6));
+        entry(&mut data, ORIENTATION_TAG, 3, 1, orientation);
+        entry(&mut data, GPS_IFD_TAG, 4, 1, u32_bytes(GPS_IFD as u32));
+        data.extend_from_slice(&[0; 4]);
+        assert_eq!(data.len(), MAKE_DATA);
+        data.extend_from_slice(b"Phone\0");
+
+        assert_eq!(data.len(), GPS_IFD);
+        data.extend_from_slice(&u16_bytes(2));
+        entry(&mut data, 1, 2, 2, *b"N\0\0\0");
+        entry(&mut data, 2, 5, 3, u32_bytes(LATITUDE_DATA as u32));
+        data.extend_from_slice(&[0; 4]);
+        assert_eq!(data.len(), LATITUDE_DATA);
+        data.extend_from_slice(&[7; 24]);
+        data
+    }
+
+    #[test]
+    fn strip_gps_clears_only_the_gps_directory() {
+        for big_endian in [false, true] {
+            let original = sample_exif(big_endian);
+            let mut exif = original.clone();
+            assert!(strip_gps(&mut exif));
+            assert_eq!(exif.len(), original.len());
+            assert_eq!(read_u16(&exif, GPS_IFD, big_endian), Some(0));
+            assert!(exif[GPS_IFD..GPS_IFD + 2 + 2 * IFD_ENTRY_SIZE]
+                .iter()
+                .all(|&byte| byte == 0));
+            assert!(exif[LATITUDE_DATA..].iter().all(|&byte| byte == 0));
+            assert_eq!(exif[..GPS_IFD], original[..GPS_IFD]);
+        }
+    }
+
+    #[test]
+    fn strip_gps_without_gps_changes_nothing() {
+        let mut exif = sample_exif(false);
+        // GPS 指针换成另一个无关的标签
+        exif[8 + 2 + 2 * IFD_ENTRY_SIZE] = 0x01;
+        let original = exif.clone();
+        assert!(!strip_gps(&mut exif));
+        assert_eq!(exif, original);
+        assert!(!strip_gps(&mut b"not exif".to_vec()));
+    }
+
+    #[test]
+    fn reset_orientation_sets_normal() {
+        for big_endian in [false, true] {
+            let mut exif = sample_exif(big_endian);
+            assert_eq!(
+                Orientation::from_exif_chunk(&exif),
+                Some(Orientation::Rotate90)
+            );
+            reset_orientation(&mut exif);
+            assert_eq!(
+                Orientation::from_exif_chunk(&exif),
+                Some(Orientation::NoTransforms)
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! 任务结束通知：把结果以 JSON POST 到 webhook（兼容 Slack / Teams 的 `text` 字段），
+//! 或通过本机的 sendmail 兼容命令发送邮件。
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::bytes_to_mb;
+
+/// 通知在任务线程中同步发送，对方无响应时不能一直卡住
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default)]
+pub struct Notifier {
+    pub webhook_url: Option<String>,
+    pub email: Option<EmailTarget>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailTarget {
+    pub to: String,
+    /// 从标准输入读取完整邮件的命令，默认 `sendmail -t`
+    pub command: Vec<String>,
+}
+
+impl EmailTarget {
+    pub fn new(to: String) -> Self {
+        Self {
+            to,
+            command: vec!["sendmail".to_string(), "-t".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobReport {
+    pub job_id: u64,
+    pub folder: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub total_saved: i64,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+impl JobReport {
+    pub fn new(job_id: u64, folder: &Path) -> Self {
+        Self {
+            job_id,
+            folder: folder.display().to_string(),
+            succeeded: 0,
+            failed: 0,
+            total_saved: 0,
+            cancelled: false,
+            error: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let outcome = if let Some(error) = &self.error {
+            format!("失败: {error}")
+        } else if self.cancelled {
+            "已取消".to_string()
+        } else {
+            "完成".to_string()
+        };
+        format!(
+            "压缩任务 #{} {outcome}\n文件夹: {}\n成功 {} 个，失败 {} 个，节省 {:.2} MB",
+            self.job_id,
+            self.folder,
+            self.succeeded,
+            self.failed,
+            bytes_to_mb(self.total_saved.max(0) as u64)
+        )
+    }
+}
+
+impl Notifier {
+    pub fn is_empty(&self) -> bool {
+        self.webhook_url.is_none() && self.email.is_none()
+    }
+
+    /// 逐个发送，某个渠道失败不影响其他渠道，错误写入诊断日志
+    pub fn notify(&self, report: &JobReport) {
+        if let Some(url) = &self.webhook_url
+            && let Err(err) = post_webhook(url, report)
+        {
+            log::warn!("webhook 通知失败: {err:#}");
+        }
+        if let Some(email) = &self.email
+            && let Err(err) = send_email(email, report)
+        {
+            log::warn!("邮件通知失败: {err:#}");
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    text: String,
+    job: &'a JobReport,
+}
+
+fn post_webhook(url: &str, report: &JobReport) -> Result<()> {
+    let payload = WebhookPayload {
+        text: report.text(),
+        job: report,
+    };
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(WEBHOOK_TIMEOUT))
+        .build()
+        .into();
+    agent
+        .post(url)
+        .send_json(&payload)
+        .with_context(|| format!("无法发送到 {url}"))?;
+    Ok(())
+}
+
+fn send_email(target: &EmailTarget, report: &JobReport) -> Result<()> {
+    let (program, args) = target
+        .command
+        .split_first()
+        .ok_or_else(|| anyhow!("未配置发信命令"))?;
+    let subject = if report.error.is_some() {
+        format!("压缩任务 #{} 失败", report.job_id)
+    } else {
+        format!("压缩任务 #{} 已结束", report.job_id)
+    };
+    let message = format!(
+        "To: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}\r\n",
+        target.to,
+        encode_header(&subject),
+        report.text()
+    );
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("无法启动发信命令: {program}"))?;
+    // 写入失败也要等子进程退出，不留下僵尸进程；标准输入随 take 出的句柄关闭
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin
+            .write_all(message.as_bytes())
+            .context("无法写入发信命令"),
+        None => Err(anyhow!("无法写入发信命令")),
+    };
+    let status = child.wait()?;
+    written?;
+    if !status.success() {
+        return Err(anyhow!("发信命令退出码 {status}"));
+    }
+    Ok(())
+}
+
+/// 按 RFC 2047 把非 ASCII 的邮件头编码成 `=?UTF-8?B?...?=`，每段不超过 75 个字符，
+/// 多段之间折行
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    // 45 字节编码后是 60 个字符，加上前后缀 72 个；按字符切分，不拆开一个字的 UTF-8 字节
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < value.len() {
+        let mut end = (start + 45).min(value.len());
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        words.push(format!(
+            "=?UTF-8?B?{}?=",
+            base64(&value.as_bytes()[start..end])
+        ));
+        start = end;
+    }
+    words.join("\r\n ")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | ((byte as u32) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        for (input, expected) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(input.as_bytes()), expected);
+        }
+    }
+
+    #[test]
+    fn non_ascii_subject_is_encoded() {
+        assert_eq!(encode_header("Job #1 done"), "Job #1 done");
+        assert_eq!(
+            encode_header("压缩任务 #1 已结束"),
+            "=?UTF-8?B?5Y6L57yp5Lu75YqhICMxIOW3sue7k+adnw==?="
+        );
+        let long = encode_header(&"压缩".repeat(20));
+        for word in long.split("\r\n ") {
+            assert!(word.len() <= 75, "{word}");
+            assert!(word.starts_with("=?UTF-8?B?") && word.ends_with("?="));
+        }
+    }
+}
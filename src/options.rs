@@ -0,0 +1,926 @@
+use anyhow::Result;
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConfigError;
+
+/// 默认跳过的目录：版本控制、依赖和构建产物、各类缓存
+pub const DEFAULT_EXCLUDED_DIRS: &[&str] = &[
+    "node_modules",
+    ".git",
+    ".svn",
+    ".hg",
+    "target",
+    "__pycache__",
+    ".cache",
+    "cache",
+];
+
+/// 当前配置结构的版本号，字段有不兼容变化时递增并补充迁移逻辑
+pub const OPTIONS_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionOptions {
+    pub version: u32,
+    pub jpeg: JpegOptions,
+    pub png: PngOptions,
+    pub webp: WebpOptions,
+    pub avif: AvifOptions,
+    pub animation: AnimationOptions,
+    pub denoise: DenoiseOptions,
+    pub auto_tune: AutoTuneOptions,
+    pub scan: ScanOptions,
+    pub backup: BackupOptions,
+    pub failures: FailureOptions,
+    pub retry: RetryOptions,
+    pub probe: ProbeOptions,
+    pub target_size: TargetSizeOptions,
+    pub parallel: ParallelOptions,
+    pub resize: ResizeOptions,
+    pub metadata: MetadataOptions,
+    pub convert: ConvertOptions,
+    pub output: OutputOptions,
+    /// 任务来自服务接口时由服务设置：解码时限制尺寸和内存。不从配置读取
+    #[serde(skip)]
+    pub untrusted_input: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JpegOptions {
+    pub enabled: bool,
+    pub quality: u8,
+    /// 源文件估计质量不高于 quality 时保持原样，避免叠加压缩损失
+    pub keep_low_quality_sources: bool,
+    pub encoder: JpegEncoderKind,
+    /// 以下三项只对 mozjpeg 生效
+    pub progressive: bool,
+    pub subsampling: ChromaSubsampling,
+    /// 网格量化，同样质量下体积更小，编码更慢
+    pub trellis: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JpegEncoderKind {
+    /// 体积更小，速度较慢；浏览器中不可用，退回到 Baseline
+    #[default]
+    Mozjpeg,
+    /// image 自带的基线编码器
+    Baseline,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChromaSubsampling {
+    /// 色度宽高各减半，体积更小
+    #[default]
+    #[serde(rename = "4:2:0")]
+    S420,
+    /// 不减少色度，适合文字、线条边缘有鲜艳颜色的图
+    #[serde(rename = "4:4:4")]
+    S444,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PngOptions {
+    pub enabled: bool,
+    /// 1-6，数值越大压缩越慢、体积越小
+    pub effort: u8,
+    /// 0 为无损，1-100 为调色板量化的强度
+    pub lossy_level: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebpOptions {
+    pub enabled: bool,
+    pub quality: u8,
+    /// 无损编码，忽略 quality
+    pub lossless: bool,
+    /// PNG 转为 WebP 时使用无损编码，保留截图、图标中清晰的边缘
+    pub lossless_png_sources: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvifOptions {
+    pub enabled: bool,
+    pub quality: u8,
+}
+
+/// GIF 和 APNG 动图逐帧解码后重新编码，保留动画
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationOptions {
+    /// 处理 GIF 文件（包括静态的）；APNG 跟随 PNG 的开关
+    pub gif_enabled: bool,
+    /// 转换为动画 WebP；关闭时保持原格式
+    pub to_webp: bool,
+}
+
+/// 有损编码前的轻度降噪，高感光度照片降噪后体积明显更小
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DenoiseOptions {
+    /// 0 为关闭，1-100 越大越平滑
+    pub strength: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoTuneOptions {
+    /// 逐张判断照片 / 截图 / 线稿 / 扫描件，并使用对应类型的质量参数，
+    /// 界面上的质量设置只作为格式开关和其余选项的来源
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// 只处理新增的文件和处理完之后改动过的文件，按每个文件记下的大小和修改时间判断
+    pub incremental: bool,
+    /// 跳过以前压缩过、之后没有改动的文件；关闭即强制再次压缩
+    pub skip_processed: bool,
+    /// 遍历时跳过的目录名，不区分大小写
+    pub excluded_dirs: Vec<String>,
+    /// 关闭时只处理所选文件夹中的文件，不进入子文件夹
+    pub recursive: bool,
+    /// 非空时只处理相对路径匹配其中任一通配符的文件，如 `assets/**`
+    pub include_patterns: Vec<String>,
+    /// 相对路径匹配其中任一通配符的文件和文件夹不处理，如 `**/thumbs/**`
+    pub exclude_patterns: Vec<String>,
+    /// 小于这么多 KB 的文件（如图标）不处理；0 为不限制
+    pub min_size_kb: u32,
+    /// 最多进入几层子文件夹；0 为不限制，recursive 关闭时不进入子文件夹
+    pub max_depth: u32,
+    /// 进入符号链接指向的文件夹，并处理链接指向的文件
+    pub follow_symlinks: bool,
+    /// 遵循 `.gitignore`（仅在 git 仓库内）和 `.ignore` 中的规则
+    pub respect_gitignore: bool,
+    /// 最近这么多分钟内修改过的文件可能仍在写入，本次先跳过；0 为不检查
+    pub min_age_minutes: u32,
+    /// 同步盘中只在云端的文件也处理，读取时由系统自动下载；关闭时跳过
+    pub download_cloud_files: bool,
+    pub target: TargetMode,
+}
+
+/// 只处理体积最大的一部分文件，用少量时间拿到大部分收益
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum TargetMode {
+    #[default]
+    All,
+    /// 最大的 count 个文件
+    LargestCount { count: usize },
+    /// 从大到小累加，直到覆盖总体积的 percent%
+    LargestShare { percent: u8 },
+}
+
+/// 完整编码前先用几小块样图试编码，预计收益太小的文件保持原样，
+/// 在大多已经优化过的图库上省下大部分编码时间
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProbeOptions {
+    /// 预计节省低于这个百分比时跳过；0 为不探测
+    pub min_saving_percent: u8,
+}
+
+/// 每个文件的体积上限，用于有大小限制的上传。超过时从设置的质量往下搜索，
+/// 已经小于上限的文件保持原样
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetSizeOptions {
+    /// 0 为不限制
+    pub max_kb: u32,
+    /// 最低质量仍超过上限时逐步缩小尺寸
+    pub allow_downscale: bool,
+}
+
+impl TargetSizeOptions {
+    pub fn max_bytes(&self) -> Option<u64> {
+        (self.max_kb > 0).then(|| self.max_kb as u64 * 1024)
+    }
+}
+
+/// 同时压缩的文件数。每个线程各自持有一张解码后的图像，超大图片较多时可适当调低
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParallelOptions {
+    /// 0 为按 CPU 核数
+    pub workers: u32,
+}
+
+impl ParallelOptions {
+    pub fn worker_count(&self) -> usize {
+        match self.workers {
+            0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+            workers => workers as usize,
+        }
+    }
+}
+
+/// 超过最大宽高的图像先等比缩小再编码，0 为该方向不限制；宽高设为同一个值即限制长边
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResizeOptions {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub filter: ResizeFilter,
+    /// 以 gpu feature 构建且有可用的 GPU 时在 GPU 上缩小大图
+    pub gpu: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+    /// 边缘清晰，速度较慢
+    #[default]
+    Lanczos3,
+    /// 略柔和，速度快
+    Triangle,
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 0,
+            max_height: 0,
+            filter: ResizeFilter::default(),
+            gpu: true,
+        }
+    }
+}
+
+impl ResizeOptions {
+    /// 需要缩小时返回缩小后的尺寸
+    pub fn fit(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let limit = |max: u32, size: u32| match max {
+            0 => f64::INFINITY,
+            max => max as f64 / size.max(1) as f64,
+        };
+        let ratio = limit(self.max_width, width).min(limit(self.max_height, height));
+        if ratio >= 1.0 {
+            return None;
+        }
+        let scaled = |size: u32| ((size as f64 * ratio).round() as u32).max(1);
+        Some((scaled(width), scaled(height)))
+    }
+}
+
+/// 重新编码时如何处理源文件的 EXIF 和 ICC 色彩配置，只有 JPEG 和 PNG 输出能写入
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataOptions {
+    pub mode: MetadataMode,
+    /// 按 EXIF 中的方向旋转、翻转像素，写入的 EXIF 方向改为正常；
+    /// 关闭时像素保持原样，不写入 EXIF 的输出会按存储的方向显示
+    pub auto_orient: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataMode {
+    #[default]
+    KeepAll,
+    /// 保留拍摄时间、相机等信息，只去除位置
+    StripGps,
+    /// 全部去除
+    StripAll,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    /// 覆盖前把原文件复制到数据目录，可在“恢复备份”中找回
+    pub enabled: bool,
+    /// 为 None 时备份到数据目录；相对路径相对于源文件夹，如 `.compress_img_backup`
+    pub folder: Option<PathBuf>,
+}
+
+impl BackupOptions {
+    /// 处理 source 时实际使用的自定义备份位置
+    pub fn location(&self, source: &Path) -> Option<PathBuf> {
+        self.folder.as_ref().map(|folder| source.join(folder))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureOptions {
+    pub action: FailureAction,
+    /// 连续失败达到这么多次后执行 action
+    pub threshold: u32,
+    /// 本次运行失败的文件超过这个百分比时中止，大量失败通常是选错文件夹、
+    /// 没有权限或磁盘已满，继续下去只会造成更多损坏；0 为不检查
+    pub abort_percent: u8,
+}
+
+/// 读写时遇到网络抖动、杀毒软件占用等暂时性错误时的重试
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryOptions {
+    /// 首次失败后最多再试的次数，0 为不重试
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub initial_backoff_ms: u64,
+}
+
+/// 对反复失败的文件的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureAction {
+    /// 留在原处，只在结果中报告
+    #[default]
+    Report,
+    /// 移到源文件夹下的复审文件夹
+    MoveToReview,
+    /// 加入忽略列表，以后不再尝试
+    Ignore,
+}
+
+/// 把所有图像统一转换为一种格式，已是该格式的文件照常原地压缩
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConvertOptions {
+    pub enabled: bool,
+    pub format: OutputFormat,
+    /// 转换成功后删除原文件；开启备份时仍可找回
+    pub remove_original: bool,
+    /// 转换后的文件名已被占用时的处理方式
+    pub collision: CollisionRule,
+}
+
+/// 把结果写到另一个文件夹，原文件保持不动
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    /// 为 None 时原地覆盖
+    pub folder: Option<PathBuf>,
+    /// 为空时保持原来的相对路径；否则按模板重命名，如 `{date}_{counter:04}_{width}x{height}`，
+    /// 扩展名自动补上
+    pub rename_template: String,
+    /// 未设置 folder 时把结果写成原文件旁边带后缀的副本，逐个确认后再替换原文件
+    pub keep_both: bool,
+    /// 副本文件名（不含扩展名）的后缀，如 photo.jpg 的副本为 photo_compressed.jpg
+    pub keep_both_suffix: String,
+    /// 压缩结果不比原文件小时也照常写入；关闭时保持原样
+    pub force_rewrite: bool,
+    /// 写入后把修改时间恢复为原文件的；关闭时为写入的时间
+    pub preserve_modified_time: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    #[default]
+    Webp,
+    Avif,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionRule {
+    /// 视为已转换过，跳过该文件
+    #[default]
+    Skip,
+    /// 覆盖已有的同名文件
+    Overwrite,
+    /// 在文件名后追加 -1、-2 ...
+    Rename,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Jpeg,
+        OutputFormat::Png,
+        OutputFormat::Webp,
+        OutputFormat::Avif,
+    ];
+
+    /// 命令行中的格式名，如 jpeg、jpg、webp，不区分大小写
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| name == format.extension() || name == format.label().to_lowercase())
+    }
+
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Avif => ImageFormat::Avif,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Png => "PNG",
+            OutputFormat::Webp => "WebP",
+            OutputFormat::Avif => "AVIF",
+        }
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            folder: None,
+            rename_template: String::new(),
+            keep_both: false,
+            keep_both_suffix: "_compressed".to_string(),
+            force_rewrite: false,
+            preserve_modified_time: true,
+        }
+    }
+}
+
+impl Default for FailureOptions {
+    fn default() -> Self {
+        Self {
+            action: FailureAction::Report,
+            threshold: 3,
+            abort_percent: 0,
+        }
+    }
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 200,
+        }
+    }
+}
+
+impl Default for MetadataOptions {
+    fn default() -> Self {
+        Self {
+            mode: MetadataMode::default(),
+            auto_orient: true,
+        }
+    }
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            incremental: false,
+            skip_processed: true,
+            excluded_dirs: DEFAULT_EXCLUDED_DIRS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            recursive: true,
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            min_size_kb: 0,
+            max_depth: 0,
+            follow_symlinks: false,
+            respect_gitignore: false,
+            min_age_minutes: 0,
+            download_cloud_files: false,
+            target: TargetMode::All,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// 遍历的最大深度，所选文件夹本身为 0 层
+    pub fn walk_depth(&self) -> Option<usize> {
+        if !self.recursive {
+            Some(1)
+        } else {
+            (self.max_depth > 0).then(|| self.max_depth as usize + 1)
+        }
+    }
+
+    pub fn is_excluded_dir(&self, name: &str) -> bool {
+        self.excluded_dirs
+            .iter()
+            .any(|excluded| excluded.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            version: OPTIONS_SCHEMA_VERSION,
+            jpeg: JpegOptions::default(),
+            png: PngOptions::default(),
+            webp: WebpOptions::default(),
+            avif: AvifOptions::default(),
+            animation: AnimationOptions::default(),
+            denoise: DenoiseOptions::default(),
+            auto_tune: AutoTuneOptions::default(),
+            scan: ScanOptions::default(),
+            backup: BackupOptions::default(),
+            failures: FailureOptions::default(),
+            retry: RetryOptions::default(),
+            probe: ProbeOptions::default(),
+            target_size: TargetSizeOptions::default(),
+            parallel: ParallelOptions::default(),
+            resize: ResizeOptions::default(),
+            metadata: MetadataOptions::default(),
+            convert: ConvertOptions::default(),
+            output: OutputOptions::default(),
+            untrusted_input: false,
+        }
+    }
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quality: 80,
+            keep_low_quality_sources: true,
+            encoder: JpegEncoderKind::default(),
+            progressive: true,
+            subsampling: ChromaSubsampling::default(),
+            trellis: true,
+        }
+    }
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            effort: 4,
+            lossy_level: 0,
+        }
+    }
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quality: 80,
+            lossless: false,
+            lossless_png_sources: true,
+        }
+    }
+}
+
+impl Default for AvifOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            quality: 70,
+        }
+    }
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            gif_enabled: true,
+            to_webp: false,
+        }
+    }
+}
+
+impl CompressionOptions {
+    pub fn is_enabled(&self, format: ImageFormat) -> bool {
+        match format {
+            ImageFormat::Jpeg => self.jpeg.enabled,
+            ImageFormat::Png => self.png.enabled,
+            ImageFormat::WebP => self.webp.enabled,
+            ImageFormat::Avif => self.avif.enabled,
+            ImageFormat::Gif => self.animation.gif_enabled,
+            _ => false,
+        }
+    }
+
+    /// 编码为 format 时是否有损，只有有损编码前才降噪
+    pub fn is_lossy(&self, format: ImageFormat) -> bool {
+        match format {
+            ImageFormat::Jpeg | ImageFormat::Avif => true,
+            ImageFormat::WebP => !self.webp.lossless,
+            ImageFormat::Png => self.png.lossy_level > 0,
+            _ => false,
+        }
+    }
+
+    /// 按输入格式调整后的编码参数：PNG 转为 WebP 时按设置改用无损编码
+    pub fn for_source(&self, input: ImageFormat) -> Cow<'_, Self> {
+        if input == ImageFormat::Png
+            && self.output_format(input) == ImageFormat::WebP
+            && self.webp.lossless_png_sources
+            && !self.webp.lossless
+        {
+            let mut adjusted = self.clone();
+            adjusted.webp.lossless = true;
+            Cow::Owned(adjusted)
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
+    /// 动图只能保持原格式或转为动画 WebP，转换为其他格式时保持原格式
+    pub fn for_animation(&self) -> Self {
+        let to_webp = self.animation.to_webp
+            || (self.convert.enabled && self.convert.format == OutputFormat::Webp);
+        let mut adjusted = self.clone();
+        adjusted.convert.enabled = to_webp;
+        adjusted.convert.format = OutputFormat::Webp;
+        adjusted
+    }
+
+    /// 输入为 input 格式时实际写出的格式
+    pub fn output_format(&self, input: ImageFormat) -> ImageFormat {
+        if self.convert.enabled {
+            self.convert.format.image_format()
+        } else {
+            input
+        }
+    }
+
+    /// 列出相比 previous 变得更“有损”的设置项，只比较当前启用的格式
+    pub fn lossier_than(&self, previous: &CompressionOptions) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.jpeg.enabled && self.jpeg.quality < previous.jpeg.quality {
+            changes.push(format!(
+                "JPEG 质量 {} → {}",
+                previous.jpeg.quality, self.jpeg.quality
+            ));
+        }
+        if self.png.enabled && self.png.lossy_level > previous.png.lossy_level {
+            changes.push(format!(
+                "PNG 有损等级 {} → {}",
+                previous.png.lossy_level, self.png.lossy_level
+            ));
+        }
+        if self.webp.enabled && self.webp.quality < previous.webp.quality {
+            changes.push(format!(
+                "WebP 质量 {} → {}",
+                previous.webp.quality, self.webp.quality
+            ));
+        }
+        if self.avif.enabled && self.avif.quality < previous.avif.quality {
+            changes.push(format!(
+                "AVIF 质量 {} → {}",
+                previous.avif.quality, self.avif.quality
+            ));
+        }
+        if self.denoise.strength > previous.denoise.strength {
+            changes.push(format!(
+                "降噪强度 {} → {}",
+                previous.denoise.strength, self.denoise.strength
+            ));
+        }
+        changes
+    }
+
+    /// 启用格式及其质量设置的简短描述，如“JPEG 质量 80，PNG 力度 4 无损”
+    pub fn describe(&self) -> String {
+        let formats = [
+            ImageFormat::Jpeg,
+            ImageFormat::Png,
+            ImageFormat::WebP,
+            ImageFormat::Avif,
+            ImageFormat::Gif,
+        ]
+        .into_iter()
+        .filter(|format| self.is_enabled(*format))
+        .map(|format| self.describe_format(format))
+        .collect::<Vec<_>>()
+        .join("，");
+        let formats = if self.auto_tune.enabled {
+            format!("按内容类型自动调整（{formats}）")
+        } else {
+            formats
+        };
+        if self.convert.enabled {
+            let target = self.convert.format.image_format();
+            format!("{formats}，全部转换为 {}", self.describe_format(target))
+        } else {
+            formats
+        }
+    }
+
+    /// 编码 format 时的质量，没有质量参数（PNG、无损 WebP）时为 None
+    pub fn quality(&self, format: ImageFormat) -> Option<u8> {
+        match format {
+            ImageFormat::Jpeg => Some(self.jpeg.quality),
+            ImageFormat::WebP if !self.webp.lossless => Some(self.webp.quality),
+            ImageFormat::Avif => Some(self.avif.quality),
+            _ => None,
+        }
+    }
+
+    pub fn set_quality(&mut self, format: ImageFormat, quality: u8) {
+        match format {
+            ImageFormat::Jpeg => self.jpeg.quality = quality,
+            ImageFormat::WebP => self.webp.quality = quality,
+            ImageFormat::Avif => self.avif.quality = quality,
+            _ => {}
+        }
+    }
+
+    /// 编码 format 时实际使用的参数
+    pub fn describe_format(&self, format: ImageFormat) -> String {
+        let encoder = match format {
+            ImageFormat::Jpeg => match (self.jpeg.encoder, self.jpeg.subsampling) {
+                (JpegEncoderKind::Baseline, _) => format!("JPEG 质量 {}", self.jpeg.quality),
+                (JpegEncoderKind::Mozjpeg, ChromaSubsampling::S420) => {
+                    format!("JPEG 质量 {} mozjpeg", self.jpeg.quality)
+                }
+                (JpegEncoderKind::Mozjpeg, ChromaSubsampling::S444) => {
+                    format!("JPEG 质量 {} mozjpeg 4:4:4", self.jpeg.quality)
+                }
+            },
+            ImageFormat::Png if self.png.lossy_level == 0 => {
+                format!("PNG 力度 {} 无损", self.png.effort)
+            }
+            ImageFormat::Png => {
+                format!("PNG 力度 {} 有损 {}", self.png.effort, self.png.lossy_level)
+            }
+            ImageFormat::WebP if self.webp.lossless => "WebP 无损".to_string(),
+            ImageFormat::WebP => format!("WebP 质量 {}", self.webp.quality),
+            ImageFormat::Avif => format!("AVIF 质量 {}", self.avif.quality),
+            ImageFormat::Gif => "GIF".to_string(),
+            other => format!("{other:?}"),
+        };
+        if self.denoise.strength > 0 && self.is_lossy(format) {
+            format!("{encoder} 降噪 {}", self.denoise.strength)
+        } else {
+            encoder
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(Self::parse_json(text)?)
+    }
+
+    /// 按版本号逐级迁移到当前结构，缺失的字段使用默认值。
+    /// 没有版本号的配置（手写的、接口传入的）按 v2 读取，只有明显是 v1 的才迁移
+    pub fn from_value(value: Value) -> Result<Self> {
+        Ok(Self::parse_value(value)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(Self::parse_json(&text).map_err(|err| err.at(path))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?).map_err(|source| {
+            ConfigError::Write {
+                path: path.to_path_buf(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    fn parse_json(text: &str) -> Result<Self, ConfigError> {
+        let value: Value = serde_json::from_str(text).map_err(|err| ConfigError::InvalidJson {
+            path: None,
+            detail: err.to_string(),
+        })?;
+        Self::parse_value(value)
+    }
+
+    fn parse_value(mut value: Value) -> Result<Self, ConfigError> {
+        let version = value.get("version").and_then(Value::as_u64);
+        if let Some(version) = version
+            && version > OPTIONS_SCHEMA_VERSION as u64
+        {
+            return Err(ConfigError::TooNew {
+                path: None,
+                version,
+                supported: OPTIONS_SCHEMA_VERSION,
+            });
+        }
+        if version.map_or_else(|| is_v1(&value), |version| version < 2) {
+            migrate_v1(&mut value);
+        }
+
+        let mut options: Self =
+            serde_json::from_value(value).map_err(|err| ConfigError::InvalidFields {
+                path: None,
+                detail: err.to_string(),
+            })?;
+        options.version = OPTIONS_SCHEMA_VERSION;
+        Ok(options)
+    }
+}
+
+/// 有 v1 的 jpeg_quality，且没有任何 v2 的字段
+fn is_v1(value: &Value) -> bool {
+    let (Some(object), Ok(Value::Object(current))) = (
+        value.as_object(),
+        serde_json::to_value(CompressionOptions::default()),
+    ) else {
+        return false;
+    };
+    object.contains_key("jpeg_quality") && !object.keys().any(|key| current.contains_key(key))
+}
+
+// v1 只有一个全局的 jpeg_quality，换成 jpeg 设置；其余字段原样保留。
+// 已有 jpeg 设置时把质量合并进去，文件声明是 v1，以 jpeg_quality 为准
+fn migrate_v1(value: &mut Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+    let quality = object
+        .remove("jpeg_quality")
+        .and_then(|quality| quality.as_u64());
+    object.insert("version".to_string(), json!(2));
+    let jpeg = object
+        .entry("jpeg")
+        .or_insert_with(|| json!({ "enabled": true }));
+    if let (Some(quality), Some(jpeg)) = (quality, jpeg.as_object_mut()) {
+        jpeg.insert("quality".to_string(), json!(quality));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_config_is_read_as_current() {
+        let options = CompressionOptions::from_json(
+            r#"{"scan": {"recursive": false}, "jpeg": {"quality": 55}, "webp": {"quality": 40}}"#,
+        )
+        .unwrap();
+        assert!(!options.scan.recursive);
+        assert_eq!(options.jpeg.quality, 55);
+        assert_eq!(options.webp.quality, 40);
+        assert_eq!(options.version, OPTIONS_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn v1_config_is_migrated() {
+        let options = CompressionOptions::from_json(r#"{"jpeg_quality": 60}"#).unwrap();
+        assert!(options.jpeg.enabled);
+        assert_eq!(options.jpeg.quality, 60);
+        assert_eq!(options.png, PngOptions::default());
+    }
+
+    #[test]
+    fn v1_migration_keeps_other_fields() {
+        let options = CompressionOptions::from_json(
+            r#"{"version": 1, "jpeg_quality": 40, "scan": {"recursive": false}}"#,
+        )
+        .unwrap();
+        assert_eq!(options.jpeg.quality, 40);
+        assert!(!options.scan.recursive);
+    }
+
+    #[test]
+    fn v1_quality_is_merged_into_an_existing_jpeg_table() {
+        let options = CompressionOptions::from_json(
+            r#"{"version": 1, "jpeg_quality": 45, "jpeg": {"quality": 90, "progressive": false}}"#,
+        )
+        .unwrap();
+        assert_eq!(options.jpeg.quality, 45);
+        assert!(!options.jpeg.progressive);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let text = format!(r#"{{"version": {}}}"#, OPTIONS_SCHEMA_VERSION + 1);
+        assert!(CompressionOptions::from_json(&text).is_err());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut options = CompressionOptions::default();
+        options.scan.recursive = false;
+        options.jpeg.quality = 33;
+        let text = options.to_json().unwrap();
+        assert_eq!(CompressionOptions::from_json(&text).unwrap(), options);
+    }
+}
@@ -0,0 +1,333 @@
+//! 输出到其他文件夹时各文件的目标路径：保持相对路径，或按模板重命名；
+//! 保留两份模式下则是原文件旁边带后缀的副本
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::options::OutputOptions;
+use crate::{app_data, review};
+
+/// 界面上显示的模板说明
+pub const TEMPLATE_HELP: &str =
+    "{name} 原文件名，{date} 修改日期，{counter} 或 {counter:04} 序号，{width}x{height} 尺寸";
+
+enum Token {
+    Text(String),
+    Name,
+    Date,
+    Counter { width: usize },
+    Width,
+    Height,
+}
+
+pub struct OutputPlanner {
+    source_root: PathBuf,
+    /// 保留两份模式下副本的后缀，此时写到原文件旁边，不使用 folder 和模板
+    beside_suffix: Option<String>,
+    folder: PathBuf,
+    template: Option<Vec<Token>>,
+    // 序号按修改时间分配，与处理顺序（从大到小）无关
+    counters: HashMap<PathBuf, usize>,
+    used: HashSet<PathBuf>,
+}
+
+impl OutputPlanner {
+    /// 原地覆盖时返回 None；模板写错时直接报错，不处理任何文件
+    pub fn new<'a>(
+        source_root: &Path,
+        options: &OutputOptions,
+        files: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Option<Self>> {
+        let Some(folder) = &options.folder else {
+            if !options.keep_both {
+                return Ok(None);
+            }
+            // 后缀为空时副本就是原文件本身
+            if options.keep_both_suffix.trim().is_empty() {
+                return Err(anyhow!("保留两份时副本的后缀不能为空"));
+            }
+            return Ok(Some(Self {
+                source_root: source_root.to_path_buf(),
+                beside_suffix: Some(options.keep_both_suffix.clone()),
+                folder: PathBuf::new(),
+                template: None,
+                counters: HashMap::new(),
+                used: HashSet::new(),
+            }));
+        };
+        // 写到源文件夹本身就成了不经备份的原地覆盖
+        if same_file::is_same_file(source_root, folder).unwrap_or(false) {
+            return Err(anyhow!(
+                "输出文件夹不能是源文件夹本身，请选择其他文件夹或改为原地覆盖"
+            ));
+        }
+        let template = options.rename_template.trim();
+        let template = if template.is_empty() {
+            None
+        } else {
+            Some(parse_template(template)?)
+        };
+
+        let mut ordered: Vec<(u64, &Path)> = files
+            .into_iter()
+            .map(|path| (modified_secs(path), path))
+            .collect();
+        ordered.sort();
+        let counters = ordered
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, path))| (path.to_path_buf(), index + 1))
+            .collect();
+
+        Ok(Some(Self {
+            source_root: source_root.to_path_buf(),
+            beside_suffix: None,
+            folder: folder.clone(),
+            template,
+            counters,
+            used: HashSet::new(),
+        }))
+    }
+
+    /// path 的输出位置。extension 为按设置和文件内容预计写出的扩展名（见 convert::planned_format），
+    /// None 时沿用原文件的
+    pub fn destination(&mut self, path: &Path, extension: Option<&str>) -> Result<PathBuf> {
+        if let Some(suffix) = &self.beside_suffix {
+            return Ok(review::copy_path(path, suffix));
+        }
+        let original_extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (name, extension): (OsString, String) = match &self.template {
+            None => {
+                let relative = path.strip_prefix(&self.source_root).unwrap_or(path);
+                let name = relative.with_extension("").into_os_string();
+                (name, extension.unwrap_or(&original_extension).to_string())
+            }
+            Some(template) => {
+                let name = render(
+                    template,
+                    path,
+                    self.counters.get(path).copied().unwrap_or(0),
+                )?;
+                if name.is_empty() {
+                    return Err(anyhow!("模板生成的文件名为空: {}", path.display()));
+                }
+                let extension = extension.unwrap_or(&original_extension).to_lowercase();
+                (OsString::from(name), extension)
+            }
+        };
+        // 模板或转换格式可能让多个文件得到同一个名字（如 a.png 和 a.jpg 都转为 a.webp），
+        // 输出文件夹中也可能已有上次运行的结果，都追加 -1、-2 ...；写入时不覆盖已有文件
+        let file_name = |suffix: Option<usize>| {
+            let mut file_name = name.clone();
+            if let Some(suffix) = suffix {
+                file_name.push(format!("-{suffix}"));
+            }
+            if !extension.is_empty() {
+                file_name.push(format!(".{extension}"));
+            }
+            file_name
+        };
+        let mut candidate = self.folder.join(file_name(None));
+        let mut suffix = 1;
+        while self.used.contains(&candidate) || candidate.exists() {
+            candidate = self.folder.join(file_name(Some(suffix)));
+            suffix += 1;
+        }
+        self.used.insert(candidate.clone());
+        Ok(candidate)
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("模板中的 {{ 没有闭合: {template}"))?;
+        let placeholder = &rest[start + 1..end];
+        let (key, spec) = placeholder
+            .split_once(':')
+            .map_or((placeholder, None), |(key, spec)| (key, Some(spec)));
+        tokens.push(match (key, spec) {
+            ("name", None) => Token::Name,
+            ("date", None) => Token::Date,
+            ("width", None) => Token::Width,
+            ("height", None) => Token::Height,
+            ("counter", None) => Token::Counter { width: 0 },
+            ("counter", Some(spec)) => Token::Counter {
+                width: spec
+                    .parse()
+                    .with_context(|| format!("无效的序号位数: {{{placeholder}}}"))?,
+            },
+            _ => return Err(anyhow!("未知的占位符: {{{placeholder}}}")),
+        });
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+fn render(template: &[Token], path: &Path, counter: usize) -> Result<String> {
+    let needs_size = template
+        .iter()
+        .any(|token| matches!(token, Token::Width | Token::Height));
+    let (width, height) = if needs_size {
+        image::image_dimensions(path)
+            .with_context(|| format!("无法读取图像尺寸: {}", path.display()))?
+    } else {
+        (0, 0)
+    };
+
+    let mut name = String::new();
+    for token in template {
+        match token {
+            Token::Text(text) => name.push_str(text),
+            Token::Name => name.push_str(
+                &path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            Token::Date => name.push_str(&app_data::format_date(modified_secs(path))),
+            Token::Counter { width } => name.push_str(&format!("{counter:0width$}")),
+            Token::Width => name.push_str(&width.to_string()),
+            Token::Height => name.push_str(&height.to_string()),
+        }
+    }
+    Ok(sanitize(&name))
+}
+
+// 允许用 / 分出子文件夹，其余在常见文件系统上不合法的字符换成 _，并去掉 .. 防止写出输出文件夹
+fn sanitize(name: &str) -> String {
+    name.split(['/', '\\'])
+        .map(|part| {
+            part.chars()
+                .map(|c| match c {
+                    ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                    c if c.is_control() => '_',
+                    c => c,
+                })
+                .collect::<String>()
+        })
+        .filter(|part| !part.is_empty() && part != "." && part != "..")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .map(app_data::unix_secs)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planner(template: &str, files: &[&Path]) -> OutputPlanner {
+        let options = OutputOptions {
+            folder: Some(PathBuf::from("/out")),
+            rename_template: template.to_string(),
+            ..OutputOptions::default()
+        };
+        OutputPlanner::new(Path::new("/photos"), &options, files.iter().copied())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn template_errors_are_reported() {
+        assert!(parse_template("{name").is_err());
+        assert!(parse_template("{size}").is_err());
+        assert!(parse_template("{counter:x}").is_err());
+        assert!(parse_template("img_{name}_{counter:04}").is_ok());
+    }
+
+    #[test]
+    fn template_renders_name_and_padded_counter() {
+        let template = parse_template("{name}_{counter:03}").unwrap();
+        assert_eq!(
+            render(&template, Path::new("/photos/cat.jpg"), 7).unwrap(),
+            "cat_007"
+        );
+    }
+
+    #[test]
+    fn sanitize_keeps_subfolders_inside_the_output() {
+        assert_eq!(sanitize("2024/a:b?c"), "2024/a_b_c");
+        assert_eq!(sanitize("../..\\x/./y"), "x/y");
+        assert_eq!(sanitize("a\u{7}b"), "a_b");
+    }
+
+    #[test]
+    fn converted_names_do_not_collide() {
+        let png = Path::new("/photos/sub/a.png");
+        let jpg = Path::new("/photos/sub/a.jpg");
+        let mut planner = planner("", &[png, jpg]);
+        assert_eq!(
+            planner.destination(png, Some("webp")).unwrap(),
+            Path::new("/out/sub/a.webp")
+        );
+        assert_eq!(
+            planner.destination(jpg, Some("webp")).unwrap(),
+            Path::new("/out/sub/a-1.webp")
+        );
+        assert_eq!(
+            planner
+                .destination(Path::new("/photos/b.JPG"), None)
+                .unwrap(),
+            Path::new("/out/b.JPG")
+        );
+    }
+
+    #[test]
+    fn existing_outputs_are_not_reused() {
+        let root = std::env::temp_dir().join(format!("compress_img_output_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let out = root.join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        // 上次运行写出的文件
+        std::fs::write(out.join("a.webp"), b"").unwrap();
+        let options = OutputOptions {
+            folder: Some(out.clone()),
+            ..OutputOptions::default()
+        };
+        let source = root.join("a.png");
+        let mut planner = OutputPlanner::new(&root, &options, [source.as_path()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            planner.destination(&source, Some("webp")).unwrap(),
+            out.join("a-1.webp")
+        );
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn template_names_get_a_numbered_suffix() {
+        let first = Path::new("/photos/a.jpg");
+        let second = Path::new("/photos/sub/a.jpg");
+        let mut planner = planner("{name}", &[first, second]);
+        assert_eq!(
+            planner.destination(first, None).unwrap(),
+            Path::new("/out/a.jpg")
+        );
+        assert_eq!(
+            planner.destination(second, None).unwrap(),
+            Path::new("/out/a-1.jpg")
+        );
+    }
+}
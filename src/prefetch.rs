@@ -0,0 +1,32 @@
+//! 批量处理时在后台线程中提前读入接下来的几个文件，当前文件编码的同时
+//! 下一个文件已在传输，NAS、移动硬盘等高延迟存储上能省掉大部分等待时间。
+
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::options::CompressionOptions;
+use crate::{read_source, SourceBytes};
+
+/// 最多提前读入的文件数，限制预读占用的内存
+const PREFETCH_DEPTH: usize = 2;
+
+/// 按 paths 的顺序依次产出每个文件及其读取结果。
+/// 迭代器被丢弃后后台线程在读完手头的文件时退出
+pub fn prefetch(
+    paths: Vec<PathBuf>,
+    options: &CompressionOptions,
+) -> impl Iterator<Item = (PathBuf, Result<SourceBytes>)> {
+    let (sender, receiver) = mpsc::sync_channel(PREFETCH_DEPTH);
+    let options = options.clone();
+    thread::spawn(move || {
+        for path in paths {
+            let source = read_source(&path, &options);
+            if sender.send((path, source)).is_err() {
+                return;
+            }
+        }
+    });
+    Receiver::into_iter(receiver)
+}
@@ -0,0 +1,80 @@
+use crate::options::CompressionOptions;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityPreset {
+    Maximum,
+    High,
+    Balanced,
+    Small,
+    Tiny,
+}
+
+impl QualityPreset {
+    /// 与界面下拉框中的顺序一致（下拉框第 0 项为“自定义”）
+    pub const ALL: [QualityPreset; 5] = [
+        QualityPreset::Maximum,
+        QualityPreset::High,
+        QualityPreset::Balanced,
+        QualityPreset::Small,
+        QualityPreset::Tiny,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            QualityPreset::Maximum => "最高",
+            QualityPreset::High => "高",
+            QualityPreset::Balanced => "均衡",
+            QualityPreset::Small => "较小",
+            QualityPreset::Tiny => "极小",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            QualityPreset::Maximum => "几乎无法察觉差异，体积缩减有限，适合存档原片",
+            QualityPreset::High => "放大查看才可能发现细微差异，适合仍需二次编辑的图片",
+            QualityPreset::Balanced => "日常浏览看不出差异，体积通常能减少一半左右",
+            QualityPreset::Small => "细节和渐变处有轻微瑕疵，PNG 会量化颜色，适合网页和聊天分享",
+            QualityPreset::Tiny => "可见块状和色带，适合缩略图或对画质要求很低的场景",
+        }
+    }
+
+    pub fn bundle(self) -> QualityBundle {
+        let (jpeg, png_effort, png_lossy, webp, avif) = match self {
+            QualityPreset::Maximum => (95, 6, 0, 95, 90),
+            QualityPreset::High => (88, 5, 0, 88, 80),
+            QualityPreset::Balanced => (80, 4, 0, 80, 70),
+            QualityPreset::Small => (68, 6, 40, 68, 55),
+            QualityPreset::Tiny => (50, 6, 75, 50, 40),
+        };
+        QualityBundle {
+            jpeg,
+            png_effort,
+            png_lossy,
+            webp,
+            avif,
+        }
+    }
+}
+
+/// 一组各格式的质量参数。质量预设、内容配置和按图分类的自动调整都通过它改写选项
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QualityBundle {
+    pub jpeg: u8,
+    pub png_effort: u8,
+    /// PNG 有损量化程度，0 为无损
+    pub png_lossy: u8,
+    pub webp: u8,
+    pub avif: u8,
+}
+
+impl QualityBundle {
+    /// 只调整质量参数，各格式的启用开关保持用户的选择
+    pub fn apply_to(self, options: &mut CompressionOptions) {
+        options.jpeg.quality = self.jpeg;
+        options.png.effort = self.png_effort;
+        options.png.lossy_level = self.png_lossy;
+        options.webp.quality = self.webp;
+        options.avif.quality = self.avif;
+    }
+}
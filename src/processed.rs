@@ -0,0 +1,193 @@
+//! 已处理文件的索引：记下每个压缩过的文件之后的大小、修改时间和内容校验值。
+//! 再次运行时三者都没变的文件直接跳过，避免反复压缩同一个 JPEG 叠加损失。
+//! 另记下每个处理完的源文件当时的大小和修改时间，增量模式据此只处理新增和改动过的文件。
+//! 与失败记录一样，保存时只合并本次记下的条目。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::app_data::{self, path_key};
+
+const INDEX_FILE_NAME: &str = "processed.json";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessedIndex {
+    /// 键为规范化后的文件路径
+    files: BTreeMap<String, ProcessedFile>,
+    /// 处理完（含保持原样、写到别处）的源文件，键同上
+    sources: BTreeMap<String, SourceFile>,
+    /// 载入后记下的键，保存时据此合并
+    #[serde(skip)]
+    changed: BTreeSet<String>,
+    #[serde(skip)]
+    changed_sources: BTreeSet<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct ProcessedFile {
+    size: u64,
+    /// 修改时间，Unix 毫秒
+    modified_ms: u64,
+    crc32: u32,
+}
+
+/// 源文件处理完时的大小和修改时间，只比较元数据，大文件夹也很快
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SourceFile {
+    size: u64,
+    modified_ms: u64,
+}
+
+impl ProcessedFile {
+    /// 只读元数据，不读内容
+    fn stat(path: &Path) -> Option<(u64, u64)> {
+        let metadata = path.metadata().ok().filter(|metadata| metadata.is_file())?;
+        let modified_ms = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64;
+        Some((metadata.len(), modified_ms))
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let (size, modified_ms) = Self::stat(path)?;
+        let crc32 = crc32fast::hash(&fs::read(path).ok()?);
+        Some(Self {
+            size,
+            modified_ms,
+            crc32,
+        })
+    }
+}
+
+impl ProcessedIndex {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&index_path()?)
+    }
+
+    /// 重新读出磁盘上的索引，合并本次记下的条目后写回
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&index_path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        app_data::with_file_lock(path, || Self::read(path))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if self.changed.is_empty() && self.changed_sources.is_empty() {
+            return Ok(());
+        }
+        app_data::with_file_lock(path, || {
+            // 文件损坏时以本次内容为准
+            let mut current = Self::read(path).unwrap_or_else(|err| {
+                log::warn!("{err:#}");
+                Self::default()
+            });
+            app_data::merge_changed(&mut current.files, &self.files, &self.changed);
+            app_data::merge_changed(&mut current.sources, &self.sources, &self.changed_sources);
+            app_data::write_atomic(path, serde_json::to_string(&current)?.as_bytes())
+                .context("无法写入已处理文件索引")
+        })
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("无法读取已处理文件索引: {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("无法解析已处理文件索引: {}", path.display()))
+    }
+
+    /// 记下 path 现在的状态
+    pub fn record(&mut self, path: &Path) {
+        if let Some(file) = ProcessedFile::read(path) {
+            let key = path_key(path);
+            self.files.insert(key.clone(), file);
+            self.changed.insert(key);
+        }
+    }
+
+    /// 记下源文件处理完时的状态，失败或推迟的文件不记，增量模式下次照常处理
+    pub fn record_source(&mut self, path: &Path) {
+        if let Some((size, modified_ms)) = ProcessedFile::stat(path) {
+            let key = path_key(path);
+            self.sources
+                .insert(key.clone(), SourceFile { size, modified_ms });
+            self.changed_sources.insert(key);
+        }
+    }
+
+    /// 增量模式：源文件处理完之后大小和修改时间都没变。没有记录的文件
+    /// （新复制进来的，即使保留了旧的修改时间）都算改动过
+    pub fn is_source_unchanged(&self, path: &Path) -> bool {
+        if self.sources.is_empty() {
+            return false;
+        }
+        self.sources.get(&path_key(path)).is_some_and(|recorded| {
+            ProcessedFile::stat(path) == Some((recorded.size, recorded.modified_ms))
+        })
+    }
+
+    /// 压缩过且之后没有改动；大小和修改时间都一致时才读内容核对
+    pub fn is_unchanged(&self, path: &Path) -> bool {
+        if self.files.is_empty() {
+            return false;
+        }
+        let Some(recorded) = self.files.get(&path_key(path)) else {
+            return false;
+        };
+        ProcessedFile::stat(path) == Some((recorded.size, recorded.modified_ms))
+            && ProcessedFile::read(path).as_ref() == Some(recorded)
+    }
+}
+
+fn index_path() -> Result<PathBuf> {
+    Ok(app_data::data_dir()?.join(INDEX_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_sessions_keep_each_others_records() {
+        let root =
+            std::env::temp_dir().join(format!("compress_img_processed_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let index = root.join(INDEX_FILE_NAME);
+        let (a, b, c) = (root.join("a.jpg"), root.join("b.jpg"), root.join("c.jpg"));
+        for path in [&a, &b, &c] {
+            fs::write(path, path.display().to_string()).unwrap();
+        }
+        let mut earlier = ProcessedIndex::default();
+        earlier.record(&c);
+        earlier.save_to(&index).unwrap();
+
+        let mut first = ProcessedIndex::load_from(&index).unwrap();
+        let mut second = ProcessedIndex::load_from(&index).unwrap();
+        first.record(&a);
+        second.record(&b);
+        first.save_to(&index).unwrap();
+        second.save_to(&index).unwrap();
+
+        let merged = ProcessedIndex::load_from(&index).unwrap();
+        assert!(merged.is_unchanged(&a));
+        assert!(merged.is_unchanged(&b));
+        assert!(merged.is_unchanged(&c));
+
+        fs::write(&a, "edited").unwrap();
+        assert!(!merged.is_unchanged(&a));
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::classify::{self, ImageClass};
+use crate::options::CompressionOptions;
+use crate::preset::QualityBundle;
+use crate::{codec, scan};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+const SAMPLE_LIMIT: usize = 24;
+// 某一类占比达到该阈值才认为文件夹是单一类型，否则建议“混合”
+const DOMINANT_SHARE: f32 = 0.7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentProfile {
+    Photos,
+    Screenshots,
+    LineArt,
+    Scans,
+    Mixed,
+}
+
+impl ContentProfile {
+    pub fn key(self) -> &'static str {
+        match self {
+            ContentProfile::Photos => "photos",
+            ContentProfile::Screenshots => "screenshots",
+            ContentProfile::LineArt => "line_art",
+            ContentProfile::Scans => "scans",
+            ContentProfile::Mixed => "mixed",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "photos" => Some(ContentProfile::Photos),
+            "screenshots" => Some(ContentProfile::Screenshots),
+            "line_art" => Some(ContentProfile::LineArt),
+            "scans" => Some(ContentProfile::Scans),
+            "mixed" => Some(ContentProfile::Mixed),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentProfile::Photos => "照片",
+            ContentProfile::Screenshots => "截图",
+            ContentProfile::LineArt => "线稿",
+            ContentProfile::Scans => "扫描件",
+            ContentProfile::Mixed => "混合内容",
+        }
+    }
+
+    pub fn bundle(self) -> QualityBundle {
+        let (jpeg, png_effort, png_lossy, webp, avif) = match self {
+            ContentProfile::Photos => (78, 4, 0, 78, 65),
+            // 截图以文字和纯色为主，JPEG 需要较高质量才不糊字，PNG 量化收益很大
+            ContentProfile::Screenshots => (88, 6, 30, 90, 80),
+            // 线稿颜色很少，调色板量化几乎无损；有损格式要保住锐利的边缘
+            ContentProfile::LineArt => (90, 6, 50, 90, 80),
+            ContentProfile::Scans => (70, 6, 60, 70, 60),
+            ContentProfile::Mixed => (80, 4, 0, 80, 70),
+        };
+        QualityBundle {
+            jpeg,
+            png_effort,
+            png_lossy,
+            webp,
+            avif,
+        }
+    }
+}
+
+impl From<ImageClass> for ContentProfile {
+    fn from(class: ImageClass) -> Self {
+        match class {
+            ImageClass::Photo => ContentProfile::Photos,
+            ImageClass::Screenshot => ContentProfile::Screenshots,
+            ImageClass::LineArt => ContentProfile::LineArt,
+            ImageClass::Scan => ContentProfile::Scans,
+        }
+    }
+}
+
+pub struct ProfileSuggestion {
+    pub profile: ContentProfile,
+    pub sampled: usize,
+    pub photos: usize,
+    pub screenshots: usize,
+    pub line_art: usize,
+    pub scans: usize,
+}
+
+impl ProfileSuggestion {
+    pub fn summary(&self) -> String {
+        format!(
+            "建议配置: {}（抽样 {} 张：照片 {} / 截图 {} / 线稿 {} / 扫描件 {}）",
+            self.profile.label(),
+            self.sampled,
+            self.photos,
+            self.screenshots,
+            self.line_art,
+            self.scans
+        )
+    }
+}
+
+pub fn suggest_profile(folder: &Path, options: &CompressionOptions) -> Result<ProfileSuggestion> {
+    let files = scan::scan_folder(folder, options).files;
+    if files.is_empty() {
+        return Err(anyhow!("文件夹中没有可分析的图像"));
+    }
+
+    let mut suggestion = ProfileSuggestion {
+        profile: ContentProfile::Mixed,
+        sampled: 0,
+        photos: 0,
+        screenshots: 0,
+        line_art: 0,
+        scans: 0,
+    };
+    for path in scan::sample_evenly(&files, SAMPLE_LIMIT) {
+        let Ok((_, image)) = codec::open_image(&path) else {
+            continue;
+        };
+        suggestion.sampled += 1;
+        match classify::classify(&image) {
+            ImageClass::Photo => suggestion.photos += 1,
+            ImageClass::Screenshot => suggestion.screenshots += 1,
+            ImageClass::LineArt => suggestion.line_art += 1,
+            ImageClass::Scan => suggestion.scans += 1,
+        }
+    }
+    if suggestion.sampled == 0 {
+        return Err(anyhow!("抽样的图像均无法解码"));
+    }
+
+    let dominant = [
+        (ImageClass::Photo, suggestion.photos),
+        (ImageClass::Screenshot, suggestion.screenshots),
+        (ImageClass::LineArt, suggestion.line_art),
+        (ImageClass::Scan, suggestion.scans),
+    ]
+    .into_iter()
+    .max_by_key(|(_, count)| *count)
+    .filter(|(_, count)| *count as f32 >= suggestion.sampled as f32 * DOMINANT_SHARE);
+    if let Some((class, _)) = dominant {
+        suggestion.profile = class.into();
+    }
+    Ok(suggestion)
+}
@@ -0,0 +1,211 @@
+//! 运行报告：逐个列出本次处理的图像及结果，运行结束后保存为 CSV 或 JSON 供审计。
+//! 不是图像的文件不列入报告。
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::batch::FileOutcome;
+use crate::error::Language;
+use crate::scan::SkipReason;
+use crate::{app_data, savings_percent};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Ok,
+    /// 未处理或判断不值得改写，文件保持原样
+    Skipped,
+    Error,
+}
+
+impl ReportStatus {
+    fn name(self) -> &'static str {
+        match self {
+            ReportStatus::Ok => "ok",
+            ReportStatus::Skipped => "skipped",
+            ReportStatus::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportEntry {
+    pub path: PathBuf,
+    pub original_size: u64,
+    pub new_size: u64,
+    pub saved_percent: f64,
+    pub status: ReportStatus,
+    /// 跳过的原因、错误信息或写出的新文件
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReportSummary {
+    pub total: usize,
+    pub ok: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub saved_percent: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RunReport {
+    pub entries: Vec<ReportEntry>,
+}
+
+#[derive(Serialize)]
+struct ReportFile<'a> {
+    created_at: String,
+    summary: ReportSummary,
+    files: &'a [ReportEntry],
+}
+
+impl ReportEntry {
+    /// 处理完成的一个文件，原因和错误按 language 写出
+    pub fn new(path: &Path, outcome: &FileOutcome, language: Language) -> Self {
+        match outcome {
+            FileOutcome::Compressed(stats) => ReportEntry {
+                path: path.to_path_buf(),
+                original_size: stats.original_size,
+                new_size: stats.new_size,
+                saved_percent: savings_percent(stats.original_size, stats.new_size),
+                status: if stats.kept_reason.is_some() {
+                    ReportStatus::Skipped
+                } else {
+                    ReportStatus::Ok
+                },
+                detail: match (stats.kept_text(language), &stats.output) {
+                    (Some(reason), _) => reason,
+                    (None, Some(output)) => output.display().to_string(),
+                    (None, None) => String::new(),
+                },
+            },
+            FileOutcome::Failed(failure) => {
+                let size = file_size(path);
+                ReportEntry {
+                    path: path.to_path_buf(),
+                    original_size: size,
+                    new_size: size,
+                    saved_percent: 0.0,
+                    status: ReportStatus::Error,
+                    detail: failure.render(language),
+                }
+            }
+        }
+    }
+}
+
+impl RunReport {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn record(&mut self, path: &Path, outcome: &FileOutcome, language: Language) {
+        self.entries.push(ReportEntry::new(path, outcome, language));
+    }
+
+    /// 扫描或开始前就被跳过的文件
+    pub fn record_skipped(&mut self, path: &Path, reason: SkipReason, language: Language) {
+        if reason == SkipReason::Unsupported {
+            return;
+        }
+        // 排除的文件夹整个算作一项，没有大小
+        let size = file_size(path);
+        self.entries.push(ReportEntry {
+            path: path.to_path_buf(),
+            original_size: size,
+            new_size: size,
+            saved_percent: 0.0,
+            status: ReportStatus::Skipped,
+            detail: reason.render(language).to_string(),
+        });
+    }
+
+    pub fn summary(&self) -> ReportSummary {
+        let mut summary = ReportSummary {
+            total: self.entries.len(),
+            ..ReportSummary::default()
+        };
+        for entry in &self.entries {
+            match entry.status {
+                ReportStatus::Ok => summary.ok += 1,
+                ReportStatus::Skipped => summary.skipped += 1,
+                ReportStatus::Error => summary.errors += 1,
+            }
+            summary.bytes_before += entry.original_size;
+            summary.bytes_after += entry.new_size;
+        }
+        summary.saved_percent = savings_percent(summary.bytes_before, summary.bytes_after);
+        summary
+    }
+
+    /// 每个文件一行，最后一行为合计
+    pub fn to_csv(&self) -> String {
+        let mut text = String::from("path,original_size,new_size,saved_percent,status,detail\n");
+        for entry in &self.entries {
+            text.push_str(&format!(
+                "{},{},{},{:.2},{},{}\n",
+                csv_field(&entry.path.display().to_string()),
+                entry.original_size,
+                entry.new_size,
+                entry.saved_percent,
+                entry.status.name(),
+                csv_field(&entry.detail)
+            ));
+        }
+        let summary = self.summary();
+        text.push_str(&format!(
+            "合计,{},{},{:.2},,{}\n",
+            summary.bytes_before,
+            summary.bytes_after,
+            summary.saved_percent,
+            csv_field(&format!(
+                "ok {} / skipped {} / error {}",
+                summary.ok, summary.skipped, summary.errors
+            ))
+        ));
+        text
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let file = ReportFile {
+            created_at: app_data::format_datetime(app_data::unix_now()),
+            summary: self.summary(),
+            files: &self.entries,
+        };
+        serde_json::to_string_pretty(&file).context("无法序列化报告")
+    }
+
+    /// 扩展名为 .json 时保存为 JSON，否则为 CSV
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let text = if is_json {
+            self.to_json()?
+        } else {
+            // 带 BOM，Excel 才能正确识别中文
+            format!("\u{feff}{}", self.to_csv())
+        };
+        fs::write(path, text).with_context(|| format!("无法写入报告: {}", path.display()))
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    path.metadata()
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map_or(0, |metadata| metadata.len())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,53 @@
+//! 暂时性 I/O 错误的重试：网络盘抖动、杀毒软件或同步程序短暂占用文件时，
+//! 稍等片刻再试通常就能成功，不必让整个文件失败。
+
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use crate::options::RetryOptions;
+
+/// 执行 op，遇到暂时性错误时按指数退避重试，retries 累加实际重试的次数。
+/// 重试用尽后返回最后一次的错误，重试次数由调用方随错误一起报告
+pub fn with_retry<T>(
+    options: &RetryOptions,
+    retries: &mut u32,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = Duration::from_millis(options.initial_backoff_ms);
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < options.max_retries && is_transient(&err) => {
+                log::debug!("暂时性错误，{} ms 后重试: {err}", backoff.as_millis());
+                thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+                attempt += 1;
+                *retries += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+pub fn is_transient(err: &io::Error) -> bool {
+    // 共享冲突、锁冲突（杀毒软件扫描时常见）、意外的网络错误、网络名不再可用
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(32 | 33 | 59 | 64)) {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+            | ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NetworkDown
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+    )
+}
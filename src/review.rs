@@ -0,0 +1,117 @@
+//! 保留两份的复查模式：压缩结果先写成原文件旁边带后缀的副本，
+//! 逐个确认后接受的副本替换原文件，拒绝的副本被删除。
+
+use anyhow::{Context, Result};
+use image::{ImageFormat, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backup::BackupSession;
+use crate::codec;
+use crate::options::CompressionOptions;
+use crate::scan::{self, SkipReason};
+
+#[derive(Clone, Debug)]
+pub struct ReviewItem {
+    pub original: PathBuf,
+    pub copy: PathBuf,
+}
+
+impl ReviewItem {
+    /// (原文件大小, 副本大小)
+    pub fn sizes(&self) -> (u64, u64) {
+        let size = |path: &Path| path.metadata().map(|m| m.len()).unwrap_or(0);
+        (size(&self.original), size(&self.copy))
+    }
+}
+
+/// 在原文件名（不含扩展名）后追加 suffix
+pub fn copy_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(match path.extension() {
+        Some(extension) => format!("{stem}{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}{suffix}"),
+    })
+}
+
+/// path 是某个原文件的待确认副本时返回原文件。转换格式时两者扩展名不同，优先找扩展名相同的
+pub fn original_of(path: &Path, suffix: &str) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let base = stem
+        .strip_suffix(suffix)
+        .filter(|base| !suffix.is_empty() && !base.is_empty())?;
+    let candidates: Vec<PathBuf> = fs::read_dir(path.parent()?)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate != path
+                && candidate.file_stem().is_some_and(|stem| stem == base)
+                && ImageFormat::from_path(candidate).is_ok()
+        })
+        .collect();
+    candidates
+        .iter()
+        .find(|candidate| candidate.extension() == path.extension())
+        .or(candidates.first())
+        .cloned()
+}
+
+/// folder 中所有待确认的副本
+pub fn find_pending(folder: &Path, options: &CompressionOptions) -> Vec<ReviewItem> {
+    let suffix = &options.output.keep_both_suffix;
+    scan::scan_folder(folder, options)
+        .skipped
+        .into_iter()
+        .filter(|(_, reason)| *reason == SkipReason::PendingReview)
+        .filter_map(|(copy, _)| {
+            original_of(&copy, suffix).map(|original| ReviewItem { original, copy })
+        })
+        .collect()
+}
+
+/// 用副本替换原文件，返回替换后的路径；backup 不为 None 时先备份原文件
+pub fn accept(item: &ReviewItem, backup: Option<&mut BackupSession>) -> Result<PathBuf> {
+    if let Some(backup) = backup {
+        backup.save(&item.original)?;
+    }
+    // 扩展名只差大小写时视为同一个文件，不区分大小写的文件系统上改名后再删除会删掉结果
+    let same_extension = match (item.original.extension(), item.copy.extension()) {
+        (Some(original), Some(copy)) => original.eq_ignore_ascii_case(copy),
+        (original, copy) => original == copy,
+    };
+    let target = match item.copy.extension() {
+        Some(extension) if !same_extension => item.original.with_extension(extension),
+        _ => item.original.clone(),
+    };
+    fs::rename(&item.copy, &target)
+        .with_context(|| format!("无法替换原文件: {}", item.original.display()))?;
+    if target != item.original {
+        fs::remove_file(&item.original)
+            .with_context(|| format!("无法删除原文件: {}", item.original.display()))?;
+    }
+    Ok(target)
+}
+
+pub fn reject(item: &ReviewItem) -> Result<()> {
+    fs::remove_file(&item.copy)
+        .with_context(|| format!("无法删除压缩副本: {}", item.copy.display()))
+}
+
+/// 图像中央 size × size 的区域，按原始像素截取，用来对比压缩前后的细节
+pub fn preview(path: &Path, size: u32) -> Result<RgbaImage> {
+    let (_, image) = codec::open_image(path)?;
+    let width = size.min(image.width());
+    let height = size.min(image.height());
+    Ok(image
+        .crop_imm(
+            (image.width() - width) / 2,
+            (image.height() - height) / 2,
+            width,
+            height,
+        )
+        .to_rgba8())
+}
@@ -0,0 +1,493 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use image::ImageFormat;
+use same_file::Handle;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{ConfigError, Language};
+use crate::failures::REVIEW_DIR_NAME;
+use crate::lock::LOCK_FILE_NAME;
+use crate::options::{CompressionOptions, ScanOptions};
+use crate::review;
+
+pub struct ScanResult {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<anyhow::Error>,
+    /// 受支持但不匹配包含列表而被跳过的图像数
+    pub not_included: usize,
+    /// 遍历到但没有列入 files 的文件，排除的文件夹整个算作一项
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+/// 文件没有被处理的原因，前几种在扫描时判断，其余由批量处理判断
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// 不是图像，或是不支持的图像格式
+    Unsupported,
+    /// 该格式的压缩未启用
+    FormatDisabled,
+    /// 位于排除的文件夹中
+    ExcludedDir,
+    /// 不匹配包含列表
+    NotIncluded,
+    /// 匹配排除规则
+    ExcludedPattern,
+    /// 小于最小文件大小
+    TooSmall,
+    /// 保留两份模式下还没确认的压缩副本
+    PendingReview,
+    /// 在忽略列表中：反复失败后自动加入，或被手动设为不再处理
+    Ignored,
+    /// 用户在预估结果中选择本次跳过
+    Deselected,
+    /// 转换后的文件已存在
+    AlreadyConverted,
+    /// 同步盘中只在云端，未下载到本地
+    CloudOnly,
+    /// 增量模式下处理完之后未修改
+    Unchanged,
+    /// 以前压缩过，之后没有改动
+    AlreadyProcessed,
+    /// 刚被修改，可能仍在写入
+    TooRecent,
+    /// 不在“最大文件”范围内
+    NotTargeted,
+}
+
+impl SkipReason {
+    pub fn render(&self, language: Language) -> &'static str {
+        let zh = language == Language::Chinese;
+        match self {
+            SkipReason::Unsupported if zh => "不是支持的图像格式",
+            SkipReason::Unsupported => "not a supported image format",
+            SkipReason::FormatDisabled if zh => "该格式的压缩未启用",
+            SkipReason::FormatDisabled => "compression of this format is disabled",
+            SkipReason::ExcludedDir if zh => "位于排除的文件夹中",
+            SkipReason::ExcludedDir => "inside an excluded folder",
+            SkipReason::NotIncluded if zh => "不匹配包含规则",
+            SkipReason::NotIncluded => "does not match the include patterns",
+            SkipReason::ExcludedPattern if zh => "匹配排除规则",
+            SkipReason::ExcludedPattern => "matches an exclude pattern",
+            SkipReason::TooSmall if zh => "小于最小文件大小",
+            SkipReason::TooSmall => "smaller than the minimum file size",
+            SkipReason::PendingReview if zh => "待确认的压缩副本",
+            SkipReason::PendingReview => "compressed copy awaiting review",
+            SkipReason::Ignored if zh => "在忽略列表中",
+            SkipReason::Ignored => "on the ignore list",
+            SkipReason::Deselected if zh => "已在预估结果中选择跳过",
+            SkipReason::Deselected => "deselected in the estimate",
+            SkipReason::AlreadyConverted if zh => "已转换过，目标文件已存在",
+            SkipReason::AlreadyConverted => "already converted, the target file exists",
+            SkipReason::CloudOnly if zh => "只在云端，未下载到本地",
+            SkipReason::CloudOnly => "only in the cloud, not downloaded",
+            SkipReason::Unchanged if zh => "处理完之后未修改",
+            SkipReason::Unchanged => "not modified since it was processed",
+            SkipReason::AlreadyProcessed if zh => "已压缩过，之后未改动",
+            SkipReason::AlreadyProcessed => "already compressed and not modified since",
+            SkipReason::TooRecent if zh => "最近刚修改，可能仍在写入",
+            SkipReason::TooRecent => "modified very recently, may still be written",
+            SkipReason::NotTargeted if zh => "不在最大文件范围内",
+            SkipReason::NotTargeted => "not among the largest files",
+        }
+    }
+}
+
+// 大目录树里条目极多，进度回调按时间节流
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanProgress {
+    pub dirs_visited: usize,
+    pub files_found: usize,
+    pub total_bytes: u64,
+}
+
+pub fn scan_folder(folder: &Path, options: &CompressionOptions) -> ScanResult {
+    scan_folder_with_progress(folder, options, |_| {})
+}
+
+/// 与 scan_folder 相同，遍历过程中不时通过 on_progress 报告进度
+pub fn scan_folder_with_progress(
+    folder: &Path,
+    options: &CompressionOptions,
+    mut on_progress: impl FnMut(ScanProgress),
+) -> ScanResult {
+    let mut progress = ScanProgress::default();
+    let mut last_report = Instant::now();
+    let mut result = ScanResult {
+        files: Vec::new(),
+        errors: Vec::new(),
+        not_included: 0,
+        skipped: Vec::new(),
+    };
+    // 规则写错时宁可什么都不处理，也不要退化成处理整个文件夹
+    let (include, exclude) = match (include_set(&options.scan), exclude_set(&options.scan)) {
+        (Ok(include), Ok(exclude)) => (include, exclude),
+        (Err(err), _) | (_, Err(err)) => {
+            result.errors.push(err);
+            return result;
+        }
+    };
+    let min_size = options.scan.min_size_kb as u64 * 1024;
+
+    let scan_options = options.scan.clone();
+    // 输出文件夹或备份位置在源文件夹内时不要把上次的结果再扫进来。
+    // 按文件身份比较，相对路径、多余的斜杠或符号链接写法不同也能认出
+    let skip_dirs: Vec<Handle> = [
+        options.output.folder.clone(),
+        options.backup.location(folder),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|dir| Handle::from_path(dir).ok())
+    .collect();
+    let respect_gitignore = options.scan.respect_gitignore;
+    let root = folder.to_path_buf();
+    let dir_exclude = exclude.clone();
+    // filter_entry 的闭包要求 Send + Sync，被排除的文件夹先收集到这里
+    let excluded_dirs = Arc::new(Mutex::new(Vec::new()));
+    let excluded = Arc::clone(&excluded_dirs);
+    // 关掉 ignore 默认的隐藏文件等过滤，只按选项决定是否读取忽略规则
+    let walker = WalkBuilder::new(folder)
+        .standard_filters(false)
+        .git_ignore(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .ignore(respect_gitignore)
+        .parents(respect_gitignore)
+        .max_depth(options.scan.walk_depth())
+        .follow_links(options.scan.follow_symlinks)
+        .filter_entry(move |entry| {
+            if entry.depth() == 0 || !entry.file_type().is_some_and(|t| t.is_dir()) {
+                return true;
+            }
+            if !skip_dirs.is_empty()
+                && Handle::from_path(entry.path()).is_ok_and(|dir| skip_dirs.contains(&dir))
+            {
+                return false;
+            }
+            let name = entry.file_name().to_string_lossy();
+            let is_review_dir = entry.depth() == 1 && name == REVIEW_DIR_NAME;
+            if is_review_dir {
+                return false;
+            }
+            let reason = if scan_options.is_excluded_dir(&name) {
+                SkipReason::ExcludedDir
+            } else if let Some(exclude) = &dir_exclude
+                && exclude.is_match(entry.path().strip_prefix(&root).unwrap_or(entry.path()))
+            {
+                SkipReason::ExcludedPattern
+            } else {
+                return true;
+            };
+            if let Ok(mut excluded) = excluded.lock() {
+                excluded.push((entry.path().to_path_buf(), reason));
+            }
+            false
+        })
+        .build();
+    for entry in walker {
+        match entry {
+            Ok(e) => {
+                if last_report.elapsed() >= PROGRESS_INTERVAL {
+                    on_progress(progress);
+                    last_report = Instant::now();
+                }
+                if e.file_type().is_some_and(|t| t.is_dir()) {
+                    progress.dirs_visited += 1;
+                    continue;
+                }
+                // 本程序自己的锁文件不算跳过的文件
+                if !e.file_type().is_some_and(|t| t.is_file()) || e.file_name() == LOCK_FILE_NAME {
+                    continue;
+                }
+                match ImageFormat::from_path(e.path()) {
+                    Ok(_)
+                        if review::original_of(e.path(), &options.output.keep_both_suffix)
+                            .is_some() =>
+                    {
+                        result
+                            .skipped
+                            .push((e.into_path(), SkipReason::PendingReview));
+                        continue;
+                    }
+                    Ok(format) if options.is_enabled(format) => {}
+                    Ok(
+                        ImageFormat::Jpeg
+                        | ImageFormat::Png
+                        | ImageFormat::WebP
+                        | ImageFormat::Avif
+                        | ImageFormat::Gif,
+                    ) => {
+                        result
+                            .skipped
+                            .push((e.into_path(), SkipReason::FormatDisabled));
+                        continue;
+                    }
+                    _ => {
+                        result
+                            .skipped
+                            .push((e.into_path(), SkipReason::Unsupported));
+                        continue;
+                    }
+                }
+                let relative = e.path().strip_prefix(folder).unwrap_or(e.path());
+                if let Some(include) = &include
+                    && !include.is_match(relative)
+                {
+                    result.not_included += 1;
+                    result
+                        .skipped
+                        .push((e.into_path(), SkipReason::NotIncluded));
+                    continue;
+                }
+                if let Some(exclude) = &exclude
+                    && exclude.is_match(relative)
+                {
+                    result
+                        .skipped
+                        .push((e.into_path(), SkipReason::ExcludedPattern));
+                    continue;
+                }
+                let size = e.metadata().map(|m| m.len()).unwrap_or(0);
+                if size < min_size {
+                    result.skipped.push((e.into_path(), SkipReason::TooSmall));
+                    continue;
+                }
+                progress.files_found += 1;
+                progress.total_bytes += size;
+                result.files.push(e.into_path());
+            }
+            Err(err) => result.errors.push(err.into()),
+        }
+    }
+    on_progress(progress);
+    if let Ok(mut excluded) = excluded_dirs.lock() {
+        result.skipped.append(&mut excluded);
+    }
+    result
+}
+
+/// 包含列表为空时返回 None，表示不过滤
+pub fn include_set(options: &ScanOptions) -> Result<Option<GlobSet>> {
+    glob_set(&options.include_patterns, false)
+}
+
+/// 排除列表为空时返回 None
+pub fn exclude_set(options: &ScanOptions) -> Result<Option<GlobSet>> {
+    glob_set(&options.exclude_patterns, true)
+}
+
+fn glob_set(patterns: &[String], exclude: bool) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            Glob::new(pattern).map_err(|err| ConfigError::InvalidPattern {
+                exclude,
+                pattern: pattern.clone(),
+                detail: err.kind().to_string(),
+            })?,
+        );
+    }
+    Ok(Some(builder.build()?))
+}
+
+pub fn is_supported_image(path: &Path, options: &CompressionOptions) -> bool {
+    ImageFormat::from_path(path)
+        .map(|format| options.is_enabled(format))
+        .unwrap_or(false)
+}
+
+/// 在整个列表中均匀间隔地取最多 limit 个，避免只抽到同一个子目录
+pub fn sample_evenly(files: &[PathBuf], limit: usize) -> Vec<PathBuf> {
+    let step = (files.len() / limit.max(1)).max(1);
+    files.iter().step_by(step).take(limit).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OutputOptions;
+    use std::fs;
+
+    /// 在临时目录下按 (相对路径, 字节数) 建立文件，扫描只看扩展名，内容无关紧要
+    fn tree(name: &str, files: &[(&str, usize)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("compress_img_scan_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, size) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0u8; *size]).unwrap();
+        }
+        root
+    }
+
+    fn relative(root: &Path, paths: impl IntoIterator<Item = PathBuf>) -> Vec<String> {
+        let mut paths: Vec<String> = paths
+            .into_iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn reason_of(result: &ScanResult, root: &Path, path: &str) -> Option<SkipReason> {
+        let path = root.join(path);
+        result
+            .skipped
+            .iter()
+            .find(|(skipped, _)| *skipped == path)
+            .map(|(_, reason)| *reason)
+    }
+
+    #[test]
+    fn skips_small_unsupported_and_excluded_files() {
+        let root = tree(
+            "filters",
+            &[
+                ("a.jpg", 2048),
+                ("tiny.png", 10),
+                ("notes.txt", 2048),
+                ("sub/b.png", 2048),
+                ("sub/thumbs/t.jpg", 2048),
+                ("node_modules/x.png", 2048),
+            ],
+        );
+        let options = CompressionOptions {
+            scan: ScanOptions {
+                min_size_kb: 1,
+                exclude_patterns: vec!["**/thumbs/**".to_string()],
+                ..ScanOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+
+        let result = scan_folder(&root, &options);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            relative(&root, result.files.clone()),
+            ["a.jpg", "sub/b.png"]
+        );
+        assert_eq!(
+            reason_of(&result, &root, "tiny.png"),
+            Some(SkipReason::TooSmall)
+        );
+        assert_eq!(
+            reason_of(&result, &root, "notes.txt"),
+            Some(SkipReason::Unsupported)
+        );
+        assert_eq!(
+            reason_of(&result, &root, "node_modules"),
+            Some(SkipReason::ExcludedDir)
+        );
+        // 排除规则可能在文件夹上命中，也可能在其中的文件上命中
+        assert!(result.skipped.iter().any(|(path, reason)| {
+            path.starts_with(root.join("sub/thumbs")) && *reason == SkipReason::ExcludedPattern
+        }));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn include_patterns_and_depth_limit_the_scan() {
+        let root = tree(
+            "include",
+            &[
+                ("top.jpg", 16),
+                ("assets/a.png", 16),
+                ("assets/deep/b.jpg", 16),
+                ("other/c.jpg", 16),
+            ],
+        );
+        let include = CompressionOptions {
+            scan: ScanOptions {
+                include_patterns: vec!["assets/**".to_string()],
+                ..ScanOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+        let result = scan_folder(&root, &include);
+        assert_eq!(
+            relative(&root, result.files.clone()),
+            ["assets/a.png", "assets/deep/b.jpg"]
+        );
+        assert_eq!(result.not_included, 2);
+        assert_eq!(
+            reason_of(&result, &root, "other/c.jpg"),
+            Some(SkipReason::NotIncluded)
+        );
+
+        let shallow = CompressionOptions {
+            scan: ScanOptions {
+                max_depth: 1,
+                ..ScanOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+        assert_eq!(
+            relative(&root, scan_folder(&root, &shallow).files),
+            ["assets/a.png", "other/c.jpg", "top.jpg"]
+        );
+
+        let flat = CompressionOptions {
+            scan: ScanOptions {
+                recursive: false,
+                ..ScanOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+        assert_eq!(
+            relative(&root, scan_folder(&root, &flat).files),
+            ["top.jpg"]
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn invalid_pattern_scans_nothing() {
+        let root = tree("invalid", &[("a.jpg", 16)]);
+        let options = CompressionOptions {
+            scan: ScanOptions {
+                exclude_patterns: vec!["[".to_string()],
+                ..ScanOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+        let result = scan_folder(&root, &options);
+        assert!(result.files.is_empty());
+        assert_eq!(result.errors.len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn output_folder_and_lock_file_are_left_out() {
+        let root = tree(
+            "output",
+            &[("a.jpg", 16), ("out/a.jpg", 16), (LOCK_FILE_NAME, 0)],
+        );
+        // 写法与遍历到的路径不同，仍是同一个文件夹
+        let options = CompressionOptions {
+            output: OutputOptions {
+                folder: Some(root.join("sub").join("..").join("out")),
+                ..OutputOptions::default()
+            },
+            ..CompressionOptions::default()
+        };
+        fs::create_dir_all(root.join("sub")).unwrap();
+
+        let result = scan_folder(&root, &options);
+        assert_eq!(relative(&root, result.files), ["a.jpg"]);
+        assert!(result.skipped.is_empty(), "{:?}", result.skipped);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,203 @@
+//! 目标大小模式：在质量上二分搜索，找到不超过体积上限的最高质量；
+//! 最低质量仍然超出时，按设置逐步缩小尺寸后再搜索。
+
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+
+use crate::codec;
+use crate::metadata::ImageMetadata;
+use crate::options::CompressionOptions;
+
+/// 搜索的质量下限，再低画面已明显损坏
+const MIN_QUALITY: u8 = 10;
+/// 最多缩小的次数
+const MAX_DOWNSCALE_STEPS: usize = 6;
+/// 缩小时不低于这个边长
+const MIN_DIMENSION: u32 = 16;
+
+pub struct SizedOutput {
+    pub bytes: Vec<u8>,
+    /// 最终使用的参数，质量已换成搜索的结果
+    pub options: CompressionOptions,
+    /// 缩小后的尺寸，没有缩小时为 None
+    pub resized: Option<(u32, u32)>,
+    /// 是否达到了上限
+    pub reached: bool,
+}
+
+impl SizedOutput {
+    pub fn describe(&self, format: ImageFormat, max_bytes: u64) -> String {
+        let mut text = self.options.describe_format(format);
+        if let Some((width, height)) = self.resized {
+            text.push_str(&format!(" 缩小到 {width}x{height}"));
+        }
+        let limit = max_bytes / 1024;
+        if self.reached {
+            text.push_str(&format!("（目标 {limit} KB）"));
+        } else {
+            text.push_str(&format!("（未能达到目标 {limit} KB）"));
+        }
+        text
+    }
+}
+
+/// 编码为不超过 max_bytes 的 format；达不到时返回尝试过的最小结果。
+/// 写入的 metadata 也计入体积
+pub fn encode_to_size(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &CompressionOptions,
+    metadata: &ImageMetadata,
+    max_bytes: u64,
+) -> Result<SizedOutput> {
+    let mut output = search_quality(image, format, options, metadata, max_bytes)?;
+    if output.reached || !options.target_size.allow_downscale {
+        return Ok(output);
+    }
+
+    let (mut width, mut height) = image.dimensions();
+    for _ in 0..MAX_DOWNSCALE_STEPS {
+        // 体积大致与像素数成正比，按面积比例缩小，再留一点余量
+        let ratio = (max_bytes as f64 / output.bytes.len() as f64).sqrt() * 0.9;
+        let ratio = ratio.clamp(0.1, 0.9);
+        width = ((width as f64 * ratio) as u32).max(MIN_DIMENSION);
+        height = ((height as f64 * ratio) as u32).max(MIN_DIMENSION);
+        let resized = image.resize_exact(width, height, FilterType::Lanczos3);
+        let mut attempt = search_quality(&resized, format, options, metadata, max_bytes)?;
+        attempt.resized = Some((width, height));
+        let smallest = width == MIN_DIMENSION || height == MIN_DIMENSION;
+        output = attempt;
+        if output.reached || smallest {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+// 先试设置的质量，超出时在 [MIN_QUALITY, 设置的质量) 中二分
+fn search_quality(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &CompressionOptions,
+    metadata: &ImageMetadata,
+    max_bytes: u64,
+) -> Result<SizedOutput> {
+    let encode = |quality: Option<u8>| -> Result<(Vec<u8>, CompressionOptions)> {
+        let mut adjusted = options.clone();
+        if let Some(quality) = quality {
+            adjusted.set_quality(format, quality);
+        }
+        Ok((
+            codec::encode_with_metadata(image, format, &adjusted, metadata)?,
+            adjusted,
+        ))
+    };
+
+    let initial = options.quality(format);
+    let (bytes, used) = encode(initial)?;
+    if bytes.len() as u64 <= max_bytes {
+        return Ok(sized(bytes, used, true));
+    }
+    let Some(initial) = initial.filter(|quality| *quality > MIN_QUALITY) else {
+        return Ok(sized(bytes, used, false));
+    };
+
+    let mut smallest = (bytes, used);
+    let mut best = None;
+    let (mut low, mut high) = (MIN_QUALITY, initial - 1);
+    while low <= high {
+        let middle = low + (high - low) / 2;
+        let (bytes, used) = encode(Some(middle))?;
+        if bytes.len() as u64 <= max_bytes {
+            best = Some((bytes, used));
+            low = middle + 1;
+        } else {
+            if bytes.len() < smallest.0.len() {
+                smallest = (bytes, used);
+            }
+            if middle == MIN_QUALITY {
+                break;
+            }
+            high = middle - 1;
+        }
+    }
+    Ok(match best {
+        Some((bytes, used)) => sized(bytes, used, true),
+        None => sized(smallest.0, smallest.1, false),
+    })
+}
+
+fn sized(bytes: Vec<u8>, options: CompressionOptions, reached: bool) -> SizedOutput {
+    SizedOutput {
+        bytes,
+        options,
+        resized: None,
+        reached,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    // 带噪点的图像，体积随质量明显变化
+    fn noisy() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(96, 64, |x, y| {
+            let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)) % 64;
+            Rgb([
+                (x * 2 + noise) as u8,
+                (y * 3 + noise) as u8,
+                (noise * 3) as u8,
+            ])
+        }))
+    }
+
+    fn search(max_bytes: u64) -> (SizedOutput, CompressionOptions) {
+        let options = CompressionOptions::default();
+        let output = search_quality(
+            &noisy(),
+            ImageFormat::Jpeg,
+            &options,
+            &ImageMetadata::default(),
+            max_bytes,
+        )
+        .unwrap();
+        (output, options)
+    }
+
+    #[test]
+    fn keeps_configured_quality_when_it_fits() {
+        let (output, options) = search(u64::MAX);
+        assert!(output.reached);
+        assert_eq!(output.options.jpeg.quality, options.jpeg.quality);
+    }
+
+    #[test]
+    fn finds_a_lower_quality_under_the_limit() {
+        let (full, options) = search(u64::MAX);
+        let limit = full.bytes.len() as u64 * 2 / 3;
+        let (output, _) = search(limit);
+        assert!(output.reached);
+        assert!(output.bytes.len() as u64 <= limit);
+        let quality = output.options.jpeg.quality;
+        assert!((MIN_QUALITY..options.jpeg.quality).contains(&quality));
+        // 返回的参数能重现同样的结果
+        let again = codec::encode_with_metadata(
+            &noisy(),
+            ImageFormat::Jpeg,
+            &output.options,
+            &ImageMetadata::default(),
+        )
+        .unwrap();
+        assert_eq!(again.len(), output.bytes.len());
+    }
+
+    #[test]
+    fn unreachable_limit_returns_the_smallest_attempt() {
+        let (output, _) = search(16);
+        assert!(!output.reached);
+        assert_eq!(output.options.jpeg.quality, MIN_QUALITY);
+    }
+}
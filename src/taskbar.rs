@@ -0,0 +1,102 @@
+//! 在 Windows 任务栏图标上显示批量处理进度，窗口最小化时也能看到。
+//! 其他平台上所有操作都是空操作。
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskbarState {
+    /// 不显示进度
+    None,
+    /// 扫描中，总数未知
+    Indeterminate,
+    Normal,
+    /// 有文件处理失败，进度条变红
+    Error,
+    /// 等待用户确认，进度条变黄
+    Paused,
+}
+
+pub struct TaskbarProgress {
+    #[cfg(windows)]
+    inner: Option<win32::Taskbar>,
+}
+
+impl TaskbarProgress {
+    /// window 需已显示，否则还没有对应的系统窗口，之后的调用都不起作用
+    #[cfg_attr(not(windows), allow(unused_variables))]
+    pub fn new(window: &slint::Window) -> Self {
+        Self {
+            #[cfg(windows)]
+            inner: win32::Taskbar::new(window),
+        }
+    }
+
+    /// progress 为 0.0-1.0，只在 Normal、Error、Paused 状态下显示
+    #[cfg_attr(not(windows), allow(unused_variables))]
+    pub fn update(&self, state: TaskbarState, progress: f32) {
+        #[cfg(windows)]
+        if let Some(taskbar) = &self.inner {
+            taskbar.update(state, progress);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::{
+        ITaskbarList3, TaskbarList, TBPFLAG, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS,
+        TBPF_NORMAL, TBPF_PAUSED,
+    };
+
+    use super::TaskbarState;
+
+    // SetProgressValue 的总数，进度按千分比传入
+    const SCALE: u64 = 1000;
+
+    pub struct Taskbar {
+        list: ITaskbarList3,
+        hwnd: HWND,
+    }
+
+    impl Taskbar {
+        pub fn new(window: &slint::Window) -> Option<Self> {
+            let handle = window.window_handle();
+            let RawWindowHandle::Win32(win32) = handle.window_handle().ok()?.as_raw() else {
+                return None;
+            };
+            let hwnd = HWND(win32.hwnd.get() as *mut _);
+            // 界面线程一般已由窗口库初始化过 COM，重复初始化的返回值可以忽略
+            unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                let list: ITaskbarList3 =
+                    CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER).ok()?;
+                list.HrInit().ok()?;
+                Some(Self { list, hwnd })
+            }
+        }
+
+        pub fn update(&self, state: TaskbarState, progress: f32) {
+            let flag: TBPFLAG = match state {
+                TaskbarState::None => TBPF_NOPROGRESS,
+                TaskbarState::Indeterminate => TBPF_INDETERMINATE,
+                TaskbarState::Normal => TBPF_NORMAL,
+                TaskbarState::Error => TBPF_ERROR,
+                TaskbarState::Paused => TBPF_PAUSED,
+            };
+            let completed = (progress.clamp(0.0, 1.0) * SCALE as f32) as u64;
+            // 任务栏显示失败不影响处理，忽略错误
+            unsafe {
+                if matches!(
+                    state,
+                    TaskbarState::Normal | TaskbarState::Error | TaskbarState::Paused
+                ) {
+                    let _ = self.list.SetProgressValue(self.hwnd, completed, SCALE);
+                }
+                let _ = self.list.SetProgressState(self.hwnd, flag);
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! 质量对比：按当前设置在内存中重新编码一张图像，截取原图和结果的同一区域并排显示，
+//! 调整质量时可以直接看到效果。不写入任何文件。
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::fs;
+use std::path::Path;
+
+use crate::options::CompressionOptions;
+use crate::profile::ContentProfile;
+use crate::{animation, classify, codec, compress_buffer, metadata};
+
+/// 对比图的最大边长，区域更大时缩小显示
+const VIEW_SIZE: u32 = 720;
+
+pub struct Comparison {
+    pub original: RgbaImage,
+    pub compressed: RgbaImage,
+    /// 实际编码成的格式
+    pub format: ImageFormat,
+    pub original_size: u64,
+    pub new_size: u64,
+}
+
+/// 源文件的格式和按 options 压缩时会编码成的格式，按文件内容判断
+pub fn target_format(
+    path: &Path,
+    options: &CompressionOptions,
+) -> Result<(ImageFormat, ImageFormat)> {
+    let format = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .with_context(|| format!("无法读取文件: {}", path.display()))?
+        .format()
+        .with_context(|| format!("无法确定图像格式: {}", path.display()))?;
+    Ok((format, options.for_source(format).output_format(format)))
+}
+
+/// zoom 为 1 时显示整张图，为 n 时显示中央 1/n 宽高的区域
+pub fn compare(path: &Path, options: &CompressionOptions, zoom: u32) -> Result<Comparison> {
+    let input = fs::read(path).with_context(|| format!("无法读取文件: {}", path.display()))?;
+    let (format, mut original) = codec::decode_buffer(&input)?;
+    // 用户明确选了这张图，不受格式开关影响
+    let mut options = options.clone();
+    options.jpeg.enabled = true;
+    options.png.enabled = true;
+    options.webp.enabled = true;
+    options.avif.enabled = true;
+    options.animation.gif_enabled = true;
+    // 与批量处理一样按内容类型换用质量参数，并按 EXIF 方向摆正原图，两边才对得上
+    let animated = animation::is_animation(&input, format);
+    if options.auto_tune.enabled && !animated {
+        ContentProfile::from(classify::classify(&original))
+            .bundle()
+            .apply_to(&mut options);
+    }
+    if !animated {
+        metadata::auto_orient(&input, &mut original, &options.metadata);
+    }
+    let output = compress_buffer(&input, &options)?;
+    let (_, compressed) = codec::decode_buffer(&output)?;
+    Ok(Comparison {
+        original: view(&original, zoom),
+        // 缩小尺寸后按比例截取，仍是同一块区域
+        compressed: view(&compressed, zoom),
+        format: options.for_source(format).output_format(format),
+        original_size: input.len() as u64,
+        new_size: output.len() as u64,
+    })
+}
+
+fn view(image: &DynamicImage, zoom: u32) -> RgbaImage {
+    let zoom = zoom.max(1);
+    let width = (image.width() / zoom).max(1);
+    let height = (image.height() / zoom).max(1);
+    let region = image.crop_imm(
+        (image.width() - width) / 2,
+        (image.height() - height) / 2,
+        width,
+        height,
+    );
+    if width.max(height) > VIEW_SIZE {
+        region
+            .resize(VIEW_SIZE, VIEW_SIZE, FilterType::Triangle)
+            .to_rgba8()
+    } else {
+        region.to_rgba8()
+    }
+}
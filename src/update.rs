@@ -0,0 +1,58 @@
+//! 新版本检查：读取 GitHub 上最新的正式 release，与当前版本比较
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::time::Duration;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/WangHaoZhengMing/compress_img/releases/latest";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub url: String,
+}
+
+pub fn current_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+/// 有比当前更新的版本时返回它，已是最新时返回 None
+pub fn check_for_update() -> Result<Option<UpdateInfo>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into();
+    let release: GithubRelease = agent
+        .get(LATEST_RELEASE_URL)
+        .header("Accept", "application/vnd.github+json")
+        .header(
+            "User-Agent",
+            concat!("compress_img/", env!("CARGO_PKG_VERSION")),
+        )
+        .call()
+        .context("无法连接更新服务器")?
+        .body_mut()
+        .read_json()
+        .context("无法解析版本信息")?;
+
+    let latest = parse_version(&release.tag_name)
+        .with_context(|| format!("无法识别的版本号: {}", release.tag_name))?;
+    let current = parse_version(current_version())?;
+    Ok((latest > current).then(|| UpdateInfo {
+        version: latest.to_string(),
+        url: release.html_url,
+    }))
+}
+
+fn parse_version(tag: &str) -> Result<Version> {
+    let text = tag.trim().trim_start_matches(['v', 'V']);
+    Ok(Version::parse(text)?)
+}
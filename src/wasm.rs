@@ -0,0 +1,23 @@
+use wasm_bindgen::prelude::*;
+
+use crate::options::CompressionOptions;
+
+/// 压缩一张图像。options_json 为空时使用默认设置，格式与 CompressionOptions 的 JSON 一致。
+#[wasm_bindgen]
+pub fn compress(input: &[u8], options_json: &str) -> Result<Vec<u8>, JsError> {
+    let options = if options_json.trim().is_empty() {
+        CompressionOptions::default()
+    } else {
+        CompressionOptions::from_json(options_json).map_err(to_js_error)?
+    };
+    crate::compress_buffer(input, &options).map_err(to_js_error)
+}
+
+#[wasm_bindgen(js_name = defaultOptions)]
+pub fn default_options() -> Result<String, JsError> {
+    CompressionOptions::default().to_json().map_err(to_js_error)
+}
+
+fn to_js_error(err: anyhow::Error) -> JsError {
+    JsError::new(&format!("{err:#}"))
+}
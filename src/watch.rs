@@ -0,0 +1,190 @@
+//! 监视文件夹：新增或修改的图像在一段时间内不再变化后自动压缩，处理方式与手动运行相同。
+//! 压缩写出的文件记下大小和修改时间，之后再收到它们的事件时不处理，避免反复压缩。
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::batch::{self, BatchEvent, BatchSummary, FileOutcome, FileOverrides};
+use crate::control::RunControl;
+use crate::lock::LockPolicy;
+use crate::options::{CompressionOptions, TargetMode};
+use crate::scan;
+
+/// 文件最后一次变化后等待这么久才处理，截图、下载等通常分几次写完
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum WatchEvent {
+    FileFinished {
+        path: PathBuf,
+        outcome: FileOutcome,
+    },
+    /// 一批文件处理完成
+    BatchFinished(BatchSummary),
+    Error(String),
+}
+
+/// 停止或丢弃时不再开始新的文件，正在处理的文件完成后监视线程退出
+pub struct FolderWatcher {
+    control: Arc<RunControl>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+impl FolderWatcher {
+    /// 开始监视 folder，只处理此后新增或修改的图像；options.scan.recursive 决定是否包括子文件夹。
+    /// on_event 在监视线程中调用
+    pub fn start(
+        folder: &Path,
+        options: &CompressionOptions,
+        on_event: impl FnMut(WatchEvent) + Send + 'static,
+    ) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .context("无法创建文件监视")?;
+        let mode = if options.scan.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(folder, mode)
+            .with_context(|| format!("无法监视文件夹: {}", folder.display()))?;
+
+        let control = Arc::new(RunControl::default());
+        let folder = folder.to_path_buf();
+        let options = watch_options(options);
+        thread::spawn({
+            let control = Arc::clone(&control);
+            move || {
+                // 监视线程退出时才停止接收事件
+                let _watcher = watcher;
+                watch_loop(&folder, &options, &receiver, &control, on_event);
+            }
+        });
+        Ok(Self { control })
+    }
+
+    pub fn stop(&self) {
+        self.control.cancel();
+    }
+}
+
+impl Drop for FolderWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 文件已经等到不再变化才处理，不再按修改时间推迟或跳过，也不只挑最大的文件
+fn watch_options(options: &CompressionOptions) -> CompressionOptions {
+    let mut options = options.clone();
+    options.scan.incremental = false;
+    options.scan.min_age_minutes = 0;
+    options.scan.target = TargetMode::All;
+    options
+}
+
+fn watch_loop(
+    folder: &Path,
+    options: &CompressionOptions,
+    receiver: &Receiver<notify::Result<notify::Event>>,
+    control: &RunControl,
+    mut on_event: impl FnMut(WatchEvent),
+) {
+    // 最后一次收到事件的时间
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    // 本次监视中压缩写出的文件
+    let mut written: HashMap<PathBuf, FileStamp> = HashMap::new();
+    while !control.is_cancelled() {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if scan::is_supported_image(&path, options) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => on_event(WatchEvent::Error(format!("文件监视出错: {err}"))),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let mut ready = HashSet::new();
+        pending.retain(|path, changed| {
+            let settled = now.duration_since(*changed) >= SETTLE_TIME;
+            if settled {
+                ready.insert(path.clone());
+            }
+            !settled
+        });
+        // 大小和修改时间都没变，说明是自己写出之后没有再被改动过
+        ready.retain(|path| {
+            path.is_file()
+                && written
+                    .get(path)
+                    .is_none_or(|stamp| FileStamp::read(path) != Some(*stamp))
+        });
+        if ready.is_empty() {
+            continue;
+        }
+
+        let overrides = FileOverrides {
+            only: Some(ready),
+            ..FileOverrides::default()
+        };
+        let result = batch::run_batch_with(
+            folder,
+            options,
+            LockPolicy::Wait,
+            control,
+            &overrides,
+            |_| true,
+            |event| {
+                let BatchEvent::FileFinished { path, outcome, .. } = event else {
+                    return;
+                };
+                if let FileOutcome::Compressed(stats) = &outcome {
+                    for output in [Some(&path), stats.output.as_ref()].into_iter().flatten() {
+                        if let Some(stamp) = FileStamp::read(output) {
+                            written.insert(output.clone(), stamp);
+                        }
+                    }
+                }
+                on_event(WatchEvent::FileFinished { path, outcome });
+            },
+        );
+        match result {
+            Ok(summary) if summary.processed() > 0 => {
+                on_event(WatchEvent::BatchFinished(summary));
+            }
+            Ok(_) => {}
+            Err(err) => on_event(WatchEvent::Error(format!("{err:#}"))),
+        }
+    }
+}